@@ -0,0 +1,13 @@
+-- Add migration script here
+
+/* Stack all score transfers */
+CREATE TABLE IF NOT EXISTS TransferHistory
+(
+    guild_id integer NOT NULL,
+    sender_id integer NOT NULL,
+    receiver_id integer NOT NULL,
+    amount integer NOT NULL,
+    sender_score integer NOT NULL,
+    receiver_score integer NOT NULL,
+    transfer_time integer NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS GuildSetting
+(
+    guild_id integer NOT NULL,
+    timezone nvarchar NOT NULL DEFAULT 'Asia/Seoul',
+    day_start_hour integer NOT NULL DEFAULT 6,
+    primary key(guild_id)
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS ScoringRule
+(
+    guild_id integer NOT NULL,
+    rank_points nvarchar NOT NULL DEFAULT '10,5,3,1',
+    anniversary_bonus integer NOT NULL DEFAULT 1500,
+    primary key(guild_id)
+);
+
+/* kind is either 'modulo' or 'exact' */
+CREATE TABLE IF NOT EXISTS ComboBonus
+(
+    guild_id integer NOT NULL,
+    kind nvarchar NOT NULL,
+    combo integer NOT NULL,
+    bonus integer NOT NULL,
+    primary key(guild_id, kind, combo)
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+
+/* Last known display name, used when the member can not be resolved from Discord */
+ALTER TABLE Member ADD COLUMN display_name nvarchar;
+ALTER TABLE Member ADD COLUMN departed integer NOT NULL DEFAULT 0;
+
+ALTER TABLE GuildSetting ADD COLUMN hide_departed integer NOT NULL DEFAULT 0;
@@ -0,0 +1,22 @@
+-- Add migration script here
+
+/* Points earned by each attendance, hit_score is the score after it */
+ALTER TABLE AttendanceHistory ADD COLUMN hit_point integer NOT NULL DEFAULT 0;
+
+/* Recover the points of past attendances from the previous attendance and the transfers since then */
+UPDATE AttendanceHistory SET hit_point = hit_score
+    - COALESCE((SELECT p.hit_score FROM AttendanceHistory p
+        WHERE p.guild_id=AttendanceHistory.guild_id AND p.user_id=AttendanceHistory.user_id
+        AND p.hit_time < AttendanceHistory.hit_time ORDER BY p.hit_time DESC LIMIT 1), 0)
+    - COALESCE((SELECT SUM(t.amount) FROM TransferHistory t
+        WHERE t.guild_id=AttendanceHistory.guild_id AND t.receiver_id=AttendanceHistory.user_id
+        AND t.transfer_time < AttendanceHistory.hit_time
+        AND t.transfer_time >= COALESCE((SELECT MAX(p.hit_time) FROM AttendanceHistory p
+            WHERE p.guild_id=AttendanceHistory.guild_id AND p.user_id=AttendanceHistory.user_id
+            AND p.hit_time < AttendanceHistory.hit_time), 0)), 0)
+    + COALESCE((SELECT SUM(t.amount) FROM TransferHistory t
+        WHERE t.guild_id=AttendanceHistory.guild_id AND t.sender_id=AttendanceHistory.user_id
+        AND t.transfer_time < AttendanceHistory.hit_time
+        AND t.transfer_time >= COALESCE((SELECT MAX(p.hit_time) FROM AttendanceHistory p
+            WHERE p.guild_id=AttendanceHistory.guild_id AND p.user_id=AttendanceHistory.user_id
+            AND p.hit_time < AttendanceHistory.hit_time), 0)), 0);
@@ -0,0 +1,7 @@
+-- Add migration script here
+
+/* Start of the last attendance day the daily reset was done for */
+ALTER TABLE AttendanceTimeCount ADD COLUMN reset_time integer NOT NULL DEFAULT 0;
+
+/* Channel to post the summary of the previous day in */
+ALTER TABLE GuildSetting ADD COLUMN summary_channel integer;
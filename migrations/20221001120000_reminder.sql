@@ -0,0 +1,17 @@
+-- Add migration script here
+
+/* Members who opted in to a reminder before their combo breaks */
+CREATE TABLE IF NOT EXISTS Reminder
+(
+    guild_id integer NOT NULL,
+    user_id integer NOT NULL,
+    /* Local time of day in minutes from midnight */
+    remind_time integer NOT NULL,
+    delivery nvarchar NOT NULL DEFAULT 'dm',
+    last_reminded integer NOT NULL DEFAULT 0,
+    primary key(guild_id, user_id)
+);
+
+ALTER TABLE GuildSetting ADD COLUMN reminder_enabled integer NOT NULL DEFAULT 0;
+ALTER TABLE GuildSetting ADD COLUMN reminder_channel integer;
+ALTER TABLE GuildSetting ADD COLUMN reminder_min_combo integer NOT NULL DEFAULT 3;
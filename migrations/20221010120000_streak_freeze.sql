@@ -0,0 +1,22 @@
+-- Add migration script here
+
+/* Streak freezes which protect the combo for a missed day each */
+ALTER TABLE Member ADD COLUMN freezes integer NOT NULL DEFAULT 0;
+
+/* freeze_every is the combo interval a freeze is earned at, 0 to never earn one */
+ALTER TABLE ScoringRule ADD COLUMN freeze_price integer NOT NULL DEFAULT 100;
+ALTER TABLE ScoringRule ADD COLUMN freeze_every integer NOT NULL DEFAULT 30;
+ALTER TABLE ScoringRule ADD COLUMN max_freezes integer NOT NULL DEFAULT 2;
+
+/* kind is 'buy', 'earn' or 'use'. score is the score after the event and
+   frozen_day is the start of the missed day a 'use' covered */
+CREATE TABLE IF NOT EXISTS FreezeHistory
+(
+    guild_id integer NOT NULL,
+    user_id integer NOT NULL,
+    kind nvarchar NOT NULL,
+    count integer NOT NULL,
+    score integer NOT NULL,
+    frozen_day integer,
+    event_time integer NOT NULL
+);
@@ -0,0 +1,30 @@
+-- Add migration script here
+
+/* stock is NULL for unlimited items, role_id is the Discord role granted to the buyer and
+   duration is the number of days a purchase lasts, NULL to never expire */
+CREATE TABLE IF NOT EXISTS ShopItem
+(
+    guild_id integer NOT NULL,
+    name nvarchar NOT NULL,
+    price integer NOT NULL,
+    stock integer,
+    role_id integer,
+    duration integer,
+    PRIMARY KEY (guild_id, name)
+);
+
+/* Purchases keep the item as it was sold. score is the score of the buyer after the purchase and
+   expired is set once the purchase passed expire_time and its role was taken back */
+CREATE TABLE IF NOT EXISTS Inventory
+(
+    purchase_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL,
+    user_id integer NOT NULL,
+    name nvarchar NOT NULL,
+    price integer NOT NULL,
+    role_id integer,
+    score integer NOT NULL,
+    purchase_time integer NOT NULL,
+    expire_time integer,
+    expired boolean NOT NULL DEFAULT 0
+);
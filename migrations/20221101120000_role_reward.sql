@@ -0,0 +1,13 @@
+-- Add migration script here
+
+/* kind is 'score' or 'combo'. A member holds role_id while the score or the alive combo is at
+   least threshold, and loses it when the condition breaks only if revoke is set */
+CREATE TABLE IF NOT EXISTS RoleReward
+(
+    guild_id integer NOT NULL,
+    role_id integer NOT NULL,
+    kind nvarchar NOT NULL,
+    threshold integer NOT NULL,
+    revoke boolean NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, role_id)
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+
+/* achievement is the id of a badge in the catalog of nalgang-core, achieved_time is the time of
+   the attendance which earned it */
+CREATE TABLE IF NOT EXISTS MemberAchievement
+(
+    guild_id integer NOT NULL,
+    user_id integer NOT NULL,
+    achievement nvarchar NOT NULL,
+    achieved_time integer NOT NULL,
+    PRIMARY KEY (guild_id, user_id, achievement)
+);
@@ -0,0 +1,16 @@
+-- Add migration script here
+
+/* Append-only log of the changes admins made to members. field is 'score' or 'combo' and
+   before and after are its values around the change */
+CREATE TABLE IF NOT EXISTS AuditLog
+(
+    audit_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL,
+    actor_id integer NOT NULL,
+    target_id integer NOT NULL,
+    field nvarchar NOT NULL,
+    before integer NOT NULL,
+    after integer NOT NULL,
+    reason nvarchar NOT NULL,
+    event_time integer NOT NULL
+);
@@ -0,0 +1,107 @@
+-- Add migration script here
+
+/* Registered guilds, which every per guild table refers to */
+CREATE TABLE IF NOT EXISTS Guild
+(
+    guild_id integer NOT NULL PRIMARY KEY
+);
+
+INSERT OR IGNORE INTO Guild (guild_id)
+    SELECT guild_id FROM AttendanceTimeCount
+    UNION SELECT guild_id FROM Member
+    UNION SELECT guild_id FROM DailyAttendance
+    UNION SELECT guild_id FROM AttendanceHistory;
+
+/* Duplicated rows were written together, and reads took the first one of them, so the row with
+   the smallest rowid is kept. */
+CREATE TABLE Member_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    score integer NOT NULL DEFAULT 0,
+    combo integer NOT NULL DEFAULT 0,
+    hit_time integer NOT NULL DEFAULT 0,
+    display_name nvarchar,
+    departed integer NOT NULL DEFAULT 0,
+    freezes integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+INSERT INTO Member_new (guild_id, user_id, score, combo, hit_time, display_name, departed, freezes)
+    SELECT guild_id, user_id, score, combo, hit_time, display_name, departed, freezes FROM Member
+    WHERE rowid IN (SELECT MIN(rowid) FROM Member GROUP BY guild_id, user_id);
+
+/* Attendances of members without a Member row are kept with departed members. */
+INSERT OR IGNORE INTO Member_new (guild_id, user_id, departed)
+    SELECT guild_id, user_id, 1 FROM DailyAttendance
+    UNION SELECT guild_id, user_id, 1 FROM AttendanceHistory;
+
+DROP TABLE Member;
+ALTER TABLE Member_new RENAME TO Member;
+CREATE INDEX Member_score ON Member (guild_id, score);
+
+CREATE TABLE AttendanceTimeCount_new
+(
+    guild_id integer NOT NULL PRIMARY KEY REFERENCES Guild (guild_id),
+    hit_count integer NOT NULL DEFAULT 0,
+    hit_time integer NOT NULL DEFAULT 0,
+    reset_time integer NOT NULL DEFAULT 0
+);
+
+INSERT INTO AttendanceTimeCount_new (guild_id, hit_count, hit_time, reset_time)
+    SELECT guild_id, hit_count, hit_time, reset_time FROM AttendanceTimeCount
+    WHERE rowid IN (SELECT MIN(rowid) FROM AttendanceTimeCount GROUP BY guild_id);
+
+/* Guilds known only from their members are registered. */
+INSERT OR IGNORE INTO AttendanceTimeCount_new (guild_id) SELECT guild_id FROM Guild;
+
+DROP TABLE AttendanceTimeCount;
+ALTER TABLE AttendanceTimeCount_new RENAME TO AttendanceTimeCount;
+
+/* A member attends at most once a second, so identical attendances are duplicates. */
+CREATE TABLE DailyAttendance_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    hit_message nvarchar,
+    hit_time integer NOT NULL,
+    PRIMARY KEY (guild_id, user_id, hit_time),
+    FOREIGN KEY (guild_id, user_id) REFERENCES Member (guild_id, user_id)
+);
+
+INSERT INTO DailyAttendance_new (guild_id, user_id, hit_message, hit_time)
+    SELECT guild_id, user_id, hit_message, hit_time FROM DailyAttendance
+    WHERE rowid IN (SELECT MIN(rowid) FROM DailyAttendance GROUP BY guild_id, user_id, hit_time);
+
+DROP TABLE DailyAttendance;
+ALTER TABLE DailyAttendance_new RENAME TO DailyAttendance;
+CREATE INDEX DailyAttendance_hit_time ON DailyAttendance (guild_id, hit_time);
+
+CREATE TABLE AttendanceHistory_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    hit_message nvarchar,
+    hit_time integer NOT NULL,
+    hit_score integer NOT NULL,
+    hit_combo integer NOT NULL,
+    hit_rank integer NOT NULL,
+    hit_point integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id, hit_time),
+    FOREIGN KEY (guild_id, user_id) REFERENCES Member (guild_id, user_id)
+);
+
+INSERT INTO AttendanceHistory_new (guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank, hit_point)
+    SELECT guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank, hit_point
+    FROM AttendanceHistory
+    WHERE rowid IN (SELECT MIN(rowid) FROM AttendanceHistory GROUP BY guild_id, user_id, hit_time);
+
+DROP TABLE AttendanceHistory;
+ALTER TABLE AttendanceHistory_new RENAME TO AttendanceHistory;
+CREATE INDEX AttendanceHistory_hit_time ON AttendanceHistory (guild_id, hit_time);
+
+/* History tables read by guild and time */
+CREATE INDEX IF NOT EXISTS TransferHistory_transfer_time ON TransferHistory (guild_id, transfer_time);
+CREATE INDEX IF NOT EXISTS FreezeHistory_event_time ON FreezeHistory (guild_id, user_id, event_time);
+CREATE INDEX IF NOT EXISTS Inventory_user ON Inventory (guild_id, user_id);
+CREATE INDEX IF NOT EXISTS AuditLog_guild ON AuditLog (guild_id, audit_id);
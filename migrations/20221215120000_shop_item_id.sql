@@ -0,0 +1,27 @@
+-- Add migration script here
+
+/* Every definition of an item gets a new item_id, which purchases keep, so cancelling a purchase
+   restocks only the item it was sold from and not a redefined item of the same name. */
+CREATE TABLE ShopItem_new
+(
+    item_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL,
+    name nvarchar NOT NULL,
+    price integer NOT NULL,
+    stock integer,
+    role_id integer,
+    duration integer,
+    UNIQUE (guild_id, name)
+);
+
+INSERT INTO ShopItem_new (guild_id, name, price, stock, role_id, duration)
+    SELECT guild_id, name, price, stock, role_id, duration FROM ShopItem;
+
+DROP TABLE ShopItem;
+ALTER TABLE ShopItem_new RENAME TO ShopItem;
+
+/* Earlier purchases are taken as sold from the item of their name. */
+ALTER TABLE Inventory ADD COLUMN item_id integer;
+UPDATE Inventory SET item_id=(
+    SELECT item_id FROM ShopItem s WHERE s.guild_id=Inventory.guild_id AND s.name=Inventory.name
+);
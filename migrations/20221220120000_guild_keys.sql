@@ -0,0 +1,233 @@
+-- Add migration script here
+
+/* The rest of the per guild tables refer to Guild, and the history tables get an id as their
+   primary key, since a member can send or use the same thing twice in a second. Guilds known only
+   from these tables are registered, as 20221201120000_schema_keys did for the member tables. */
+INSERT OR IGNORE INTO Guild (guild_id)
+    SELECT guild_id FROM Token
+    UNION SELECT guild_id FROM TransferHistory
+    UNION SELECT guild_id FROM GuildSetting
+    UNION SELECT guild_id FROM ScoringRule
+    UNION SELECT guild_id FROM ComboBonus
+    UNION SELECT guild_id FROM Reminder
+    UNION SELECT guild_id FROM FreezeHistory
+    UNION SELECT guild_id FROM ShopItem
+    UNION SELECT guild_id FROM Inventory
+    UNION SELECT guild_id FROM RoleReward
+    UNION SELECT guild_id FROM MemberAchievement
+    UNION SELECT guild_id FROM AuditLog;
+
+INSERT OR IGNORE INTO AttendanceTimeCount (guild_id) SELECT guild_id FROM Guild;
+
+CREATE TABLE Token_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    token char(32),
+    PRIMARY KEY (guild_id, user_id)
+);
+
+INSERT INTO Token_new (guild_id, user_id, token) SELECT guild_id, user_id, token FROM Token;
+
+DROP TABLE Token;
+ALTER TABLE Token_new RENAME TO Token;
+
+CREATE TABLE TransferHistory_new
+(
+    transfer_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    sender_id integer NOT NULL,
+    receiver_id integer NOT NULL,
+    amount integer NOT NULL,
+    sender_score integer NOT NULL,
+    receiver_score integer NOT NULL,
+    transfer_time integer NOT NULL
+);
+
+INSERT INTO TransferHistory_new (guild_id, sender_id, receiver_id, amount, sender_score, receiver_score, transfer_time)
+    SELECT guild_id, sender_id, receiver_id, amount, sender_score, receiver_score, transfer_time
+    FROM TransferHistory ORDER BY rowid;
+
+DROP TABLE TransferHistory;
+ALTER TABLE TransferHistory_new RENAME TO TransferHistory;
+CREATE INDEX TransferHistory_transfer_time ON TransferHistory (guild_id, transfer_time);
+
+CREATE TABLE GuildSetting_new
+(
+    guild_id integer NOT NULL PRIMARY KEY REFERENCES Guild (guild_id),
+    timezone nvarchar NOT NULL DEFAULT 'Asia/Seoul',
+    day_start_hour integer NOT NULL DEFAULT 6,
+    hide_departed integer NOT NULL DEFAULT 0,
+    summary_channel integer,
+    reminder_enabled integer NOT NULL DEFAULT 0,
+    reminder_channel integer,
+    reminder_min_combo integer NOT NULL DEFAULT 3
+);
+
+INSERT INTO GuildSetting_new (guild_id, timezone, day_start_hour, hide_departed, summary_channel, reminder_enabled, reminder_channel, reminder_min_combo)
+    SELECT guild_id, timezone, day_start_hour, hide_departed, summary_channel, reminder_enabled, reminder_channel, reminder_min_combo
+    FROM GuildSetting;
+
+DROP TABLE GuildSetting;
+ALTER TABLE GuildSetting_new RENAME TO GuildSetting;
+
+CREATE TABLE ScoringRule_new
+(
+    guild_id integer NOT NULL PRIMARY KEY REFERENCES Guild (guild_id),
+    rank_points nvarchar NOT NULL DEFAULT '10,5,3,1',
+    anniversary_bonus integer NOT NULL DEFAULT 1500,
+    freeze_price integer NOT NULL DEFAULT 100,
+    freeze_every integer NOT NULL DEFAULT 30,
+    max_freezes integer NOT NULL DEFAULT 2
+);
+
+INSERT INTO ScoringRule_new (guild_id, rank_points, anniversary_bonus, freeze_price, freeze_every, max_freezes)
+    SELECT guild_id, rank_points, anniversary_bonus, freeze_price, freeze_every, max_freezes
+    FROM ScoringRule;
+
+DROP TABLE ScoringRule;
+ALTER TABLE ScoringRule_new RENAME TO ScoringRule;
+
+CREATE TABLE ComboBonus_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    kind nvarchar NOT NULL,
+    combo integer NOT NULL,
+    bonus integer NOT NULL,
+    PRIMARY KEY (guild_id, kind, combo)
+);
+
+INSERT INTO ComboBonus_new (guild_id, kind, combo, bonus)
+    SELECT guild_id, kind, combo, bonus FROM ComboBonus;
+
+DROP TABLE ComboBonus;
+ALTER TABLE ComboBonus_new RENAME TO ComboBonus;
+
+CREATE TABLE Reminder_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    remind_time integer NOT NULL,
+    delivery nvarchar NOT NULL DEFAULT 'dm',
+    last_reminded integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+INSERT INTO Reminder_new (guild_id, user_id, remind_time, delivery, last_reminded)
+    SELECT guild_id, user_id, remind_time, delivery, last_reminded FROM Reminder;
+
+DROP TABLE Reminder;
+ALTER TABLE Reminder_new RENAME TO Reminder;
+
+CREATE TABLE FreezeHistory_new
+(
+    freeze_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    kind nvarchar NOT NULL,
+    count integer NOT NULL,
+    score integer NOT NULL,
+    frozen_day integer,
+    event_time integer NOT NULL
+);
+
+INSERT INTO FreezeHistory_new (guild_id, user_id, kind, count, score, frozen_day, event_time)
+    SELECT guild_id, user_id, kind, count, score, frozen_day, event_time
+    FROM FreezeHistory ORDER BY rowid;
+
+DROP TABLE FreezeHistory;
+ALTER TABLE FreezeHistory_new RENAME TO FreezeHistory;
+CREATE INDEX FreezeHistory_event_time ON FreezeHistory (guild_id, user_id, event_time);
+
+/* item_id and purchase_id are kept, so purchases still refer to their item. */
+CREATE TABLE ShopItem_new
+(
+    item_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    name nvarchar NOT NULL,
+    price integer NOT NULL,
+    stock integer,
+    role_id integer,
+    duration integer,
+    UNIQUE (guild_id, name)
+);
+
+INSERT INTO ShopItem_new (item_id, guild_id, name, price, stock, role_id, duration)
+    SELECT item_id, guild_id, name, price, stock, role_id, duration FROM ShopItem;
+
+DROP TABLE ShopItem;
+ALTER TABLE ShopItem_new RENAME TO ShopItem;
+
+CREATE TABLE Inventory_new
+(
+    purchase_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    name nvarchar NOT NULL,
+    price integer NOT NULL,
+    role_id integer,
+    score integer NOT NULL,
+    purchase_time integer NOT NULL,
+    expire_time integer,
+    expired boolean NOT NULL DEFAULT 0,
+    item_id integer
+);
+
+INSERT INTO Inventory_new (purchase_id, guild_id, user_id, name, price, role_id, score, purchase_time, expire_time, expired, item_id)
+    SELECT purchase_id, guild_id, user_id, name, price, role_id, score, purchase_time, expire_time, expired, item_id
+    FROM Inventory;
+
+DROP TABLE Inventory;
+ALTER TABLE Inventory_new RENAME TO Inventory;
+CREATE INDEX Inventory_user ON Inventory (guild_id, user_id);
+
+CREATE TABLE RoleReward_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    role_id integer NOT NULL,
+    kind nvarchar NOT NULL,
+    threshold integer NOT NULL,
+    revoke boolean NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+INSERT INTO RoleReward_new (guild_id, role_id, kind, threshold, revoke)
+    SELECT guild_id, role_id, kind, threshold, revoke FROM RoleReward;
+
+DROP TABLE RoleReward;
+ALTER TABLE RoleReward_new RENAME TO RoleReward;
+
+CREATE TABLE MemberAchievement_new
+(
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    user_id integer NOT NULL,
+    achievement nvarchar NOT NULL,
+    achieved_time integer NOT NULL,
+    PRIMARY KEY (guild_id, user_id, achievement)
+);
+
+INSERT INTO MemberAchievement_new (guild_id, user_id, achievement, achieved_time)
+    SELECT guild_id, user_id, achievement, achieved_time FROM MemberAchievement;
+
+DROP TABLE MemberAchievement;
+ALTER TABLE MemberAchievement_new RENAME TO MemberAchievement;
+
+CREATE TABLE AuditLog_new
+(
+    audit_id integer PRIMARY KEY AUTOINCREMENT,
+    guild_id integer NOT NULL REFERENCES Guild (guild_id),
+    actor_id integer NOT NULL,
+    target_id integer NOT NULL,
+    field nvarchar NOT NULL,
+    before integer NOT NULL,
+    after integer NOT NULL,
+    reason nvarchar NOT NULL,
+    event_time integer NOT NULL
+);
+
+INSERT INTO AuditLog_new (audit_id, guild_id, actor_id, target_id, field, before, after, reason, event_time)
+    SELECT audit_id, guild_id, actor_id, target_id, field, before, after, reason, event_time FROM AuditLog;
+
+DROP TABLE AuditLog;
+ALTER TABLE AuditLog_new RENAME TO AuditLog;
+CREATE INDEX AuditLog_guild ON AuditLog (guild_id, audit_id);
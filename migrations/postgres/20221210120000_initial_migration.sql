@@ -0,0 +1,189 @@
+-- Add migration script here
+
+/* Same schema as the SQLite migrations up to 20221201120000_schema_keys, with bigint ids and
+   boolean flags. Later changes get a migration on both sides. */
+CREATE TABLE IF NOT EXISTS Guild
+(
+    guild_id bigint NOT NULL PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS Member
+(
+    guild_id bigint NOT NULL REFERENCES Guild (guild_id),
+    user_id bigint NOT NULL,
+    score bigint NOT NULL DEFAULT 0,
+    combo bigint NOT NULL DEFAULT 0,
+    hit_time bigint NOT NULL DEFAULT 0,
+    display_name text,
+    departed boolean NOT NULL DEFAULT FALSE,
+    freezes bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS Member_score ON Member (guild_id, score);
+
+CREATE TABLE IF NOT EXISTS AttendanceTimeCount
+(
+    guild_id bigint NOT NULL PRIMARY KEY REFERENCES Guild (guild_id),
+    hit_count bigint NOT NULL DEFAULT 0,
+    hit_time bigint NOT NULL DEFAULT 0,
+    reset_time bigint NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS DailyAttendance
+(
+    guild_id bigint NOT NULL REFERENCES Guild (guild_id),
+    user_id bigint NOT NULL,
+    hit_message text,
+    hit_time bigint NOT NULL,
+    PRIMARY KEY (guild_id, user_id, hit_time),
+    FOREIGN KEY (guild_id, user_id) REFERENCES Member (guild_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS DailyAttendance_hit_time ON DailyAttendance (guild_id, hit_time);
+
+CREATE TABLE IF NOT EXISTS AttendanceHistory
+(
+    guild_id bigint NOT NULL REFERENCES Guild (guild_id),
+    user_id bigint NOT NULL,
+    hit_message text,
+    hit_time bigint NOT NULL,
+    hit_score bigint NOT NULL,
+    hit_combo bigint NOT NULL,
+    hit_rank bigint NOT NULL,
+    hit_point bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id, hit_time),
+    FOREIGN KEY (guild_id, user_id) REFERENCES Member (guild_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS AttendanceHistory_hit_time ON AttendanceHistory (guild_id, hit_time);
+
+CREATE TABLE IF NOT EXISTS Token
+(
+    guild_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    token char(32),
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS TransferHistory
+(
+    guild_id bigint NOT NULL,
+    sender_id bigint NOT NULL,
+    receiver_id bigint NOT NULL,
+    amount bigint NOT NULL,
+    sender_score bigint NOT NULL,
+    receiver_score bigint NOT NULL,
+    transfer_time bigint NOT NULL
+);
+CREATE INDEX IF NOT EXISTS TransferHistory_transfer_time ON TransferHistory (guild_id, transfer_time);
+
+CREATE TABLE IF NOT EXISTS GuildSetting
+(
+    guild_id bigint NOT NULL PRIMARY KEY,
+    timezone text NOT NULL DEFAULT 'Asia/Seoul',
+    day_start_hour bigint NOT NULL DEFAULT 6,
+    hide_departed boolean NOT NULL DEFAULT FALSE,
+    summary_channel bigint,
+    reminder_enabled boolean NOT NULL DEFAULT FALSE,
+    reminder_channel bigint,
+    reminder_min_combo bigint NOT NULL DEFAULT 3
+);
+
+CREATE TABLE IF NOT EXISTS ScoringRule
+(
+    guild_id bigint NOT NULL PRIMARY KEY,
+    rank_points text NOT NULL DEFAULT '10,5,3,1',
+    anniversary_bonus bigint NOT NULL DEFAULT 1500,
+    freeze_price bigint NOT NULL DEFAULT 100,
+    freeze_every bigint NOT NULL DEFAULT 30,
+    max_freezes bigint NOT NULL DEFAULT 2
+);
+
+CREATE TABLE IF NOT EXISTS ComboBonus
+(
+    guild_id bigint NOT NULL,
+    kind text NOT NULL,
+    combo bigint NOT NULL,
+    bonus bigint NOT NULL,
+    PRIMARY KEY (guild_id, kind, combo)
+);
+
+/* remind_time is the local time of day in minutes from midnight */
+CREATE TABLE IF NOT EXISTS Reminder
+(
+    guild_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    remind_time bigint NOT NULL,
+    delivery text NOT NULL DEFAULT 'dm',
+    last_reminded bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS FreezeHistory
+(
+    guild_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    kind text NOT NULL,
+    count bigint NOT NULL,
+    score bigint NOT NULL,
+    frozen_day bigint,
+    event_time bigint NOT NULL
+);
+CREATE INDEX IF NOT EXISTS FreezeHistory_event_time ON FreezeHistory (guild_id, user_id, event_time);
+
+CREATE TABLE IF NOT EXISTS ShopItem
+(
+    guild_id bigint NOT NULL,
+    name text NOT NULL,
+    price bigint NOT NULL,
+    stock bigint,
+    role_id bigint,
+    duration bigint,
+    PRIMARY KEY (guild_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS Inventory
+(
+    purchase_id bigint GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    guild_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    name text NOT NULL,
+    price bigint NOT NULL,
+    role_id bigint,
+    score bigint NOT NULL,
+    purchase_time bigint NOT NULL,
+    expire_time bigint,
+    expired boolean NOT NULL DEFAULT FALSE
+);
+CREATE INDEX IF NOT EXISTS Inventory_user ON Inventory (guild_id, user_id);
+
+CREATE TABLE IF NOT EXISTS RoleReward
+(
+    guild_id bigint NOT NULL,
+    role_id bigint NOT NULL,
+    kind text NOT NULL,
+    threshold bigint NOT NULL,
+    revoke boolean NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE IF NOT EXISTS MemberAchievement
+(
+    guild_id bigint NOT NULL,
+    user_id bigint NOT NULL,
+    achievement text NOT NULL,
+    achieved_time bigint NOT NULL,
+    PRIMARY KEY (guild_id, user_id, achievement)
+);
+
+CREATE TABLE IF NOT EXISTS AuditLog
+(
+    audit_id bigint GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    guild_id bigint NOT NULL,
+    actor_id bigint NOT NULL,
+    target_id bigint NOT NULL,
+    field text NOT NULL,
+    before bigint NOT NULL,
+    after bigint NOT NULL,
+    reason text NOT NULL,
+    event_time bigint NOT NULL
+);
+CREATE INDEX IF NOT EXISTS AuditLog_guild ON AuditLog (guild_id, audit_id);
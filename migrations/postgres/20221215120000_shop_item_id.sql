@@ -0,0 +1,9 @@
+-- Add migration script here
+
+/* Every definition of an item gets a new item_id, which purchases keep, so cancelling a purchase
+   restocks only the item it was sold from and not a redefined item of the same name. */
+ALTER TABLE ShopItem ADD COLUMN item_id bigint GENERATED BY DEFAULT AS IDENTITY UNIQUE;
+
+/* Earlier purchases are taken as sold from the item of their name. */
+ALTER TABLE Inventory ADD COLUMN item_id bigint;
+UPDATE Inventory i SET item_id=s.item_id FROM ShopItem s WHERE s.guild_id=i.guild_id AND s.name=i.name;
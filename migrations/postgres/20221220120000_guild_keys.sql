@@ -0,0 +1,37 @@
+-- Add migration script here
+
+/* The rest of the per guild tables refer to Guild, and the history tables get an id as their
+   primary key, since a member can send or use the same thing twice in a second. Guilds known only
+   from these tables are registered first. */
+INSERT INTO Guild (guild_id)
+    SELECT guild_id FROM Token
+    UNION SELECT guild_id FROM TransferHistory
+    UNION SELECT guild_id FROM GuildSetting
+    UNION SELECT guild_id FROM ScoringRule
+    UNION SELECT guild_id FROM ComboBonus
+    UNION SELECT guild_id FROM Reminder
+    UNION SELECT guild_id FROM FreezeHistory
+    UNION SELECT guild_id FROM ShopItem
+    UNION SELECT guild_id FROM Inventory
+    UNION SELECT guild_id FROM RoleReward
+    UNION SELECT guild_id FROM MemberAchievement
+    UNION SELECT guild_id FROM AuditLog
+ON CONFLICT DO NOTHING;
+
+INSERT INTO AttendanceTimeCount (guild_id) SELECT guild_id FROM Guild ON CONFLICT DO NOTHING;
+
+ALTER TABLE TransferHistory ADD COLUMN transfer_id bigint GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY;
+ALTER TABLE FreezeHistory ADD COLUMN freeze_id bigint GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY;
+
+ALTER TABLE Token ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE TransferHistory ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE GuildSetting ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE ScoringRule ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE ComboBonus ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE Reminder ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE FreezeHistory ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE ShopItem ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE Inventory ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE RoleReward ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE MemberAchievement ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
+ALTER TABLE AuditLog ADD FOREIGN KEY (guild_id) REFERENCES Guild (guild_id);
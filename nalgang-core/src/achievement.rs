@@ -0,0 +1,162 @@
+use std::fmt;
+
+// Attendances within this many seconds from the start of the day earn `Achievement::DayStart`.
+pub const DAY_START_WINDOW: i64 = 60;
+
+// What an attendance tells about the member
+pub struct AttendanceRecord {
+    pub rank: i64,
+    pub combo: i64,
+    // Attendances of the member including this one
+    pub count: i64,
+    // Seconds from the start of the attendance day
+    pub day_time: i64,
+}
+
+// Badge a member earns once, evaluated on every attendance
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Achievement {
+    FirstAttendance,
+    FirstPlace,
+    Attendances100,
+    Attendances1000,
+    Combo7,
+    Combo30,
+    Combo100,
+    Combo365,
+    DayStart,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 9] = [
+        Achievement::FirstAttendance,
+        Achievement::FirstPlace,
+        Achievement::DayStart,
+        Achievement::Combo7,
+        Achievement::Combo30,
+        Achievement::Combo100,
+        Achievement::Combo365,
+        Achievement::Attendances100,
+        Achievement::Attendances1000,
+    ];
+
+    // Stored id, which never changes
+    pub fn id(&self) -> &'static str {
+        match self {
+            Achievement::FirstAttendance => "first_attendance",
+            Achievement::FirstPlace => "first_place",
+            Achievement::Attendances100 => "attendances_100",
+            Achievement::Attendances1000 => "attendances_1000",
+            Achievement::Combo7 => "combo_7",
+            Achievement::Combo30 => "combo_30",
+            Achievement::Combo100 => "combo_100",
+            Achievement::Combo365 => "combo_365",
+            Achievement::DayStart => "day_start",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Achievement::ALL
+            .iter()
+            .find(|achievement| achievement.id() == id)
+            .copied()
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::FirstAttendance => "처음으로 날갱했습니다.",
+            Achievement::FirstPlace => "1등으로 날갱했습니다.",
+            Achievement::Attendances100 => "100번 날갱했습니다.",
+            Achievement::Attendances1000 => "1000번 날갱했습니다.",
+            Achievement::Combo7 => "7일 연속 날갱했습니다.",
+            Achievement::Combo30 => "30일 연속 날갱했습니다.",
+            Achievement::Combo100 => "100일 연속 날갱했습니다.",
+            Achievement::Combo365 => "365일 연속 날갱했습니다.",
+            Achievement::DayStart => "하루가 시작되고 1분 안에 날갱했습니다.",
+        }
+    }
+
+    pub fn achieved(&self, record: &AttendanceRecord) -> bool {
+        match self {
+            Achievement::FirstAttendance => record.count >= 1,
+            Achievement::FirstPlace => record.rank == 0,
+            Achievement::Attendances100 => record.count >= 100,
+            Achievement::Attendances1000 => record.count >= 1000,
+            Achievement::Combo7 => record.combo >= 7,
+            Achievement::Combo30 => record.combo >= 30,
+            Achievement::Combo100 => record.combo >= 100,
+            Achievement::Combo365 => record.combo >= 365,
+            Achievement::DayStart => record.day_time < DAY_START_WINDOW,
+        }
+    }
+
+    // Every achievement the attendance satisfies, earned before or not
+    pub fn evaluate(record: &AttendanceRecord) -> Vec<Achievement> {
+        Achievement::ALL
+            .iter()
+            .filter(|achievement| achievement.achieved(record))
+            .copied()
+            .collect()
+    }
+}
+
+impl fmt::Display for Achievement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Achievement::FirstAttendance => "첫 날갱",
+            Achievement::FirstPlace => "일등",
+            Achievement::Attendances100 => "날갱 백 번",
+            Achievement::Attendances1000 => "날갱 천 번",
+            Achievement::Combo7 => "일주일 개근",
+            Achievement::Combo30 => "한 달 개근",
+            Achievement::Combo100 => "백일 개근",
+            Achievement::Combo365 => "일 년 개근",
+            Achievement::DayStart => "칼같은 날갱",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate() {
+        let record = AttendanceRecord {
+            rank: 0,
+            combo: 30,
+            count: 120,
+            day_time: 3600,
+        };
+        assert_eq!(
+            Achievement::evaluate(&record),
+            vec![
+                Achievement::FirstAttendance,
+                Achievement::FirstPlace,
+                Achievement::Combo7,
+                Achievement::Combo30,
+                Achievement::Attendances100,
+            ]
+        );
+    }
+
+    #[test]
+    fn day_start() {
+        let record = |day_time| AttendanceRecord {
+            rank: 1,
+            combo: 1,
+            count: 1,
+            day_time,
+        };
+        assert!(Achievement::DayStart.achieved(&record(DAY_START_WINDOW - 1)));
+        assert!(!Achievement::DayStart.achieved(&record(DAY_START_WINDOW)));
+    }
+
+    #[test]
+    fn ids() {
+        for achievement in Achievement::ALL {
+            assert_eq!(Achievement::from_id(achievement.id()), Some(achievement));
+        }
+    }
+}
@@ -0,0 +1,742 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::reminder::ReminderSetting;
+use crate::scoring::{ComboBonus, ComboMilestone, FreezeRule, RankPoints, ScoringRule};
+use crate::timezone::{DayBoundary, GuildTimezone, MAX_TIMESTAMP};
+use crate::{nalgang_error, NalgangError, NalgangErrorInner};
+
+pub const ARCHIVE_VERSION: i64 = 1;
+
+// Backup of the members, attendance history and settings of a guild.
+#[derive(Serialize, Deserialize)]
+pub struct GuildArchive {
+    pub version: i64,
+    // Guild the archive was exported from, which may differ from the guild it is imported into
+    pub guild_id: i64,
+    pub exported_time: i64,
+    pub settings: ArchivedSettings,
+    pub members: Vec<ArchivedMember>,
+    pub history: Vec<ArchivedAttendance>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedSettings {
+    pub timezone: String,
+    pub day_start_hour: i64,
+    pub hide_departed: bool,
+    pub summary_channel: Option<i64>,
+    pub reminder_enabled: bool,
+    pub reminder_channel: Option<i64>,
+    pub reminder_min_combo: i64,
+    pub rank_points: String,
+    pub combo_bonuses: Vec<ArchivedComboBonus>,
+    pub anniversary_bonus: i64,
+    pub freeze_price: i64,
+    pub freeze_every: i64,
+    pub freeze_max: i64,
+}
+
+// `kind` is the kind of `ComboMilestone`.
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedComboBonus {
+    pub kind: String,
+    pub combo: i64,
+    pub bonus: i64,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+pub struct ArchivedMember {
+    pub user_id: i64,
+    pub score: i64,
+    pub combo: i64,
+    pub hit_time: i64,
+    pub freezes: i64,
+    pub display_name: Option<String>,
+    pub departed: bool,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+pub struct ArchivedAttendance {
+    pub user_id: i64,
+    pub hit_time: i64,
+    pub hit_rank: i64,
+    pub hit_point: i64,
+    pub hit_score: i64,
+    pub hit_combo: i64,
+    pub hit_message: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Json,
+    Csv,
+}
+
+impl ArchiveFormat {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Json => "json",
+            ArchiveFormat::Csv => "csv",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "json" => Some(ArchiveFormat::Json),
+            "csv" => Some(ArchiveFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+// What to do with a member of the archive who is already registered in the guild
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    // Keeps the registered member as it is.
+    Skip,
+    // Replaces the member and its attendance history with the archived ones.
+    Overwrite,
+    // Adds the archived score, keeps the combo of the later attendance and adds the attendances
+    // the member does not have.
+    MergeScores,
+}
+
+impl ConflictPolicy {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::MergeScores => "merge",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "skip" => Some(ConflictPolicy::Skip),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "merge" => Some(ConflictPolicy::MergeScores),
+            _ => None,
+        }
+    }
+}
+
+// Result of an import. Settings are restored into a new guild, or over the existing ones when
+// overwriting.
+#[derive(Default)]
+pub struct ImportReport {
+    pub guild_created: bool,
+    pub added: i64,
+    pub skipped: i64,
+    pub overwritten: i64,
+    pub merged: i64,
+    pub history_added: i64,
+    pub settings_restored: bool,
+}
+
+fn invalid(reason: String) -> NalgangError {
+    nalgang_error!(NalgangErrorInner::InvalidArchive(reason))
+}
+
+impl ArchivedSettings {
+    pub fn new(
+        boundary: &DayBoundary,
+        hide_departed: bool,
+        summary_channel: Option<i64>,
+        reminder: &ReminderSetting,
+        rule: &ScoringRule,
+    ) -> Self {
+        ArchivedSettings {
+            timezone: boundary.timezone.to_string(),
+            day_start_hour: boundary.day_start_hour as i64,
+            hide_departed,
+            summary_channel,
+            reminder_enabled: reminder.enabled,
+            reminder_channel: reminder.channel_id,
+            reminder_min_combo: reminder.min_combo,
+            rank_points: rule.rank_points.to_string(),
+            combo_bonuses: rule
+                .combo_bonuses
+                .iter()
+                .map(|combo_bonus| ArchivedComboBonus {
+                    kind: combo_bonus.milestone.kind().to_string(),
+                    combo: combo_bonus.milestone.days(),
+                    bonus: combo_bonus.bonus,
+                })
+                .collect(),
+            anniversary_bonus: rule.anniversary_bonus,
+            freeze_price: rule.freeze.price,
+            freeze_every: rule.freeze.every,
+            freeze_max: rule.freeze.max,
+        }
+    }
+
+    pub fn day_boundary(&self) -> Result<DayBoundary, NalgangError> {
+        let timezone = self
+            .timezone
+            .parse::<GuildTimezone>()
+            .map_err(|_| invalid(format!("알 수 없는 시간대입니다: {}", self.timezone)))?;
+        if !(0..24).contains(&self.day_start_hour) {
+            return Err(invalid(format!(
+                "하루의 시작 시각이 잘못되었습니다: {}",
+                self.day_start_hour
+            )));
+        }
+        Ok(DayBoundary {
+            timezone,
+            day_start_hour: self.day_start_hour as u32,
+        })
+    }
+
+    pub fn reminder_setting(&self) -> Result<ReminderSetting, NalgangError> {
+        if self.reminder_min_combo < 1 {
+            return Err(invalid(format!(
+                "알림을 받을 최소 연속 출석 일수가 잘못되었습니다: {}",
+                self.reminder_min_combo
+            )));
+        }
+        Ok(ReminderSetting {
+            enabled: self.reminder_enabled,
+            channel_id: self.reminder_channel,
+            min_combo: self.reminder_min_combo,
+        })
+    }
+
+    pub fn scoring_rule(&self) -> Result<ScoringRule, NalgangError> {
+        let rank_points = self.rank_points.parse::<RankPoints>().map_err(|_| {
+            invalid(format!(
+                "순위별 점수가 잘못되었습니다: {}",
+                self.rank_points
+            ))
+        })?;
+        let mut rule = ScoringRule {
+            rank_points,
+            combo_bonuses: Vec::new(),
+            anniversary_bonus: self.anniversary_bonus,
+            freeze: FreezeRule {
+                price: self.freeze_price,
+                every: self.freeze_every,
+                max: self.freeze_max,
+            },
+        };
+        if [self.freeze_price, self.freeze_every, self.freeze_max]
+            .iter()
+            .any(|value| *value < 0)
+        {
+            return Err(invalid("휴가 규칙에 음수가 있습니다.".to_string()));
+        }
+        for combo_bonus in self.combo_bonuses.iter() {
+            let milestone = ComboMilestone::from_kind(&combo_bonus.kind, combo_bonus.combo)
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "연속 출석 보너스가 잘못되었습니다: {} {}",
+                        combo_bonus.kind, combo_bonus.combo
+                    ))
+                })?;
+            rule.combo_bonuses.push(ComboBonus {
+                milestone,
+                bonus: combo_bonus.bonus,
+            });
+        }
+        Ok(rule)
+    }
+}
+
+impl GuildArchive {
+    // Checks that the archive can be imported as a whole, before anything is written.
+    pub fn validate(&self) -> Result<(), NalgangError> {
+        if self.version != ARCHIVE_VERSION {
+            return Err(invalid(format!(
+                "지원하지 않는 버전입니다: {}",
+                self.version
+            )));
+        }
+        self.settings.day_boundary()?;
+        self.settings.reminder_setting()?;
+        self.settings.scoring_rule()?;
+        if !(0..=MAX_TIMESTAMP).contains(&self.exported_time) {
+            return Err(invalid(format!(
+                "내보낸 시각이 올바르지 않습니다: {}",
+                self.exported_time
+            )));
+        }
+
+        let mut members = HashSet::new();
+        for member in self.members.iter() {
+            if !members.insert(member.user_id) {
+                return Err(invalid(format!(
+                    "멤버가 중복되었습니다: {}",
+                    member.user_id
+                )));
+            }
+            if member.combo < 0 || member.freezes < 0 {
+                return Err(invalid(format!(
+                    "멤버의 연속 출석이나 휴가가 음수입니다: {}",
+                    member.user_id
+                )));
+            }
+            if !(0..=self.exported_time).contains(&member.hit_time) {
+                return Err(invalid(format!(
+                    "멤버의 마지막 출석 시각이 올바르지 않습니다: {} {}",
+                    member.user_id, member.hit_time
+                )));
+            }
+        }
+
+        let mut attendances = HashSet::new();
+        for entry in self.history.iter() {
+            if !members.contains(&entry.user_id) {
+                return Err(invalid(format!(
+                    "멤버 목록에 없는 계정의 출석 기록이 있습니다: {}",
+                    entry.user_id
+                )));
+            }
+            if !(0..=self.exported_time).contains(&entry.hit_time) {
+                return Err(invalid(format!(
+                    "출석 시각이 올바르지 않습니다: {} {}",
+                    entry.user_id, entry.hit_time
+                )));
+            }
+            if !attendances.insert((entry.user_id, entry.hit_time)) {
+                return Err(invalid(format!(
+                    "출석 기록이 중복되었습니다: {} {}",
+                    entry.user_id, entry.hit_time
+                )));
+            }
+            if entry.hit_rank < 0 || entry.hit_combo < 0 {
+                return Err(invalid(format!(
+                    "출석 기록의 순위나 연속 출석이 음수입니다: {} {}",
+                    entry.user_id, entry.hit_time
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Attendances of each member in the order of the archive
+    pub fn history_by_member(&self) -> HashMap<i64, Vec<&ArchivedAttendance>> {
+        let mut history: HashMap<i64, Vec<&ArchivedAttendance>> = HashMap::new();
+        for entry in self.history.iter() {
+            history.entry(entry.user_id).or_default().push(entry);
+        }
+        history
+    }
+
+    pub fn encode(&self, format: ArchiveFormat) -> String {
+        match format {
+            // Serializing plain structs of numbers and strings does not fail.
+            ArchiveFormat::Json => serde_json::to_string_pretty(self).unwrap(),
+            ArchiveFormat::Csv => self.to_csv(),
+        }
+    }
+
+    // Reads an archive of either format, telling them apart by the first character.
+    pub fn decode(data: &str) -> Result<Self, NalgangError> {
+        let data = data.trim_start_matches('\u{feff}');
+        if data.trim_start().starts_with('{') {
+            serde_json::from_str(data).map_err(|e| invalid(format!("JSON 오류: {}", e)))
+        } else {
+            Self::from_csv(data)
+        }
+    }
+
+    // A CSV archive is a single file whose first column tells what each row is:
+    //   nalgang,version,guild_id,exported_time
+    //   setting,name,value
+    //   combo_bonus,kind,combo,bonus
+    //   member,user_id,score,combo,hit_time,freezes,departed,display_name
+    //   history,user_id,hit_time,hit_rank,hit_point,hit_score,hit_combo,hit_message
+    // An empty optional value is none.
+    fn to_csv(&self) -> String {
+        let settings = &self.settings;
+        let optional = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+        let mut rows: Vec<Vec<String>> = vec![
+            vec![
+                "nalgang".to_string(),
+                self.version.to_string(),
+                self.guild_id.to_string(),
+                self.exported_time.to_string(),
+            ],
+            setting_row("timezone", settings.timezone.clone()),
+            setting_row("day_start_hour", settings.day_start_hour.to_string()),
+            setting_row("hide_departed", flag(settings.hide_departed)),
+            setting_row("summary_channel", optional(settings.summary_channel)),
+            setting_row("reminder_enabled", flag(settings.reminder_enabled)),
+            setting_row("reminder_channel", optional(settings.reminder_channel)),
+            setting_row(
+                "reminder_min_combo",
+                settings.reminder_min_combo.to_string(),
+            ),
+            setting_row("rank_points", settings.rank_points.clone()),
+            setting_row("anniversary_bonus", settings.anniversary_bonus.to_string()),
+            setting_row("freeze_price", settings.freeze_price.to_string()),
+            setting_row("freeze_every", settings.freeze_every.to_string()),
+            setting_row("freeze_max", settings.freeze_max.to_string()),
+        ];
+        for combo_bonus in settings.combo_bonuses.iter() {
+            rows.push(vec![
+                "combo_bonus".to_string(),
+                combo_bonus.kind.clone(),
+                combo_bonus.combo.to_string(),
+                combo_bonus.bonus.to_string(),
+            ]);
+        }
+        for member in self.members.iter() {
+            rows.push(vec![
+                "member".to_string(),
+                member.user_id.to_string(),
+                member.score.to_string(),
+                member.combo.to_string(),
+                member.hit_time.to_string(),
+                member.freezes.to_string(),
+                flag(member.departed),
+                member.display_name.clone().unwrap_or_default(),
+            ]);
+        }
+        for entry in self.history.iter() {
+            rows.push(vec![
+                "history".to_string(),
+                entry.user_id.to_string(),
+                entry.hit_time.to_string(),
+                entry.hit_rank.to_string(),
+                entry.hit_point.to_string(),
+                entry.hit_score.to_string(),
+                entry.hit_combo.to_string(),
+                entry.hit_message.clone().unwrap_or_default(),
+            ]);
+        }
+
+        let mut csv = String::new();
+        for row in rows {
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    fn from_csv(data: &str) -> Result<Self, NalgangError> {
+        let rows = parse_csv(data)?;
+        let mut rows = rows.iter();
+        let (line, header) = rows
+            .next()
+            .ok_or_else(|| invalid("빈 파일입니다.".to_string()))?;
+        if header.len() != 4 || header[0] != "nalgang" {
+            return Err(invalid(format!(
+                "{}번째 줄: 날갱 백업 파일이 아닙니다.",
+                line
+            )));
+        }
+        let mut archive = GuildArchive {
+            version: number(*line, &header[1])?,
+            guild_id: number(*line, &header[2])?,
+            exported_time: number(*line, &header[3])?,
+            settings: ArchivedSettings::new(
+                &DayBoundary::default(),
+                false,
+                None,
+                &ReminderSetting::default(),
+                &ScoringRule::default(),
+            ),
+            members: Vec::new(),
+            history: Vec::new(),
+        };
+        // Bonuses are only those of the archive, not the default ones.
+        archive.settings.combo_bonuses.clear();
+
+        for (line, row) in rows {
+            let line = *line;
+            let expect = |length: usize| {
+                if row.len() == length {
+                    Ok(())
+                } else {
+                    Err(invalid(format!(
+                        "{}번째 줄: 항목이 {}개여야 합니다.",
+                        line, length
+                    )))
+                }
+            };
+            match row[0].as_str() {
+                "setting" => {
+                    expect(3)?;
+                    archive.settings.set(line, &row[1], &row[2])?;
+                }
+                "combo_bonus" => {
+                    expect(4)?;
+                    archive.settings.combo_bonuses.push(ArchivedComboBonus {
+                        kind: row[1].clone(),
+                        combo: number(line, &row[2])?,
+                        bonus: number(line, &row[3])?,
+                    });
+                }
+                "member" => {
+                    expect(8)?;
+                    archive.members.push(ArchivedMember {
+                        user_id: number(line, &row[1])?,
+                        score: number(line, &row[2])?,
+                        combo: number(line, &row[3])?,
+                        hit_time: number(line, &row[4])?,
+                        freezes: number(line, &row[5])?,
+                        departed: boolean(line, &row[6])?,
+                        display_name: text(&row[7]),
+                    });
+                }
+                "history" => {
+                    expect(8)?;
+                    archive.history.push(ArchivedAttendance {
+                        user_id: number(line, &row[1])?,
+                        hit_time: number(line, &row[2])?,
+                        hit_rank: number(line, &row[3])?,
+                        hit_point: number(line, &row[4])?,
+                        hit_score: number(line, &row[5])?,
+                        hit_combo: number(line, &row[6])?,
+                        hit_message: text(&row[7]),
+                    });
+                }
+                kind => {
+                    return Err(invalid(format!(
+                        "{}번째 줄: 알 수 없는 항목입니다: {}",
+                        line, kind
+                    )))
+                }
+            }
+        }
+        Ok(archive)
+    }
+}
+
+impl ArchivedSettings {
+    fn set(&mut self, line: usize, name: &str, value: &str) -> Result<(), NalgangError> {
+        match name {
+            "timezone" => self.timezone = value.to_string(),
+            "day_start_hour" => self.day_start_hour = number(line, value)?,
+            "hide_departed" => self.hide_departed = boolean(line, value)?,
+            "summary_channel" => self.summary_channel = optional_number(line, value)?,
+            "reminder_enabled" => self.reminder_enabled = boolean(line, value)?,
+            "reminder_channel" => self.reminder_channel = optional_number(line, value)?,
+            "reminder_min_combo" => self.reminder_min_combo = number(line, value)?,
+            "rank_points" => self.rank_points = value.to_string(),
+            "anniversary_bonus" => self.anniversary_bonus = number(line, value)?,
+            "freeze_price" => self.freeze_price = number(line, value)?,
+            "freeze_every" => self.freeze_every = number(line, value)?,
+            "freeze_max" => self.freeze_max = number(line, value)?,
+            _ => {
+                return Err(invalid(format!(
+                    "{}번째 줄: 알 수 없는 설정입니다: {}",
+                    line, name
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn setting_row(name: &str, value: String) -> Vec<String> {
+    vec!["setting".to_string(), name.to_string(), value]
+}
+
+fn flag(value: bool) -> String {
+    (value as i64).to_string()
+}
+
+fn text(field: &str) -> Option<String> {
+    (!field.is_empty()).then(|| field.to_string())
+}
+
+fn number(line: usize, field: &str) -> Result<i64, NalgangError> {
+    field
+        .parse()
+        .map_err(|_| invalid(format!("{}번째 줄: 숫자가 아닙니다: {}", line, field)))
+}
+
+fn optional_number(line: usize, field: &str) -> Result<Option<i64>, NalgangError> {
+    match field {
+        "" => Ok(None),
+        _ => number(line, field).map(Some),
+    }
+}
+
+fn boolean(line: usize, field: &str) -> Result<bool, NalgangError> {
+    match field {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(invalid(format!(
+            "{}번째 줄: 0이나 1이 아닙니다: {}",
+            line, field
+        ))),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Rows of a CSV file with the line each of them starts on. Quoted fields may contain commas,
+// doubled quotes and line breaks. Empty lines are skipped.
+fn parse_csv(data: &str) -> Result<Vec<(usize, Vec<String>)>, NalgangError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let (mut line, mut row_line) = (1, 1);
+    let mut quoted = false;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                if !row.is_empty() || !field.is_empty() {
+                    row.push(std::mem::take(&mut field));
+                    rows.push((row_line, std::mem::take(&mut row)));
+                }
+                line += 1;
+                row_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(invalid(format!(
+            "{}번째 줄: 따옴표가 닫히지 않았습니다.",
+            row_line
+        )));
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive() -> GuildArchive {
+        let mut settings = ArchivedSettings::new(
+            &DayBoundary::default(),
+            true,
+            Some(7),
+            &ReminderSetting::default(),
+            &ScoringRule::default(),
+        );
+        settings.combo_bonuses.pop();
+        GuildArchive {
+            version: ARCHIVE_VERSION,
+            guild_id: 1,
+            exported_time: 1704067200,
+            settings,
+            members: vec![
+                ArchivedMember {
+                    user_id: 10,
+                    score: 15,
+                    combo: 2,
+                    hit_time: 1704067200,
+                    freezes: 1,
+                    display_name: Some("a, \"b\"".to_string()),
+                    departed: false,
+                },
+                ArchivedMember {
+                    user_id: 11,
+                    score: 0,
+                    combo: 0,
+                    hit_time: 0,
+                    freezes: 0,
+                    display_name: None,
+                    departed: true,
+                },
+            ],
+            history: vec![ArchivedAttendance {
+                user_id: 10,
+                hit_time: 1704067200,
+                hit_rank: 0,
+                hit_point: 10,
+                hit_score: 15,
+                hit_combo: 2,
+                hit_message: Some("line\nbreak".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let archive = archive();
+        let csv = archive.encode(ArchiveFormat::Csv);
+        let decoded = GuildArchive::decode(&csv).ok().unwrap();
+        assert_eq!(
+            decoded.encode(ArchiveFormat::Json),
+            archive.encode(ArchiveFormat::Json)
+        );
+        assert_eq!(decoded.settings.combo_bonuses.len(), 1);
+        assert!(decoded.validate().is_ok());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json = archive().encode(ArchiveFormat::Json);
+        let decoded = GuildArchive::decode(&json).ok().unwrap();
+        assert_eq!(decoded.encode(ArchiveFormat::Json), json);
+    }
+
+    #[test]
+    fn invalid_archives() {
+        let mut unknown_member = archive();
+        unknown_member.history[0].user_id = 12;
+        let mut duplicate_member = archive();
+        duplicate_member.members[1].user_id = 10;
+        let mut timezone = archive();
+        timezone.settings.timezone = "Mars/Olympus".to_string();
+        let mut version = archive();
+        version.version = ARCHIVE_VERSION + 1;
+        let mut exported_time = archive();
+        exported_time.exported_time = i64::MAX;
+        let mut member_time = archive();
+        member_time.members[0].hit_time = -1;
+        let mut future_attendance = archive();
+        future_attendance.history[0].hit_time = 99999999999999;
+        for archive in [
+            unknown_member,
+            duplicate_member,
+            timezone,
+            version,
+            exported_time,
+            member_time,
+            future_attendance,
+        ] {
+            let e = archive.validate().err().unwrap();
+            assert!(matches!(e.kind, NalgangErrorInner::InvalidArchive(_)));
+        }
+
+        for csv in [
+            "",
+            "member,10\n",
+            "nalgang,1,1,0\nmember,10,1,1,1,1,2,\n",
+            "nalgang,1,1,0\nhistory,\"10\n",
+        ] {
+            let e = GuildArchive::decode(csv).err().unwrap();
+            assert!(matches!(e.kind, NalgangErrorInner::InvalidArchive(_)));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+// Value of a member an admin can change
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AdjustField {
+    Score,
+    Combo,
+}
+
+impl AdjustField {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AdjustField::Score => "score",
+            AdjustField::Combo => "combo",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "score" => Some(AdjustField::Score),
+            "combo" => Some(AdjustField::Combo),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AdjustField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdjustField::Score => write!(f, "점수"),
+            AdjustField::Combo => write!(f, "연속 출석"),
+        }
+    }
+}
+
+// Change of a value, subtraction being a negative `Add`
+#[derive(Clone, Copy)]
+pub enum Adjustment {
+    Add(i64),
+    Set(i64),
+}
+
+impl Adjustment {
+    // New value, None if it does not fit in i64
+    pub fn apply(&self, value: i64) -> Option<i64> {
+        match self {
+            Adjustment::Add(amount) => value.checked_add(*amount),
+            Adjustment::Set(new_value) => Some(*new_value),
+        }
+    }
+}
+
+// Actor of the score changes an import records, since imports also run without Discord
+pub const IMPORT_ACTOR: i64 = 0;
+
+// One change made by an admin, as stored in AuditLog
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub actor_id: i64,
+    pub target_id: i64,
+    pub field: AdjustField,
+    pub before: i64,
+    pub after: i64,
+    pub reason: String,
+    pub event_time: i64,
+}
@@ -0,0 +1,16 @@
+use chrono::Utc;
+
+// Source of the current time, injected so that the engine can be driven by tests.
+pub trait Clock: Send + Sync {
+    // Current unix timestamp in seconds
+    fn now(&self) -> i64;
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
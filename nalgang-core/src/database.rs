@@ -0,0 +1,432 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+
+use crate::achievement::Achievement;
+use crate::archive::{
+    ArchivedAttendance, ArchivedMember, ConflictPolicy, GuildArchive, ImportReport,
+};
+use crate::audit::{AdjustField, Adjustment, AuditEntry};
+use crate::engine::{Attendance, GuildAttendance};
+use crate::postgres::PostgresStorage;
+use crate::reminder::{RemindTime, Reminder, ReminderDelivery, ReminderSetting};
+use crate::role_reward::RoleReward;
+use crate::scoring::{FreezeRule, ScoringRule};
+use crate::shop::{ExpiredRole, InventoryItem, ShopItem};
+use crate::sqlite::SqliteStorage;
+use crate::storage::Storage;
+use crate::timezone::DayBoundary;
+use crate::{
+    AttendanceEntry, HistoryEntry, NalgangError, NalgangMember, RankingEntry, RankingFilter,
+};
+
+// Storage chosen by the database URL at startup
+#[derive(Clone)]
+pub enum DatabaseStorage {
+    Sqlite(SqliteStorage),
+    Postgres(PostgresStorage),
+}
+
+impl DatabaseStorage {
+    // Connects to a postgres:// URL with PostgreSQL, and to any other URL with SQLite.
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            let database = sqlx::postgres::PgPoolOptions::new()
+                .max_connections(5)
+                .connect(url)
+                .await?;
+            Ok(DatabaseStorage::Postgres(PostgresStorage::new(database)))
+        } else {
+            let database = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(5)
+                .connect_with(sqlx::sqlite::SqliteConnectOptions::from_str(url)?)
+                .await?;
+            Ok(DatabaseStorage::Sqlite(SqliteStorage::new(database)))
+        }
+    }
+
+    pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        match self {
+            DatabaseStorage::Sqlite(storage) => storage.migrate().await,
+            DatabaseStorage::Postgres(storage) => storage.migrate().await,
+        }
+    }
+
+    pub async fn vacuum(&self) -> Result<(), sqlx::Error> {
+        match self {
+            DatabaseStorage::Sqlite(storage) => storage.vacuum().await,
+            DatabaseStorage::Postgres(storage) => storage.vacuum().await,
+        }
+    }
+
+    // Copies a SQLite database to a new file. PostgreSQL is backed up with its own tools such as
+    // pg_dump.
+    pub async fn backup(&self, path: &str) -> Result<(), sqlx::Error> {
+        match self {
+            DatabaseStorage::Sqlite(storage) => storage.backup(path).await,
+            DatabaseStorage::Postgres(_) => Err(sqlx::Error::Configuration(
+                "backups of PostgreSQL are made with pg_dump".into(),
+            )),
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($self: ident, $method: ident($($arg: expr),*)) => {
+        match $self {
+            DatabaseStorage::Sqlite(storage) => storage.$method($($arg),*).await,
+            DatabaseStorage::Postgres(storage) => storage.$method($($arg),*).await,
+        }
+    };
+}
+
+#[async_trait]
+impl Storage for DatabaseStorage {
+    async fn register_guild(&self, gid: i64) -> Result<(), NalgangError> {
+        delegate!(self, register_guild(gid))
+    }
+
+    async fn guilds(&self) -> Result<Vec<i64>, NalgangError> {
+        delegate!(self, guilds())
+    }
+
+    async fn register_member(&self, gid: i64, uid: i64) -> Result<(), NalgangError> {
+        delegate!(self, register_member(gid, uid))
+    }
+
+    async fn member_info(&self, member: &mut NalgangMember) -> Result<bool, NalgangError> {
+        delegate!(self, member_info(member))
+    }
+
+    async fn members(&self, gid: i64) -> Result<Vec<NalgangMember>, NalgangError> {
+        delegate!(self, members(gid))
+    }
+
+    async fn archive_members(
+        &self,
+        gid: i64,
+    ) -> Result<(Vec<ArchivedMember>, Vec<ArchivedAttendance>), NalgangError> {
+        delegate!(self, archive_members(gid))
+    }
+
+    async fn import_members(
+        &self,
+        gid: i64,
+        archive: &GuildArchive,
+        policy: ConflictPolicy,
+        current_time: i64,
+    ) -> Result<ImportReport, NalgangError> {
+        delegate!(self, import_members(gid, archive, policy, current_time))
+    }
+
+    async fn restore_guild_attendance(
+        &self,
+        gid: i64,
+        attendance: &GuildAttendance,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, restore_guild_attendance(gid, attendance))
+    }
+
+    async fn recorded_combos(&self, gid: i64) -> Result<Vec<(i64, i64, i64)>, NalgangError> {
+        delegate!(self, recorded_combos(gid))
+    }
+
+    async fn update_members(&self, members: &[NalgangMember]) -> Result<(), NalgangError> {
+        delegate!(self, update_members(members))
+    }
+
+    async fn attend(
+        &self,
+        member: &mut NalgangMember,
+        message: &str,
+        current_time: i64,
+    ) -> Result<Attendance, NalgangError> {
+        delegate!(self, attend(member, message, current_time))
+    }
+
+    async fn grant_achievements(
+        &self,
+        member: &NalgangMember,
+        achievements: &[(Achievement, i64)],
+    ) -> Result<Vec<Achievement>, NalgangError> {
+        delegate!(self, grant_achievements(member, achievements))
+    }
+
+    async fn achievements(
+        &self,
+        member: &NalgangMember,
+    ) -> Result<Vec<(Achievement, i64)>, NalgangError> {
+        delegate!(self, achievements(member))
+    }
+
+    async fn buy_freezes(
+        &self,
+        member: &mut NalgangMember,
+        count: i64,
+        rule: &FreezeRule,
+        current_time: i64,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, buy_freezes(member, count, rule, current_time))
+    }
+
+    async fn set_shop_item(&self, gid: i64, item: &ShopItem) -> Result<(), NalgangError> {
+        delegate!(self, set_shop_item(gid, item))
+    }
+
+    async fn delete_shop_item(&self, gid: i64, name: &str) -> Result<bool, NalgangError> {
+        delegate!(self, delete_shop_item(gid, name))
+    }
+
+    async fn shop_items(&self, gid: i64) -> Result<Vec<ShopItem>, NalgangError> {
+        delegate!(self, shop_items(gid))
+    }
+
+    async fn buy_item(
+        &self,
+        member: &mut NalgangMember,
+        name: &str,
+        current_time: i64,
+    ) -> Result<InventoryItem, NalgangError> {
+        delegate!(self, buy_item(member, name, current_time))
+    }
+
+    async fn cancel_purchase(
+        &self,
+        member: &mut NalgangMember,
+        purchase_id: i64,
+    ) -> Result<bool, NalgangError> {
+        delegate!(self, cancel_purchase(member, purchase_id))
+    }
+
+    async fn inventory(
+        &self,
+        member: &NalgangMember,
+        current_time: i64,
+    ) -> Result<Vec<InventoryItem>, NalgangError> {
+        delegate!(self, inventory(member, current_time))
+    }
+
+    async fn expire_items(
+        &self,
+        gid: i64,
+        current_time: i64,
+    ) -> Result<Vec<ExpiredRole>, NalgangError> {
+        delegate!(self, expire_items(gid, current_time))
+    }
+
+    async fn transfer(
+        &self,
+        sender: &mut NalgangMember,
+        receiver: &mut NalgangMember,
+        amount: i64,
+        current_time: i64,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, transfer(sender, receiver, amount, current_time))
+    }
+
+    async fn adjust_member(
+        &self,
+        actor_id: i64,
+        target: &mut NalgangMember,
+        field: AdjustField,
+        adjustment: Adjustment,
+        reason: &str,
+        current_time: i64,
+    ) -> Result<AuditEntry, NalgangError> {
+        delegate!(
+            self,
+            adjust_member(actor_id, target, field, adjustment, reason, current_time)
+        )
+    }
+
+    async fn audit_log(&self, gid: i64, limit: i64) -> Result<Vec<AuditEntry>, NalgangError> {
+        delegate!(self, audit_log(gid, limit))
+    }
+
+    async fn insert_token(
+        &self,
+        member: &NalgangMember,
+        token: &str,
+    ) -> Result<bool, NalgangError> {
+        delegate!(self, insert_token(member, token))
+    }
+
+    async fn delete_token(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        delegate!(self, delete_token(member))
+    }
+
+    async fn token_owner(&self, token: &str) -> Result<Option<NalgangMember>, NalgangError> {
+        delegate!(self, token_owner(token))
+    }
+
+    async fn update_profile(
+        &self,
+        member: &NalgangMember,
+        display_name: &str,
+        departed: bool,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, update_profile(member, display_name, departed))
+    }
+
+    async fn ranking(&self, gid: i64) -> Result<Vec<RankingEntry>, NalgangError> {
+        delegate!(self, ranking(gid))
+    }
+
+    async fn ranking_page(
+        &self,
+        filter: &RankingFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<RankingEntry>, NalgangError> {
+        delegate!(self, ranking_page(filter, offset, limit))
+    }
+
+    async fn ranked_count(&self, filter: &RankingFilter) -> Result<i64, NalgangError> {
+        delegate!(self, ranked_count(filter))
+    }
+
+    async fn member_rank(
+        &self,
+        filter: &RankingFilter,
+        uid: i64,
+    ) -> Result<Option<i64>, NalgangError> {
+        delegate!(self, member_rank(filter, uid))
+    }
+
+    async fn attendance_history(
+        &self,
+        member: &NalgangMember,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError> {
+        delegate!(self, attendance_history(member, offset, limit))
+    }
+
+    async fn attendance_count(&self, member: &NalgangMember) -> Result<i64, NalgangError> {
+        delegate!(self, attendance_count(member))
+    }
+
+    async fn attendance_history_since(
+        &self,
+        member: &NalgangMember,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError> {
+        delegate!(self, attendance_history_since(member, since))
+    }
+
+    async fn daily_attendance(
+        &self,
+        gid: i64,
+        since: i64,
+    ) -> Result<Vec<AttendanceEntry>, NalgangError> {
+        delegate!(self, daily_attendance(gid, since))
+    }
+
+    async fn reset_day(
+        &self,
+        gid: i64,
+        day_start: i64,
+        since: i64,
+    ) -> Result<Option<Vec<AttendanceEntry>>, NalgangError> {
+        delegate!(self, reset_day(gid, day_start, since))
+    }
+
+    async fn attendance_combos(
+        &self,
+        gid: i64,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError> {
+        delegate!(self, attendance_combos(gid, since, until))
+    }
+
+    async fn scores_at(
+        &self,
+        gid: i64,
+        include_departed: bool,
+        time: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError> {
+        delegate!(self, scores_at(gid, include_departed, time))
+    }
+
+    async fn day_boundary(&self, gid: i64) -> Result<DayBoundary, NalgangError> {
+        delegate!(self, day_boundary(gid))
+    }
+
+    async fn set_day_boundary(&self, gid: i64, boundary: &DayBoundary) -> Result<(), NalgangError> {
+        delegate!(self, set_day_boundary(gid, boundary))
+    }
+
+    async fn hide_departed(&self, gid: i64) -> Result<bool, NalgangError> {
+        delegate!(self, hide_departed(gid))
+    }
+
+    async fn set_hide_departed(&self, gid: i64, hide_departed: bool) -> Result<(), NalgangError> {
+        delegate!(self, set_hide_departed(gid, hide_departed))
+    }
+
+    async fn summary_channel(&self, gid: i64) -> Result<Option<i64>, NalgangError> {
+        delegate!(self, summary_channel(gid))
+    }
+
+    async fn set_summary_channel(
+        &self,
+        gid: i64,
+        channel_id: Option<i64>,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, set_summary_channel(gid, channel_id))
+    }
+
+    async fn set_reminder(
+        &self,
+        member: &NalgangMember,
+        remind_time: RemindTime,
+        delivery: ReminderDelivery,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, set_reminder(member, remind_time, delivery))
+    }
+
+    async fn delete_reminder(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        delegate!(self, delete_reminder(member))
+    }
+
+    async fn reminders(&self, gid: i64) -> Result<Vec<Reminder>, NalgangError> {
+        delegate!(self, reminders(gid))
+    }
+
+    async fn mark_reminded(&self, gid: i64, uid: i64, time: i64) -> Result<(), NalgangError> {
+        delegate!(self, mark_reminded(gid, uid, time))
+    }
+
+    async fn reminder_setting(&self, gid: i64) -> Result<ReminderSetting, NalgangError> {
+        delegate!(self, reminder_setting(gid))
+    }
+
+    async fn set_reminder_setting(
+        &self,
+        gid: i64,
+        setting: &ReminderSetting,
+    ) -> Result<(), NalgangError> {
+        delegate!(self, set_reminder_setting(gid, setting))
+    }
+
+    async fn role_rewards(&self, gid: i64) -> Result<Vec<RoleReward>, NalgangError> {
+        delegate!(self, role_rewards(gid))
+    }
+
+    async fn set_role_reward(&self, gid: i64, reward: &RoleReward) -> Result<(), NalgangError> {
+        delegate!(self, set_role_reward(gid, reward))
+    }
+
+    async fn delete_role_reward(&self, gid: i64, role_id: i64) -> Result<bool, NalgangError> {
+        delegate!(self, delete_role_reward(gid, role_id))
+    }
+
+    async fn scoring_rule(&self, gid: i64) -> Result<ScoringRule, NalgangError> {
+        delegate!(self, scoring_rule(gid))
+    }
+
+    async fn set_scoring_rule(&self, gid: i64, rule: &ScoringRule) -> Result<(), NalgangError> {
+        delegate!(self, set_scoring_rule(gid, rule))
+    }
+}
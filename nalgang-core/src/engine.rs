@@ -0,0 +1,958 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::achievement::{Achievement, AttendanceRecord};
+use crate::archive::{
+    ArchivedSettings, ConflictPolicy, GuildArchive, ImportReport, ARCHIVE_VERSION,
+};
+use crate::audit::{AdjustField, Adjustment, AuditEntry};
+use crate::clock::Clock;
+use crate::legacy::{LegacyConversion, LegacyDump};
+use crate::period::Period;
+use crate::reminder::{DueReminder, RemindTime, ReminderDelivery, ReminderSetting};
+use crate::role_reward::{RoleChanges, RoleReward};
+use crate::scoring::{FreezeRule, ScoringRule, ScoringRuleChange};
+use crate::shop::{ExpiredRole, InventoryItem, ShopItem, MAX_ITEM_DURATION};
+use crate::storage::Storage;
+use crate::summary::DailySummary;
+use crate::timezone::{DayBoundary, GuildTimezone};
+use crate::{
+    nalgang_error, utils, AttendanceCalendar, AttendanceEntry, HistoryPage, NalgangError,
+    NalgangErrorInner, NalgangMember, RankingEntry, RankingFilter, RankingKind, RankingPage,
+    ScoreHistory,
+};
+
+// Result of a successful attendance
+pub struct Attendance {
+    pub rank: i64,
+    pub combo: i64,
+    pub earned_point: i64,
+    // Whether this attendance opened a new attendance day of the guild
+    pub first_of_day: bool,
+    // Missed days covered by freezes, oldest first
+    pub frozen_days: Vec<NaiveDate>,
+    pub freeze_earned: bool,
+    // Achievements first earned by this attendance, filled by the storage
+    pub achievements: Vec<Achievement>,
+}
+
+// Last attendance of a guild, as stored in AttendanceTimeCount
+#[derive(sqlx::FromRow)]
+pub struct GuildAttendance {
+    pub hit_count: i64,
+    pub hit_time: i64,
+}
+
+// Decides rank, combo and points of an attendance of `member` at `current_time`.
+// `member` must hold its stored data.
+pub fn resolve_attendance(
+    member: &NalgangMember,
+    guild: &GuildAttendance,
+    boundary: &DayBoundary,
+    rule: &ScoringRule,
+    current_time: i64,
+) -> Result<Attendance, NalgangError> {
+    let member_hit_time = member.hit_time.unwrap();
+    let rank_boundary_time = boundary.following(guild.hit_time, 1);
+    let combo_boundary_time = boundary.following(member_hit_time, 2);
+
+    let (rank, first_of_day) = if current_time >= rank_boundary_time {
+        (0, true)
+    } else {
+        // Raise error if user tries to do duplicate hit.
+        // day_start <= t < current_time < boundary_time, then duplicate hit!
+        if boundary.round_down(guild.hit_time) <= member_hit_time {
+            return Err(nalgang_error!(NalgangErrorInner::DuplicateAttendance));
+        }
+        (guild.hit_count + 1, false)
+    };
+
+    let freezes = member.freezes.unwrap_or(0);
+    let mut frozen_days = Vec::new();
+    let combo = if current_time < combo_boundary_time {
+        member.combo.unwrap() + 1
+    } else {
+        // Freezes keep the combo only if they cover every missed day.
+        let last_date = boundary.attendance_date(member_hit_time);
+        let missed = (boundary.attendance_date(current_time) - last_date).num_days() - 1;
+        if member.combo.unwrap() > 0 && missed <= freezes {
+            frozen_days = (1..=missed)
+                .map(|day| last_date + Duration::days(day))
+                .collect();
+            member.combo.unwrap() + 1
+        } else {
+            1
+        }
+    };
+    let freeze_earned =
+        rule.freeze.earned(combo) && freezes - (frozen_days.len() as i64) < rule.freeze.max;
+
+    Ok(Attendance {
+        rank,
+        combo,
+        earned_point: rule.earned_point(rank, combo),
+        first_of_day,
+        frozen_days,
+        freeze_earned,
+        achievements: Vec::new(),
+    })
+}
+
+// Attendances of the day before a daily reset
+pub struct DayRollover {
+    pub gid: i64,
+    pub date: NaiveDate,
+    pub entries: Vec<AttendanceEntry>,
+}
+
+// Attendance engine shared by every frontend
+#[derive(Clone)]
+pub struct Nalgang<S, C> {
+    pub storage: S,
+    pub clock: C,
+}
+
+// Whether a streak last attended at `hit_time` is not broken at `current_time` yet, counting the
+// days freezes cover.
+fn streak_alive(hit_time: i64, freezes: i64, boundary: &DayBoundary, current_time: i64) -> bool {
+    let today = boundary.attendance_date(current_time);
+    hit_time >= boundary.day_start(today - Duration::days(1 + freezes))
+}
+
+// Combo of a member which is not broken at `current_time` yet
+fn alive_combo(member: &NalgangMember, boundary: &DayBoundary, current_time: i64) -> i64 {
+    let (hit_time, freezes) = (member.hit_time.unwrap(), member.freezes.unwrap_or(0));
+    match streak_alive(hit_time, freezes, boundary, current_time) {
+        true => member.combo.unwrap(),
+        false => 0,
+    }
+}
+
+impl<S: Storage, C: Clock> Nalgang<S, C> {
+    pub fn new(storage: S, clock: C) -> Self {
+        Nalgang { storage, clock }
+    }
+
+    pub async fn register_guild(&self, gid: i64) -> Result<(), NalgangError> {
+        self.storage.register_guild(gid).await
+    }
+
+    pub async fn guilds(&self) -> Result<Vec<i64>, NalgangError> {
+        self.storage.guilds().await
+    }
+
+    pub async fn members(&self, gid: i64) -> Result<Vec<NalgangMember>, NalgangError> {
+        self.storage.members(gid).await
+    }
+
+    pub async fn register(&self, member: &mut NalgangMember) -> Result<(), NalgangError> {
+        if self.storage.member_info(member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::DuplicateMemberRegister));
+        }
+        self.storage.register_member(member.gid, member.uid).await
+    }
+
+    pub async fn point(&self, member: &mut NalgangMember) -> Result<(), NalgangError> {
+        match self.storage.member_info(member).await? {
+            true => Ok(()),
+            false => Err(nalgang_error!(NalgangErrorInner::MemberNotExist)),
+        }
+    }
+
+    pub async fn attend(
+        &self,
+        member: &mut NalgangMember,
+        message: &str,
+    ) -> Result<Attendance, NalgangError> {
+        self.storage.attend(member, message, self.clock.now()).await
+    }
+
+    pub async fn achievements(
+        &self,
+        member: &NalgangMember,
+    ) -> Result<Vec<(Achievement, i64)>, NalgangError> {
+        self.storage.achievements(member).await
+    }
+
+    // Evaluates the achievements over the attendance history of every member of the guild, for
+    // the attendances made before achievements existed. Returns the number of achievements given.
+    pub async fn backfill_achievements(&self, gid: i64) -> Result<usize, NalgangError> {
+        let boundary = self.storage.day_boundary(gid).await?;
+        let mut granted = 0;
+        for member in self.storage.members(gid).await? {
+            let history = self
+                .storage
+                .attendance_history_since(&member, i64::MIN)
+                .await?;
+            let mut achieved: Vec<(Achievement, i64)> = Vec::new();
+            for (i, entry) in history.iter().enumerate() {
+                let record = AttendanceRecord {
+                    rank: entry.hit_rank,
+                    combo: entry.hit_combo,
+                    count: i as i64 + 1,
+                    day_time: entry.hit_time
+                        - boundary.day_start(boundary.attendance_date(entry.hit_time)),
+                };
+                for achievement in Achievement::evaluate(&record) {
+                    if !achieved.iter().any(|(a, _)| *a == achievement) {
+                        achieved.push((achievement, entry.hit_time));
+                    }
+                }
+            }
+            granted += self
+                .storage
+                .grant_achievements(&member, &achieved)
+                .await?
+                .len();
+        }
+        Ok(granted)
+    }
+
+    // Returns the freeze rule the member bought with.
+    pub async fn buy_freezes(
+        &self,
+        member: &mut NalgangMember,
+        count: i64,
+    ) -> Result<FreezeRule, NalgangError> {
+        if count <= 0 {
+            return Err(nalgang_error!(NalgangErrorInner::InvalidFreezeCount));
+        }
+        let rule = self.storage.scoring_rule(member.gid).await?.freeze;
+        if count > rule.max {
+            return Err(nalgang_error!(NalgangErrorInner::FreezeLimitExceeded));
+        }
+        // No score is large enough for a price past i64.
+        if rule.price.checked_mul(count).is_none() {
+            return Err(nalgang_error!(NalgangErrorInner::InsufficientScore));
+        }
+        self.storage
+            .buy_freezes(member, count, &rule, self.clock.now())
+            .await?;
+        Ok(rule)
+    }
+
+    pub async fn set_shop_item(&self, gid: i64, item: &ShopItem) -> Result<(), NalgangError> {
+        let valid = !item.name.trim().is_empty()
+            && item.price >= 0
+            && item.stock.unwrap_or(0) >= 0
+            && (1..=MAX_ITEM_DURATION).contains(&item.duration.unwrap_or(1));
+        if !valid {
+            return Err(nalgang_error!(NalgangErrorInner::InvalidShopItem));
+        }
+        self.storage.set_shop_item(gid, item).await
+    }
+
+    pub async fn delete_shop_item(&self, gid: i64, name: &str) -> Result<bool, NalgangError> {
+        self.storage.delete_shop_item(gid, name).await
+    }
+
+    pub async fn shop_items(&self, gid: i64) -> Result<Vec<ShopItem>, NalgangError> {
+        self.storage.shop_items(gid).await
+    }
+
+    pub async fn buy_item(
+        &self,
+        member: &mut NalgangMember,
+        name: &str,
+    ) -> Result<InventoryItem, NalgangError> {
+        self.storage.buy_item(member, name, self.clock.now()).await
+    }
+
+    // Undoes a purchase whose role could not be granted.
+    pub async fn cancel_purchase(
+        &self,
+        member: &mut NalgangMember,
+        purchase_id: i64,
+    ) -> Result<bool, NalgangError> {
+        self.storage.cancel_purchase(member, purchase_id).await
+    }
+
+    pub async fn inventory(
+        &self,
+        member: &NalgangMember,
+    ) -> Result<Vec<InventoryItem>, NalgangError> {
+        self.storage.inventory(member, self.clock.now()).await
+    }
+
+    // Expires the purchases of the guild which ran out, returning the roles to take back.
+    pub async fn expire_items(&self, gid: i64) -> Result<Vec<ExpiredRole>, NalgangError> {
+        self.storage.expire_items(gid, self.clock.now()).await
+    }
+
+    pub async fn transfer(
+        &self,
+        sender: &mut NalgangMember,
+        receiver: &mut NalgangMember,
+        amount: i64,
+    ) -> Result<(), NalgangError> {
+        if amount <= 0 {
+            return Err(nalgang_error!(NalgangErrorInner::InvalidTransferAmount));
+        }
+        if sender.uid == receiver.uid {
+            return Err(nalgang_error!(NalgangErrorInner::SelfTransfer));
+        }
+        self.storage
+            .transfer(sender, receiver, amount, self.clock.now())
+            .await
+    }
+
+    // Changes the score or the combo of `target` on behalf of the admin `actor`, keeping the
+    // reason in the audit log.
+    pub async fn adjust_member(
+        &self,
+        actor: &NalgangMember,
+        target: &mut NalgangMember,
+        field: AdjustField,
+        adjustment: Adjustment,
+        reason: &str,
+    ) -> Result<AuditEntry, NalgangError> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(nalgang_error!(NalgangErrorInner::MissingAuditReason));
+        }
+        self.storage
+            .adjust_member(
+                actor.uid,
+                target,
+                field,
+                adjustment,
+                reason,
+                self.clock.now(),
+            )
+            .await
+    }
+
+    pub async fn audit_log(&self, gid: i64, limit: i64) -> Result<Vec<AuditEntry>, NalgangError> {
+        self.storage.audit_log(gid, limit).await
+    }
+
+    pub async fn issue_token(&self, member: &NalgangMember) -> Result<String, NalgangError> {
+        let token = utils::generate_random_bytes();
+        match self.storage.insert_token(member, &token).await? {
+            true => Ok(token),
+            false => Err(nalgang_error!(NalgangErrorInner::DuplidateTokenIssue)),
+        }
+    }
+
+    pub async fn delete_token(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        self.storage.delete_token(member).await
+    }
+
+    pub async fn token_owner(&self, token: &str) -> Result<Option<NalgangMember>, NalgangError> {
+        self.storage.token_owner(token).await
+    }
+
+    pub async fn ranking(&self, gid: i64) -> Result<Vec<RankingEntry>, NalgangError> {
+        self.storage.ranking(gid).await
+    }
+
+    async fn ranking_filter(
+        &self,
+        gid: i64,
+        kind: RankingKind,
+        period: Period,
+    ) -> Result<RankingFilter, NalgangError> {
+        let current_time = self.clock.now();
+        let boundary = self.storage.day_boundary(gid).await?;
+        let today = boundary.attendance_date(current_time);
+        Ok(RankingFilter {
+            gid,
+            kind,
+            window: period.window(&boundary, current_time),
+            include_departed: !self.storage.hide_departed(gid).await?,
+            alive_since: boundary.day_start(today - Duration::days(1)),
+            day_end: boundary.day_start(today + Duration::days(1)),
+        })
+    }
+
+    // Returns the `page`th page of the `kind` ranking of `period`, clamped to the existing pages.
+    // The all time score ranking uses the current score, other periods the points earned in them.
+    pub async fn ranking_page(
+        &self,
+        gid: i64,
+        kind: RankingKind,
+        period: Period,
+        page: i64,
+        page_size: i64,
+    ) -> Result<RankingPage, NalgangError> {
+        let period = if kind.uses_period() {
+            period
+        } else {
+            Period::AllTime
+        };
+        let filter = self.ranking_filter(gid, kind, period).await?;
+        let count = self.storage.ranked_count(&filter).await?;
+        let page_count = ((count + page_size - 1) / page_size).max(1);
+        let page = page.clamp(0, page_count - 1);
+        let offset = page * page_size;
+        let entries = self
+            .storage
+            .ranking_page(&filter, offset, page_size)
+            .await?;
+        Ok(RankingPage {
+            kind,
+            period,
+            entries,
+            page,
+            page_count,
+            offset,
+        })
+    }
+
+    pub async fn update_profile(
+        &self,
+        member: &NalgangMember,
+        display_name: &str,
+        departed: bool,
+    ) -> Result<(), NalgangError> {
+        self.storage
+            .update_profile(member, display_name, departed)
+            .await
+    }
+
+    pub async fn member_rank(
+        &self,
+        member: &mut NalgangMember,
+        kind: RankingKind,
+        period: Period,
+    ) -> Result<i64, NalgangError> {
+        if !self.storage.member_info(member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+        let filter = self.ranking_filter(member.gid, kind, period).await?;
+        match self.storage.member_rank(&filter, member.uid).await? {
+            Some(rank) => Ok(rank),
+            None => Err(nalgang_error!(NalgangErrorInner::NotRanked)),
+        }
+    }
+
+    // Returns the `page`th page of the attendance history of `member`, latest first.
+    pub async fn history_page(
+        &self,
+        member: &mut NalgangMember,
+        page: i64,
+        page_size: i64,
+    ) -> Result<HistoryPage, NalgangError> {
+        if !self.storage.member_info(member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+        let count = self.storage.attendance_count(member).await?;
+        let page_count = ((count + page_size - 1) / page_size).max(1);
+        let page = page.clamp(0, page_count - 1);
+        let entries = self
+            .storage
+            .attendance_history(member, page * page_size, page_size)
+            .await?;
+        Ok(HistoryPage {
+            entries,
+            page,
+            page_count,
+            boundary: self.storage.day_boundary(member.gid).await?,
+        })
+    }
+
+    // Returns the attended dates of `member` in the last `days` days including today.
+    pub async fn attendance_calendar(
+        &self,
+        member: &NalgangMember,
+        days: i64,
+    ) -> Result<AttendanceCalendar, NalgangError> {
+        let boundary = self.storage.day_boundary(member.gid).await?;
+        let today = boundary.attendance_date(self.clock.now());
+        let since = boundary.day_start(today - Duration::days(days - 1));
+        let entries = self.storage.attendance_history_since(member, since).await?;
+        Ok(AttendanceCalendar {
+            today,
+            days: entries
+                .iter()
+                .map(|entry| (boundary.attendance_date(entry.hit_time), entry.hit_combo))
+                .collect(),
+        })
+    }
+
+    // Returns the score of `member` after each attendance in the last `days` days including today.
+    pub async fn score_history(
+        &self,
+        member: &NalgangMember,
+        days: i64,
+    ) -> Result<ScoreHistory, NalgangError> {
+        let boundary = self.storage.day_boundary(member.gid).await?;
+        let today = boundary.attendance_date(self.clock.now());
+        let since = today - Duration::days(days - 1);
+        let entries = self
+            .storage
+            .attendance_history_since(member, boundary.day_start(since))
+            .await?;
+        Ok(ScoreHistory {
+            since,
+            today,
+            scores: entries
+                .iter()
+                .map(|entry| (boundary.attendance_date(entry.hit_time), entry.hit_score))
+                .collect(),
+        })
+    }
+
+    // Returns the local date of the current attendance day with its attendances.
+    pub async fn today_attendance(
+        &self,
+        gid: i64,
+    ) -> Result<(NaiveDate, Vec<AttendanceEntry>), NalgangError> {
+        let current_time = self.clock.now();
+        let boundary = self.storage.day_boundary(gid).await?;
+        let entries = self
+            .storage
+            .daily_attendance(gid, boundary.round_down(current_time))
+            .await?;
+        Ok((boundary.attendance_date(current_time), entries))
+    }
+
+    // Starts the current attendance day of the guild if it has not been started yet.
+    // Returns the attendances of the previous day when it does.
+    pub async fn day_rollover(&self, gid: i64) -> Result<Option<DayRollover>, NalgangError> {
+        let boundary = self.storage.day_boundary(gid).await?;
+        let today = boundary.attendance_date(self.clock.now());
+        let yesterday = today - Duration::days(1);
+        let entries = self
+            .storage
+            .reset_day(
+                gid,
+                boundary.day_start(today),
+                boundary.day_start(yesterday),
+            )
+            .await?;
+        Ok(entries.map(|entries| DayRollover {
+            gid,
+            date: yesterday,
+            entries,
+        }))
+    }
+
+    // Recap of the day of `rollover`, with combo rewards, broken streaks and score rank changes.
+    pub async fn daily_summary(&self, rollover: DayRollover) -> Result<DailySummary, NalgangError> {
+        let gid = rollover.gid;
+        let boundary = self.storage.day_boundary(gid).await?;
+        let day_start = boundary.day_start(rollover.date);
+        let day_end = boundary.day_start(rollover.date + Duration::days(1));
+
+        // Streaks alive when the day started which the day broke, counting the days freezes cover.
+        // Members are read as they are now, so the summary is made right after the rollover.
+        let ending_combos: Vec<(i64, i64)> = self
+            .storage
+            .members(gid)
+            .await?
+            .iter()
+            .filter(|member| {
+                alive_combo(member, &boundary, day_start) > 0
+                    && alive_combo(member, &boundary, day_end) == 0
+            })
+            .map(|member| (member.uid, member.combo.unwrap()))
+            .collect();
+        let combos = self
+            .storage
+            .attendance_combos(gid, day_start, day_end)
+            .await?;
+        let include_departed = !self.storage.hide_departed(gid).await?;
+        let scores_before = self
+            .storage
+            .scores_at(gid, include_departed, day_start)
+            .await?;
+        let scores_after = self
+            .storage
+            .scores_at(gid, include_departed, day_end)
+            .await?;
+        let rule = self.storage.scoring_rule(gid).await?;
+
+        Ok(DailySummary::new(
+            rollover,
+            &ending_combos,
+            &combos,
+            &scores_before,
+            &scores_after,
+            &rule,
+        ))
+    }
+
+    // Channel to post the summary of each day in, changed if `channel_id` is given.
+    pub async fn summary_channel(
+        &self,
+        gid: i64,
+        channel_id: Option<Option<i64>>,
+    ) -> Result<Option<i64>, NalgangError> {
+        match channel_id {
+            Some(channel_id) => {
+                self.storage.set_summary_channel(gid, channel_id).await?;
+                Ok(channel_id)
+            }
+            None => self.storage.summary_channel(gid).await,
+        }
+    }
+
+    pub async fn set_reminder(
+        &self,
+        member: &mut NalgangMember,
+        remind_time: &str,
+        delivery: ReminderDelivery,
+    ) -> Result<RemindTime, NalgangError> {
+        let remind_time = remind_time
+            .parse::<RemindTime>()
+            .map_err(|_| nalgang_error!(NalgangErrorInner::InvalidRemindTime))?;
+        if !self.storage.member_info(member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+        self.storage
+            .set_reminder(member, remind_time, delivery)
+            .await?;
+        Ok(remind_time)
+    }
+
+    pub async fn delete_reminder(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        self.storage.delete_reminder(member).await
+    }
+
+    // Reminders of the guild which are due now, marked as sent. A member is reminded once a day
+    // after the reminder time if the combo is long enough, not attended today and still alive,
+    // counting the days freezes cover.
+    pub async fn due_reminders(&self, gid: i64) -> Result<Vec<DueReminder>, NalgangError> {
+        let setting = self.storage.reminder_setting(gid).await?;
+        if !setting.enabled {
+            return Ok(Vec::new());
+        }
+
+        let current_time = self.clock.now();
+        let boundary = self.storage.day_boundary(gid).await?;
+        let today = boundary.attendance_date(current_time);
+        let today_start = boundary.day_start(today);
+
+        let mut due = Vec::new();
+        for reminder in self.storage.reminders(gid).await? {
+            let streak_at_risk = reminder.combo >= setting.min_combo
+                && reminder.hit_time < today_start
+                && streak_alive(reminder.hit_time, reminder.freezes, &boundary, current_time);
+            if !streak_at_risk
+                || reminder.last_reminded >= today_start
+                || current_time < boundary.time_of_day(today, reminder.remind_time.0)
+            {
+                continue;
+            }
+
+            self.storage
+                .mark_reminded(gid, reminder.user_id, current_time)
+                .await?;
+            due.push(DueReminder {
+                user_id: reminder.user_id,
+                combo: reminder.combo,
+                delivery: reminder.delivery,
+            });
+        }
+        Ok(due)
+    }
+
+    pub async fn reminder_setting(
+        &self,
+        gid: i64,
+        enabled: Option<bool>,
+        channel_id: Option<Option<i64>>,
+        min_combo: Option<i64>,
+    ) -> Result<ReminderSetting, NalgangError> {
+        let mut setting = self.storage.reminder_setting(gid).await?;
+        if let Some(enabled) = enabled {
+            setting.enabled = enabled;
+        }
+        if let Some(channel_id) = channel_id {
+            setting.channel_id = channel_id;
+        }
+        if let Some(min_combo) = min_combo {
+            if min_combo < 1 {
+                return Err(nalgang_error!(NalgangErrorInner::InvalidReminderMinCombo));
+            }
+            setting.min_combo = min_combo;
+        }
+
+        if enabled.is_some() || channel_id.is_some() || min_combo.is_some() {
+            self.storage.set_reminder_setting(gid, &setting).await?;
+        }
+        Ok(setting)
+    }
+
+    pub async fn setting(
+        &self,
+        gid: i64,
+        timezone: Option<&str>,
+        day_start_hour: Option<i64>,
+    ) -> Result<DayBoundary, NalgangError> {
+        let mut boundary = self.storage.day_boundary(gid).await?;
+        if let Some(s) = timezone {
+            boundary.timezone = s
+                .parse::<GuildTimezone>()
+                .map_err(|_| nalgang_error!(NalgangErrorInner::InvalidTimezone))?;
+        }
+        if let Some(hour) = day_start_hour {
+            if !(0..24).contains(&hour) {
+                return Err(nalgang_error!(NalgangErrorInner::InvalidDayStartHour));
+            }
+            boundary.day_start_hour = hour as u32;
+        }
+
+        if timezone.is_some() || day_start_hour.is_some() {
+            self.storage.set_day_boundary(gid, &boundary).await?;
+        }
+        Ok(boundary)
+    }
+
+    // Whether departed members are hidden from the ranking, changed if `hide_departed` is given.
+    pub async fn hide_departed(
+        &self,
+        gid: i64,
+        hide_departed: Option<bool>,
+    ) -> Result<bool, NalgangError> {
+        match hide_departed {
+            Some(hide_departed) => {
+                self.storage.set_hide_departed(gid, hide_departed).await?;
+                Ok(hide_departed)
+            }
+            None => self.storage.hide_departed(gid).await,
+        }
+    }
+
+    pub async fn role_rewards(&self, gid: i64) -> Result<Vec<RoleReward>, NalgangError> {
+        self.storage.role_rewards(gid).await
+    }
+
+    pub async fn set_role_reward(&self, gid: i64, reward: &RoleReward) -> Result<(), NalgangError> {
+        if reward.threshold < 1 {
+            return Err(nalgang_error!(NalgangErrorInner::InvalidRoleReward));
+        }
+        self.storage.set_role_reward(gid, reward).await
+    }
+
+    pub async fn delete_role_reward(&self, gid: i64, role_id: i64) -> Result<bool, NalgangError> {
+        self.storage.delete_role_reward(gid, role_id).await
+    }
+
+    // Reward roles to give to and take from a member who has `roles` now.
+    pub async fn role_changes(
+        &self,
+        member: &mut NalgangMember,
+        roles: &[i64],
+    ) -> Result<RoleChanges, NalgangError> {
+        let rewards = self.storage.role_rewards(member.gid).await?;
+        if rewards.is_empty() {
+            return Ok(RoleChanges::default());
+        }
+        if !self.storage.member_info(member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+        let boundary = self.storage.day_boundary(member.gid).await?;
+        let combo = alive_combo(member, &boundary, self.clock.now());
+        Ok(RoleChanges::new(
+            &rewards,
+            member.score.unwrap(),
+            combo,
+            roles,
+        ))
+    }
+
+    // Same as `role_changes` for every (user_id, roles) of the guild. Unregistered users and
+    // users without changes are left out.
+    pub async fn guild_role_changes(
+        &self,
+        gid: i64,
+        members: &[(i64, Vec<i64>)],
+    ) -> Result<Vec<(i64, RoleChanges)>, NalgangError> {
+        let rewards = self.storage.role_rewards(gid).await?;
+        if rewards.is_empty() {
+            return Ok(Vec::new());
+        }
+        let boundary = self.storage.day_boundary(gid).await?;
+        let current_time = self.clock.now();
+        let registered: HashMap<i64, NalgangMember> = self
+            .storage
+            .members(gid)
+            .await?
+            .into_iter()
+            .map(|member| (member.uid, member))
+            .collect();
+
+        Ok(members
+            .iter()
+            .filter_map(|(uid, roles)| {
+                let member = registered.get(uid)?;
+                let combo = alive_combo(member, &boundary, current_time);
+                let changes = RoleChanges::new(&rewards, member.score.unwrap(), combo, roles);
+                (!changes.is_empty()).then_some((*uid, changes))
+            })
+            .collect())
+    }
+
+    pub async fn scoring_rule(
+        &self,
+        gid: i64,
+        change: ScoringRuleChange,
+    ) -> Result<ScoringRule, NalgangError> {
+        let mut rule = self.storage.scoring_rule(gid).await?;
+        match change {
+            ScoringRuleChange::Show => return Ok(rule),
+            ScoringRuleChange::RankPoints(rank_points) => rule.rank_points = rank_points,
+            ScoringRuleChange::SetComboBonus(milestone, bonus) => {
+                rule.set_combo_bonus(milestone, bonus)
+            }
+            ScoringRuleChange::RemoveComboBonus(milestone) => {
+                if !rule.remove_combo_bonus(milestone) {
+                    return Err(nalgang_error!(NalgangErrorInner::ComboBonusNotExist));
+                }
+            }
+            ScoringRuleChange::AnniversaryBonus(bonus) => rule.anniversary_bonus = bonus,
+            ScoringRuleChange::Freeze { price, every, max } => {
+                if [price, every, max].iter().flatten().any(|value| *value < 0) {
+                    return Err(nalgang_error!(NalgangErrorInner::InvalidScoringRule));
+                }
+                rule.freeze.price = price.unwrap_or(rule.freeze.price);
+                rule.freeze.every = every.unwrap_or(rule.freeze.every);
+                rule.freeze.max = max.unwrap_or(rule.freeze.max);
+            }
+            ScoringRuleChange::Reset => rule = ScoringRule::default(),
+        }
+
+        self.storage.set_scoring_rule(gid, &rule).await?;
+        Ok(rule)
+    }
+
+    pub async fn export_guild(&self, gid: i64) -> Result<GuildArchive, NalgangError> {
+        if !self.storage.guilds().await?.contains(&gid) {
+            return Err(nalgang_error!(NalgangErrorInner::GuildNotExist));
+        }
+        let settings = ArchivedSettings::new(
+            &self.storage.day_boundary(gid).await?,
+            self.storage.hide_departed(gid).await?,
+            self.storage.summary_channel(gid).await?,
+            &self.storage.reminder_setting(gid).await?,
+            &self.storage.scoring_rule(gid).await?,
+        );
+        let (members, history) = self.storage.archive_members(gid).await?;
+        Ok(GuildArchive {
+            version: ARCHIVE_VERSION,
+            guild_id: gid,
+            exported_time: self.clock.now(),
+            settings,
+            members,
+            history,
+        })
+    }
+
+    // Imports an archive into the guild. Nothing is written unless the whole archive is valid.
+    pub async fn import_guild(
+        &self,
+        gid: i64,
+        archive: &GuildArchive,
+        policy: ConflictPolicy,
+    ) -> Result<ImportReport, NalgangError> {
+        archive.validate()?;
+        let mut report = self
+            .storage
+            .import_members(gid, archive, policy, self.clock.now())
+            .await?;
+
+        if report.guild_created || policy == ConflictPolicy::Overwrite {
+            let settings = &archive.settings;
+            self.storage
+                .set_day_boundary(gid, &settings.day_boundary()?)
+                .await?;
+            self.storage
+                .set_hide_departed(gid, settings.hide_departed)
+                .await?;
+            self.storage
+                .set_summary_channel(gid, settings.summary_channel)
+                .await?;
+            self.storage
+                .set_reminder_setting(gid, &settings.reminder_setting()?)
+                .await?;
+            self.storage
+                .set_scoring_rule(gid, &settings.scoring_rule()?)
+                .await?;
+            report.settings_restored = true;
+        }
+        Ok(report)
+    }
+
+    // Maps a dump of the previous bot with the day boundary and scoring rule of the guild.
+    pub async fn convert_legacy(
+        &self,
+        gid: i64,
+        dump: &LegacyDump,
+    ) -> Result<LegacyConversion, NalgangError> {
+        let boundary = self.storage.day_boundary(gid).await?;
+        let rule = self.storage.scoring_rule(gid).await?;
+        Ok(dump.convert(&boundary, &rule, self.clock.now()))
+    }
+
+    pub async fn import_legacy(
+        &self,
+        gid: i64,
+        conversion: &LegacyConversion,
+        policy: ConflictPolicy,
+    ) -> Result<ImportReport, NalgangError> {
+        let report = self.import_guild(gid, &conversion.archive, policy).await?;
+        if let Some(attendance) = &conversion.last_attendance {
+            self.storage
+                .restore_guild_attendance(gid, attendance)
+                .await?;
+        }
+        Ok(report)
+    }
+
+    // Members whose score, combo or last attendance differ from what their history records, as
+    // (stored, recorded) pairs. The score is the one recorded by the latest attendance, transfer,
+    // purchase, admin change or import, so a purchase cancelled after later events is not
+    // refunded. With `apply` the recorded values replace the stored ones.
+    pub async fn recompute_members(
+        &self,
+        gid: i64,
+        apply: bool,
+    ) -> Result<Vec<(NalgangMember, NalgangMember)>, NalgangError> {
+        if !self.storage.guilds().await?.contains(&gid) {
+            return Err(nalgang_error!(NalgangErrorInner::GuildNotExist));
+        }
+        let scores: HashMap<i64, i64> = self
+            .storage
+            .scores_at(gid, true, i64::MAX)
+            .await?
+            .into_iter()
+            .collect();
+        let combos: HashMap<i64, (i64, i64)> = self
+            .storage
+            .recorded_combos(gid)
+            .await?
+            .into_iter()
+            .map(|(uid, combo, hit_time)| (uid, (combo, hit_time)))
+            .collect();
+
+        let mut members = self.storage.members(gid).await?;
+        members.sort_by_key(|member| member.uid);
+        let mut changed = Vec::new();
+        for member in members {
+            let (combo, hit_time) = combos.get(&member.uid).copied().unwrap_or((0, 0));
+            let mut recorded = member.clone();
+            recorded.update_data(
+                scores.get(&member.uid).copied().unwrap_or(0),
+                combo,
+                hit_time,
+            );
+            if (recorded.score, recorded.combo, recorded.hit_time)
+                != (member.score, member.combo, member.hit_time)
+            {
+                changed.push((member, recorded));
+            }
+        }
+
+        if apply {
+            let recorded: Vec<NalgangMember> = changed
+                .iter()
+                .map(|(_, recorded)| recorded.clone())
+                .collect();
+            self.storage.update_members(&recorded).await?;
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+
+#[macro_export]
+macro_rules! nalgang_error {
+    ($error: expr) => {
+        $crate::NalgangError {
+            kind: $error,
+            file: file!(),
+            line: line!(),
+        }
+    };
+}
+
+pub struct NalgangError {
+    pub kind: NalgangErrorInner,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+pub enum NalgangErrorInner {
+    DuplicateAttendance,
+    DuplicateMemberRegister,
+    DuplicateGuildRegister,
+    DuplidateTokenIssue,
+    MemberNotExist,
+    GuildNotExist,
+    SelfTransfer,
+    ReceiverNotExist,
+    InsufficientScore,
+    InvalidTransferAmount,
+    InvalidTimezone,
+    InvalidDayStartHour,
+    InvalidScoringRule,
+    ComboBonusNotExist,
+    InvalidPeriod,
+    NotRanked,
+    InvalidRemindTime,
+    InvalidReminderMinCombo,
+    InvalidFreezeCount,
+    FreezeLimitExceeded,
+    InvalidShopItem,
+    ShopItemNotExist,
+    OutOfStock,
+    InvalidRoleReward,
+    InvalidAdjustment,
+    MissingAuditReason,
+    // Describes what is wrong with the archive.
+    InvalidArchive(String),
+    BufferError(std::fmt::Error),
+    UnhandledDatabaseError(sqlx::Error),
+}
+
+impl fmt::Display for NalgangError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self.kind {
+            NalgangErrorInner::DuplicateAttendance => "duplicate attendance".to_string(),
+            NalgangErrorInner::DuplicateMemberRegister => "duplciate member register".to_string(),
+            NalgangErrorInner::DuplicateGuildRegister => "duplicate guild register".to_string(),
+            NalgangErrorInner::DuplidateTokenIssue => "duplciate token issue".to_string(),
+            NalgangErrorInner::MemberNotExist => "member not exist".to_string(),
+            NalgangErrorInner::GuildNotExist => "guild_not_exist".to_string(),
+            NalgangErrorInner::SelfTransfer => "self transfer".to_string(),
+            NalgangErrorInner::ReceiverNotExist => "receiver not exist".to_string(),
+            NalgangErrorInner::InsufficientScore => "insufficient score".to_string(),
+            NalgangErrorInner::InvalidTransferAmount => "invalid transfer amount".to_string(),
+            NalgangErrorInner::InvalidTimezone => "invalid timezone".to_string(),
+            NalgangErrorInner::InvalidDayStartHour => "invalid day start hour".to_string(),
+            NalgangErrorInner::InvalidScoringRule => "invalid scoring rule".to_string(),
+            NalgangErrorInner::ComboBonusNotExist => "combo bonus not exist".to_string(),
+            NalgangErrorInner::InvalidPeriod => "invalid period".to_string(),
+            NalgangErrorInner::NotRanked => "not ranked".to_string(),
+            NalgangErrorInner::InvalidRemindTime => "invalid remind time".to_string(),
+            NalgangErrorInner::InvalidReminderMinCombo => "invalid reminder min combo".to_string(),
+            NalgangErrorInner::InvalidFreezeCount => "invalid freeze count".to_string(),
+            NalgangErrorInner::FreezeLimitExceeded => "freeze limit exceeded".to_string(),
+            NalgangErrorInner::InvalidShopItem => "invalid shop item".to_string(),
+            NalgangErrorInner::ShopItemNotExist => "shop item not exist".to_string(),
+            NalgangErrorInner::OutOfStock => "out of stock".to_string(),
+            NalgangErrorInner::InvalidRoleReward => "invalid role reward".to_string(),
+            NalgangErrorInner::InvalidAdjustment => "invalid adjustment".to_string(),
+            NalgangErrorInner::MissingAuditReason => "missing audit reason".to_string(),
+            NalgangErrorInner::InvalidArchive(reason) => format!("invalid archive ({})", reason),
+            NalgangErrorInner::BufferError(_) => "buffer error".to_string(),
+            NalgangErrorInner::UnhandledDatabaseError(e) => e.to_string(),
+        };
+        writeln!(f, "{} error raised at {}:{}", s, self.file, self.line)
+    }
+}
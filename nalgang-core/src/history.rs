@@ -0,0 +1,43 @@
+use chrono::NaiveDate;
+
+use crate::timezone::DayBoundary;
+
+// One attendance of a member, as stored in AttendanceHistory
+#[derive(sqlx::FromRow)]
+pub struct HistoryEntry {
+    pub hit_time: i64,
+    pub hit_rank: i64,
+    pub hit_point: i64,
+    // Score of the member right after the attendance
+    pub hit_score: i64,
+    pub hit_combo: i64,
+    pub hit_message: Option<String>,
+}
+
+// One page of the attendance history of a member, latest first
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub page: i64,
+    pub page_count: i64,
+    // Day boundary of the guild to tell the attendance date of each entry
+    pub boundary: DayBoundary,
+}
+
+impl HistoryPage {
+    pub fn date(&self, entry: &HistoryEntry) -> NaiveDate {
+        self.boundary.attendance_date(entry.hit_time)
+    }
+}
+
+// Attendance dates of a member with the combo reached on each of them
+pub struct AttendanceCalendar {
+    pub today: NaiveDate,
+    pub days: Vec<(NaiveDate, i64)>,
+}
+
+// Score of a member after each attendance since `since`, oldest first
+pub struct ScoreHistory {
+    pub since: NaiveDate,
+    pub today: NaiveDate,
+    pub scores: Vec<(NaiveDate, i64)>,
+}
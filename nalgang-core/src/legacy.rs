@@ -0,0 +1,408 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::archive::{
+    ArchivedAttendance, ArchivedMember, ArchivedSettings, GuildArchive, ARCHIVE_VERSION,
+};
+use crate::engine::GuildAttendance;
+use crate::reminder::ReminderSetting;
+use crate::scoring::ScoringRule;
+use crate::timezone::{DayBoundary, MAX_TIMESTAMP};
+
+// Dump of the previous Python bot, one JSON object per guild. Times are unix seconds.
+//   {
+//     "guild_id": 1234,
+//     "members": [
+//       {"user_id": 1, "name": "nick", "score": 120, "combo": 3, "last_hit": 1669852800}
+//     ],
+//     "history": [
+//       {"user_id": 1, "time": 1669852800, "message": "hello", "point": 10}
+//     ]
+//   }
+// "name", "message" and "point" may be left out. An attendance without a point earns what the
+// scoring rule of the guild gives for its rank and combo.
+#[derive(Deserialize)]
+pub struct LegacyDump {
+    pub guild_id: i64,
+    pub members: Vec<LegacyMember>,
+    #[serde(default)]
+    pub history: Vec<LegacyAttendance>,
+}
+
+#[derive(Deserialize)]
+pub struct LegacyMember {
+    pub user_id: i64,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub score: i64,
+    pub combo: i64,
+    pub last_hit: i64,
+}
+
+#[derive(Deserialize)]
+pub struct LegacyAttendance {
+    pub user_id: i64,
+    pub time: i64,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub point: Option<i64>,
+}
+
+// Record of a dump which was left out or does not agree with the rest of the dump
+pub enum LegacyIssue {
+    // Left out, the first member of the id is kept.
+    DuplicateMember(i64),
+    // Left out, since the combo is negative or the last attendance time is out of range.
+    InvalidMember(i64),
+    // Left out, since the user is not a member of the dump.
+    UnmatchedAttendance {
+        user_id: i64,
+        time: i64,
+    },
+    // Left out, since the time is negative or after the conversion.
+    InvalidAttendance {
+        user_id: i64,
+        time: i64,
+    },
+    // Left out, since the member already attended on the same day.
+    DuplicateAttendance {
+        user_id: i64,
+        time: i64,
+    },
+    // The member keeps the values of the dump, which differ from those of the history.
+    ScoreMismatch {
+        user_id: i64,
+        score: i64,
+        history: i64,
+    },
+    ComboMismatch {
+        user_id: i64,
+        combo: i64,
+        history: i64,
+    },
+    LastHitMismatch {
+        user_id: i64,
+        last_hit: i64,
+        history: i64,
+    },
+}
+
+impl fmt::Display for LegacyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LegacyIssue::DuplicateMember(user_id) => {
+                write!(
+                    f,
+                    "member {} appears more than once, kept the first",
+                    user_id
+                )
+            }
+            LegacyIssue::InvalidMember(user_id) => write!(
+                f,
+                "member {} has a negative combo or an invalid last hit, left out",
+                user_id
+            ),
+            LegacyIssue::UnmatchedAttendance { user_id, time } => write!(
+                f,
+                "attendance of {} at {} has no member, left out",
+                user_id, time
+            ),
+            LegacyIssue::InvalidAttendance { user_id, time } => write!(
+                f,
+                "attendance of {} at {} is out of range, left out",
+                user_id, time
+            ),
+            LegacyIssue::DuplicateAttendance { user_id, time } => write!(
+                f,
+                "attendance of {} at {} is the second of its day, left out",
+                user_id, time
+            ),
+            LegacyIssue::ScoreMismatch {
+                user_id,
+                score,
+                history,
+            } => write!(
+                f,
+                "member {} has score {} but its history adds up to {}",
+                user_id, score, history
+            ),
+            LegacyIssue::ComboMismatch {
+                user_id,
+                combo,
+                history,
+            } => write!(
+                f,
+                "member {} has combo {} but its history ends with {}",
+                user_id, combo, history
+            ),
+            LegacyIssue::LastHitMismatch {
+                user_id,
+                last_hit,
+                history,
+            } => write!(
+                f,
+                "member {} last hit at {} but its history ends at {}",
+                user_id, last_hit, history
+            ),
+        }
+    }
+}
+
+// Dump mapped into an archive of the guild
+pub struct LegacyConversion {
+    pub archive: GuildArchive,
+    // Last attendance of the guild, to keep ranking the day of the dump
+    pub last_attendance: Option<GuildAttendance>,
+    pub issues: Vec<LegacyIssue>,
+}
+
+impl LegacyDump {
+    // Ranks the attendances of each day by time and replays the history of each member for its
+    // combos and scores, with the day boundary and scoring rule of the guild.
+    pub fn convert(
+        &self,
+        boundary: &DayBoundary,
+        rule: &ScoringRule,
+        current_time: i64,
+    ) -> LegacyConversion {
+        let mut issues = Vec::new();
+        // Times later than the conversion cannot be replayed, nor converted to dates past chrono.
+        let valid_time = |time: i64| (0..=current_time.min(MAX_TIMESTAMP)).contains(&time);
+
+        let mut members: Vec<&LegacyMember> = Vec::new();
+        let mut seen = HashSet::new();
+        for member in self.members.iter() {
+            if !seen.insert(member.user_id) {
+                issues.push(LegacyIssue::DuplicateMember(member.user_id));
+            } else if member.combo < 0 || !valid_time(member.last_hit) {
+                issues.push(LegacyIssue::InvalidMember(member.user_id));
+            } else {
+                members.push(member);
+            }
+        }
+        let member_ids: HashSet<i64> = members.iter().map(|member| member.user_id).collect();
+
+        // Attendances of each day in time order, one per member and day
+        let mut history: Vec<&LegacyAttendance> = self.history.iter().collect();
+        history.sort_by_key(|entry| (entry.time, entry.user_id));
+        let mut days: BTreeMap<NaiveDate, Vec<&LegacyAttendance>> = BTreeMap::new();
+        let mut attended = HashSet::new();
+        for entry in history {
+            if !valid_time(entry.time) {
+                issues.push(LegacyIssue::InvalidAttendance {
+                    user_id: entry.user_id,
+                    time: entry.time,
+                });
+                continue;
+            }
+            let date = boundary.attendance_date(entry.time);
+            if !member_ids.contains(&entry.user_id) {
+                issues.push(LegacyIssue::UnmatchedAttendance {
+                    user_id: entry.user_id,
+                    time: entry.time,
+                });
+            } else if !attended.insert((entry.user_id, date)) {
+                issues.push(LegacyIssue::DuplicateAttendance {
+                    user_id: entry.user_id,
+                    time: entry.time,
+                });
+            } else {
+                days.entry(date).or_default().push(entry);
+            }
+        }
+        let last_attendance = days.values().next_back().map(|entries| GuildAttendance {
+            hit_count: entries.len() as i64 - 1,
+            hit_time: entries.last().unwrap().time,
+        });
+
+        // (date, combo, score, time) of the last attendance of each member
+        let mut replayed: HashMap<i64, (NaiveDate, i64, i64, i64)> = HashMap::new();
+        let mut archived_history = Vec::new();
+        for (date, entries) in days.iter() {
+            for (rank, entry) in entries.iter().enumerate() {
+                let rank = rank as i64;
+                let (combo, score) = match replayed.get(&entry.user_id) {
+                    Some((last_date, combo, score, _)) if (*date - *last_date).num_days() == 1 => {
+                        (combo + 1, *score)
+                    }
+                    Some((_, _, score, _)) => (1, *score),
+                    None => (1, 0),
+                };
+                let point = entry
+                    .point
+                    .unwrap_or_else(|| rule.earned_point(rank, combo));
+                replayed.insert(entry.user_id, (*date, combo, score + point, entry.time));
+                archived_history.push(ArchivedAttendance {
+                    user_id: entry.user_id,
+                    hit_time: entry.time,
+                    hit_rank: rank,
+                    hit_point: point,
+                    hit_score: score + point,
+                    hit_combo: combo,
+                    hit_message: entry.message.clone(),
+                });
+            }
+        }
+
+        let mut archived_members = Vec::new();
+        for member in members {
+            let (_, history_combo, history_score, history_last_hit) = replayed
+                .get(&member.user_id)
+                .copied()
+                .unwrap_or((NaiveDate::MIN, 0, 0, 0));
+            let user_id = member.user_id;
+            if member.score != history_score {
+                issues.push(LegacyIssue::ScoreMismatch {
+                    user_id,
+                    score: member.score,
+                    history: history_score,
+                });
+            }
+            if member.combo != history_combo {
+                issues.push(LegacyIssue::ComboMismatch {
+                    user_id,
+                    combo: member.combo,
+                    history: history_combo,
+                });
+            }
+            if member.last_hit != history_last_hit {
+                issues.push(LegacyIssue::LastHitMismatch {
+                    user_id,
+                    last_hit: member.last_hit,
+                    history: history_last_hit,
+                });
+            }
+            archived_members.push(ArchivedMember {
+                user_id,
+                score: member.score,
+                combo: member.combo,
+                hit_time: member.last_hit,
+                freezes: 0,
+                display_name: member.name.clone(),
+                departed: false,
+            });
+        }
+
+        LegacyConversion {
+            archive: GuildArchive {
+                version: ARCHIVE_VERSION,
+                guild_id: self.guild_id,
+                exported_time: current_time,
+                settings: ArchivedSettings::new(
+                    boundary,
+                    false,
+                    None,
+                    &ReminderSetting::default(),
+                    rule,
+                ),
+                members: archived_members,
+                history: archived_history,
+            },
+            last_attendance,
+            issues,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Monday 09:00 in Asia/Seoul, 3 hours after the default day start
+    const T0: i64 = 1704067200;
+    const DAY: i64 = 86400;
+
+    fn member(user_id: i64, score: i64, combo: i64, last_hit: i64) -> LegacyMember {
+        LegacyMember {
+            user_id,
+            name: None,
+            score,
+            combo,
+            last_hit,
+        }
+    }
+
+    fn attendance(user_id: i64, time: i64, point: Option<i64>) -> LegacyAttendance {
+        LegacyAttendance {
+            user_id,
+            time,
+            message: None,
+            point,
+        }
+    }
+
+    #[test]
+    fn replays_history() {
+        let dump = LegacyDump {
+            guild_id: 1,
+            members: vec![member(1, 25, 2, T0 + DAY), member(2, 5, 1, T0 + 1)],
+            history: vec![
+                attendance(2, T0 + 1, None),
+                attendance(1, T0, None),
+                attendance(1, T0 + DAY, Some(15)),
+            ],
+        };
+        let conversion = dump.convert(
+            &DayBoundary::default(),
+            &ScoringRule::default(),
+            T0 + 2 * DAY,
+        );
+        assert!(conversion.issues.is_empty());
+        let history: Vec<(i64, i64, i64, i64)> = conversion
+            .archive
+            .history
+            .iter()
+            .map(|e| (e.user_id, e.hit_rank, e.hit_combo, e.hit_score))
+            .collect();
+        assert_eq!(history, vec![(1, 0, 1, 10), (2, 1, 1, 5), (1, 0, 2, 25)]);
+        let last = conversion.last_attendance.unwrap();
+        assert_eq!((last.hit_count, last.hit_time), (0, T0 + DAY));
+        assert!(conversion.archive.validate().is_ok());
+    }
+
+    #[test]
+    fn reports_issues() {
+        let dump = LegacyDump {
+            guild_id: 1,
+            members: vec![
+                member(1, 99, 5, T0),
+                member(1, 0, 0, 0),
+                member(2, 0, -1, 0),
+                member(3, 0, 0, T0 + DAY + 1),
+            ],
+            history: vec![
+                attendance(1, T0, None),
+                attendance(1, T0 + 60, None),
+                attendance(3, T0, None),
+                attendance(1, -1, None),
+                attendance(1, 99999999999999, None),
+            ],
+        };
+        let conversion = dump.convert(&DayBoundary::default(), &ScoringRule::default(), T0 + DAY);
+        let issues: Vec<String> = conversion.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "member 1 appears more than once, kept the first".to_string(),
+                "member 2 has a negative combo or an invalid last hit, left out".to_string(),
+                "member 3 has a negative combo or an invalid last hit, left out".to_string(),
+                "attendance of 1 at -1 is out of range, left out".to_string(),
+                format!("attendance of 3 at {} has no member, left out", T0),
+                format!(
+                    "attendance of 1 at {} is the second of its day, left out",
+                    T0 + 60
+                ),
+                "attendance of 1 at 99999999999999 is out of range, left out".to_string(),
+                "member 1 has score 99 but its history adds up to 10".to_string(),
+                "member 1 has combo 5 but its history ends with 1".to_string(),
+            ]
+        );
+        assert_eq!(conversion.archive.members.len(), 1);
+        assert_eq!(conversion.archive.history.len(), 1);
+        assert!(conversion.archive.validate().is_ok());
+    }
+}
@@ -0,0 +1,35 @@
+// Attendance engine of nalgang, independent of any chat frontend
+pub mod achievement;
+pub mod archive;
+pub mod audit;
+mod clock;
+mod database;
+mod engine;
+mod error;
+mod history;
+pub mod legacy;
+mod member;
+pub mod period;
+mod postgres;
+mod query;
+mod ranking;
+pub mod reminder;
+pub mod role_reward;
+pub mod scoring;
+pub mod shop;
+mod sqlite;
+mod storage;
+pub mod summary;
+pub mod timezone;
+mod utils;
+
+pub use clock::{Clock, SystemClock};
+pub use database::DatabaseStorage;
+pub use engine::{resolve_attendance, Attendance, DayRollover, GuildAttendance, Nalgang};
+pub use error::{NalgangError, NalgangErrorInner};
+pub use history::{AttendanceCalendar, HistoryEntry, HistoryPage, ScoreHistory};
+pub use member::{AttendanceEntry, NalgangMember};
+pub use postgres::PostgresStorage;
+pub use ranking::{RankingEntry, RankingFilter, RankingKind, RankingPage};
+pub use sqlite::SqliteStorage;
+pub use storage::Storage;
@@ -0,0 +1,38 @@
+#[derive(Clone)]
+pub struct NalgangMember {
+    pub uid: i64,
+    pub gid: i64,
+    pub score: Option<i64>,
+    pub combo: Option<i64>,
+    pub hit_time: Option<i64>,
+    pub freezes: Option<i64>,
+}
+
+impl NalgangMember {
+    pub fn new(user_id: i64, guild_id: i64) -> Self {
+        NalgangMember {
+            uid: user_id,
+            gid: guild_id,
+            score: None,
+            combo: None,
+            hit_time: None,
+            freezes: None,
+        }
+    }
+
+    pub fn update_data(&mut self, score: i64, combo: i64, hit_time: i64) {
+        self.score = Some(score);
+        self.combo = Some(combo);
+        self.hit_time = Some(hit_time);
+    }
+}
+
+// `display_name` and `departed` are the last known profile of the member.
+#[derive(sqlx::FromRow)]
+pub struct AttendanceEntry {
+    pub user_id: i64,
+    pub hit_message: Option<String>,
+    pub hit_time: i64,
+    pub display_name: Option<String>,
+    pub departed: bool,
+}
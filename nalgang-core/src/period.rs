@@ -0,0 +1,104 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::timezone::DayBoundary;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Period of a leaderboard, in attendance dates of the guild.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    ThisWeek,
+    ThisMonth,
+    ThisYear,
+    AllTime,
+    // Both dates are inclusive.
+    Custom(NaiveDate, NaiveDate),
+}
+
+impl FromStr for Period {
+    type Err = ();
+
+    // Accepts "이번주", "이번달", "올해", "전체" and "2024-01-01~2024-01-31".
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim() {
+            "이번주" => Ok(Period::ThisWeek),
+            "이번달" => Ok(Period::ThisMonth),
+            "올해" => Ok(Period::ThisYear),
+            "전체" => Ok(Period::AllTime),
+            s => {
+                let (start, end) = s.split_once('~').ok_or(())?;
+                let start = NaiveDate::parse_from_str(start.trim(), DATE_FORMAT).map_err(|_| ())?;
+                let end = NaiveDate::parse_from_str(end.trim(), DATE_FORMAT).map_err(|_| ())?;
+                Period::custom(start, end).ok_or(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Period::ThisWeek => write!(f, "이번주"),
+            Period::ThisMonth => write!(f, "이번달"),
+            Period::ThisYear => write!(f, "올해"),
+            Period::AllTime => write!(f, "전체"),
+            Period::Custom(start, end) => write!(
+                f,
+                "{}~{}",
+                start.format(DATE_FORMAT),
+                end.format(DATE_FORMAT)
+            ),
+        }
+    }
+}
+
+impl Period {
+    // Returns None if `start` is after `end`.
+    pub fn custom(start: NaiveDate, end: NaiveDate) -> Option<Self> {
+        if start > end {
+            return None;
+        }
+        Some(Period::Custom(start, end))
+    }
+
+    pub fn parse_date(s: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(s.trim(), DATE_FORMAT).ok()
+    }
+
+    // Inclusive attendance dates of the period around `today`. None for all time.
+    pub fn dates(&self, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+        match self {
+            Period::ThisWeek => Some((
+                today - Duration::days(today.weekday().num_days_from_monday() as i64),
+                today,
+            )),
+            Period::ThisMonth => Some((today.with_day(1).unwrap(), today)),
+            Period::ThisYear => Some((today.with_ordinal(1).unwrap(), today)),
+            Period::AllTime => None,
+            Period::Custom(start, end) => Some((*start, *end)),
+        }
+    }
+
+    // Time range of the period as seen by a guild at `current_time`. None for all time.
+    pub fn window(&self, boundary: &DayBoundary, current_time: i64) -> Option<RankingWindow> {
+        let today = boundary.attendance_date(current_time);
+        let (start, end) = self.dates(today)?;
+        Some(RankingWindow {
+            since: boundary.day_start(start),
+            until: boundary.day_start(end + Duration::days(1)),
+            days: ((end.min(today) - start).num_days() + 1).max(0),
+        })
+    }
+}
+
+// Unix time range [since, until) of a leaderboard
+#[derive(Clone, Copy)]
+pub struct RankingWindow {
+    pub since: i64,
+    pub until: i64,
+    // Attendance days of the window until today
+    pub days: i64,
+}
@@ -0,0 +1,1814 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::achievement::{Achievement, AttendanceRecord};
+use crate::archive::{
+    ArchivedAttendance, ArchivedMember, ConflictPolicy, GuildArchive, ImportReport,
+};
+use crate::audit::{AdjustField, Adjustment, AuditEntry, IMPORT_ACTOR};
+use crate::engine::{resolve_attendance, Attendance, GuildAttendance};
+use crate::query::{bind_filter, ranking_scores, RECORDED_COMBOS, SCORES_AT};
+use crate::reminder::{RemindTime, Reminder, ReminderDelivery, ReminderSetting};
+use crate::role_reward::{RewardKind, RoleReward};
+use crate::scoring::{ComboMilestone, FreezeRule, ScoringRule};
+use crate::shop::{ExpiredRole, InventoryItem, ShopItem};
+use crate::storage::Storage;
+use crate::timezone::DayBoundary;
+use crate::{
+    nalgang_error, AttendanceEntry, HistoryEntry, NalgangError, NalgangErrorInner, NalgangMember,
+    RankingEntry, RankingFilter,
+};
+
+// Error of a write to a per guild table. The foreign key to Guild fails if the guild is not
+// registered.
+fn guild_write_error(e: sqlx::Error) -> NalgangError {
+    match e {
+        // foreign_key_violation
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("23503") => {
+            nalgang_error!(NalgangErrorInner::GuildNotExist)
+        }
+        e => nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)),
+    }
+}
+
+// Storage backed by a PostgreSQL database, shared by every instance of the bot.
+// The query macros check against the SQLite database, so every query here is checked at runtime.
+// Rows read before a write are locked with FOR UPDATE, since other instances write concurrently.
+#[derive(Clone)]
+pub struct PostgresStorage {
+    pub database: PgPool,
+}
+
+impl PostgresStorage {
+    pub fn new(database: PgPool) -> Self {
+        PostgresStorage { database }
+    }
+
+    pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        sqlx::migrate!("../migrations/postgres")
+            .run(&self.database)
+            .await
+    }
+
+    // Gives back the space of deleted rows and refreshes the statistics of the planner.
+    pub async fn vacuum(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM ANALYZE")
+            .execute(&self.database)
+            .await
+            .map(|_| ())
+    }
+
+    async fn insert_achievements(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        member: &NalgangMember,
+        achievements: &[(Achievement, i64)],
+    ) -> Result<Vec<Achievement>, NalgangError> {
+        let mut granted = Vec::new();
+        for (achievement, achieved_time) in achievements.iter() {
+            let r = sqlx::query(
+                "INSERT INTO MemberAchievement (guild_id, user_id, achievement, achieved_time)
+                    VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+            )
+            .bind(member.gid)
+            .bind(member.uid)
+            .bind(achievement.id())
+            .bind(achieved_time)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            if r.rows_affected() > 0 {
+                granted.push(*achievement);
+            }
+        }
+        Ok(granted)
+    }
+
+    async fn get_member_info<'e, E>(
+        &self,
+        executor: E,
+        member: &mut NalgangMember,
+    ) -> Result<bool, NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    {
+        let row = sqlx::query_as::<_, (i64, i64, i64, i64)>(
+            "SELECT score, combo, hit_time, freezes FROM Member WHERE guild_id=$1 AND user_id=$2",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        match row {
+            Some((score, combo, hit_time, freezes)) => {
+                member.update_data(score, combo, hit_time);
+                member.freezes = Some(freezes);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Same as `get_member_info`, and keeps the member locked until the transaction ends.
+    async fn lock_member_info(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        member: &mut NalgangMember,
+    ) -> Result<bool, NalgangError> {
+        let row = sqlx::query_as::<_, (i64, i64, i64, i64)>(
+            "SELECT score, combo, hit_time, freezes FROM Member WHERE guild_id=$1 AND user_id=$2
+                FOR UPDATE",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        match row {
+            Some((score, combo, hit_time, freezes)) => {
+                member.update_data(score, combo, hit_time);
+                member.freezes = Some(freezes);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn update_member_info<'e, E>(
+        &self,
+        executor: E,
+        member: &NalgangMember,
+    ) -> Result<(), NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    {
+        sqlx::query(
+            "UPDATE Member SET score=$1, combo=$2, hit_time=$3, freezes=$4
+                WHERE guild_id=$5 AND user_id=$6",
+        )
+        .bind(member.score.unwrap())
+        .bind(member.combo.unwrap())
+        .bind(member.hit_time.unwrap())
+        .bind(member.freezes.unwrap())
+        .bind(member.gid)
+        .bind(member.uid)
+        .execute(executor)
+        .await
+        .map(|_| ())
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn get_day_boundary<'e, E>(
+        &self,
+        executor: E,
+        gid: i64,
+    ) -> Result<DayBoundary, NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    {
+        let row = sqlx::query_as::<_, (String, i64)>(
+            "SELECT timezone, day_start_hour FROM GuildSetting WHERE guild_id=$1",
+        )
+        .bind(gid)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        match row {
+            Some((timezone, day_start_hour)) => Ok(DayBoundary {
+                timezone: timezone
+                    .parse()
+                    .map_err(|_| nalgang_error!(NalgangErrorInner::InvalidTimezone))?,
+                day_start_hour: day_start_hour as u32,
+            }),
+            None => Ok(DayBoundary::default()),
+        }
+    }
+
+    async fn get_scoring_rule<'e, E>(
+        &self,
+        executor: E,
+        gid: i64,
+    ) -> Result<ScoringRule, NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    {
+        let rows = sqlx::query_as::<
+            _,
+            (String, i64, i64, i64, i64, Option<String>, Option<i64>, Option<i64>),
+        >(
+            "SELECT s.rank_points, s.anniversary_bonus, s.freeze_price, s.freeze_every, s.max_freezes,
+                b.kind, b.combo, b.bonus
+                FROM ScoringRule s LEFT JOIN ComboBonus b ON s.guild_id=b.guild_id
+                WHERE s.guild_id=$1 ORDER BY b.combo",
+        )
+        .bind(gid)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let (rank_points, anniversary_bonus, price, every, max, ..) = match rows.first() {
+            Some(record) => record,
+            None => return Ok(ScoringRule::default()),
+        };
+        let mut rule = ScoringRule {
+            rank_points: rank_points
+                .parse()
+                .map_err(|_| nalgang_error!(NalgangErrorInner::InvalidScoringRule))?,
+            combo_bonuses: Vec::new(),
+            anniversary_bonus: *anniversary_bonus,
+            freeze: FreezeRule {
+                price: *price,
+                every: *every,
+                max: *max,
+            },
+        };
+        for (.., kind, combo, bonus) in rows.iter() {
+            if let (Some(kind), Some(combo), Some(bonus)) = (kind, combo, bonus) {
+                let milestone = ComboMilestone::from_kind(kind, *combo)
+                    .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidScoringRule))?;
+                rule.set_combo_bonus(milestone, *bonus);
+            }
+        }
+        Ok(rule)
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn register_guild(&self, gid: i64) -> Result<(), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // The key on Guild decides concurrent registrations, so only one of them inserts.
+        let r = sqlx::query("INSERT INTO Guild (guild_id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::DuplicateGuildRegister));
+        }
+
+        sqlx::query("INSERT INTO AttendanceTimeCount (guild_id) VALUES ($1)")
+            .bind(gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn guilds(&self) -> Result<Vec<i64>, NalgangError> {
+        sqlx::query_scalar("SELECT guild_id FROM Guild")
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn register_member(&self, gid: i64, uid: i64) -> Result<(), NalgangError> {
+        match sqlx::query(
+            "INSERT INTO Member (guild_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(gid)
+        .bind(uid)
+        .execute(&self.database)
+        .await
+        {
+            Ok(r) if r.rows_affected() == 0 => {
+                Err(nalgang_error!(NalgangErrorInner::DuplicateMemberRegister))
+            }
+            Ok(_) => Ok(()),
+            // foreign_key_violation, the guild is not registered
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23503") => {
+                Err(nalgang_error!(NalgangErrorInner::GuildNotExist))
+            }
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn member_info(&self, member: &mut NalgangMember) -> Result<bool, NalgangError> {
+        self.get_member_info(&self.database, member).await
+    }
+
+    async fn members(&self, gid: i64) -> Result<Vec<NalgangMember>, NalgangError> {
+        let rows = sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(
+            "SELECT user_id, score, combo, hit_time, freezes FROM Member WHERE guild_id=$1",
+        )
+        .bind(gid)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(user_id, score, combo, hit_time, freezes)| {
+                let mut member = NalgangMember::new(user_id, gid);
+                member.update_data(score, combo, hit_time);
+                member.freezes = Some(freezes);
+                member
+            })
+            .collect())
+    }
+
+    async fn archive_members(
+        &self,
+        gid: i64,
+    ) -> Result<(Vec<ArchivedMember>, Vec<ArchivedAttendance>), NalgangError> {
+        // Both reads see the same snapshot of the guild.
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let members = sqlx::query_as::<_, ArchivedMember>(
+            "SELECT user_id, score, combo, hit_time, freezes, display_name, departed
+                FROM Member WHERE guild_id=$1 ORDER BY user_id",
+        )
+        .bind(gid)
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let history = sqlx::query_as::<_, ArchivedAttendance>(
+            "SELECT user_id, hit_time, hit_rank, hit_point, hit_score, hit_combo, hit_message
+                FROM AttendanceHistory WHERE guild_id=$1 ORDER BY hit_time, user_id",
+        )
+        .bind(gid)
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok((members, history))
+    }
+
+    async fn import_members(
+        &self,
+        gid: i64,
+        archive: &GuildArchive,
+        policy: ConflictPolicy,
+        current_time: i64,
+    ) -> Result<ImportReport, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let mut report = ImportReport::default();
+
+        let r = sqlx::query("INSERT INTO Guild (guild_id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() > 0 {
+            sqlx::query("INSERT INTO AttendanceTimeCount (guild_id) VALUES ($1)")
+                .bind(gid)
+                .execute(&mut transaction)
+                .await
+                .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            report.guild_created = true;
+        }
+
+        let history = archive.history_by_member();
+        let reason = format!("{} 서버에서 가져오기", archive.guild_id);
+        for member in archive.members.iter() {
+            let uid = member.user_id;
+            let existing = sqlx::query_as::<_, (i64, i64, i64, i64)>(
+                "SELECT score, combo, hit_time, freezes FROM Member
+                    WHERE guild_id=$1 AND user_id=$2 FOR UPDATE",
+            )
+            .bind(gid)
+            .bind(uid)
+            .fetch_optional(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+            let (before, after) = match (existing, policy) {
+                (None, _) => {
+                    sqlx::query(
+                        "INSERT INTO Member (guild_id, user_id, score, combo, hit_time, freezes, display_name, departed)
+                            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    )
+                    .bind(gid)
+                    .bind(uid)
+                    .bind(member.score)
+                    .bind(member.combo)
+                    .bind(member.hit_time)
+                    .bind(member.freezes)
+                    .bind(&member.display_name)
+                    .bind(member.departed)
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    report.added += 1;
+                    (0, member.score)
+                }
+                (Some(_), ConflictPolicy::Skip) => {
+                    report.skipped += 1;
+                    continue;
+                }
+                (Some((score, _, _, _)), ConflictPolicy::Overwrite) => {
+                    sqlx::query(
+                        "UPDATE Member SET score=$1, combo=$2, hit_time=$3, freezes=$4, display_name=$5,
+                            departed=$6 WHERE guild_id=$7 AND user_id=$8",
+                    )
+                    .bind(member.score)
+                    .bind(member.combo)
+                    .bind(member.hit_time)
+                    .bind(member.freezes)
+                    .bind(&member.display_name)
+                    .bind(member.departed)
+                    .bind(gid)
+                    .bind(uid)
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    sqlx::query("DELETE FROM AttendanceHistory WHERE guild_id=$1 AND user_id=$2")
+                        .bind(gid)
+                        .bind(uid)
+                        .execute(&mut transaction)
+                        .await
+                        .map_err(|e| {
+                            nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))
+                        })?;
+                    report.overwritten += 1;
+                    (score, member.score)
+                }
+                (Some((score, combo, hit_time, freezes)), ConflictPolicy::MergeScores) => {
+                    let (combo, hit_time, freezes) = match member.hit_time > hit_time {
+                        true => (member.combo, member.hit_time, member.freezes),
+                        false => (combo, hit_time, freezes),
+                    };
+                    sqlx::query(
+                        "UPDATE Member SET score=$1, combo=$2, hit_time=$3, freezes=$4,
+                            display_name=COALESCE(display_name, $5) WHERE guild_id=$6 AND user_id=$7",
+                    )
+                    .bind(score + member.score)
+                    .bind(combo)
+                    .bind(hit_time)
+                    .bind(freezes)
+                    .bind(&member.display_name)
+                    .bind(gid)
+                    .bind(uid)
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    report.merged += 1;
+                    (score, score + member.score)
+                }
+            };
+            sqlx::query(
+                "INSERT INTO AuditLog (guild_id, actor_id, target_id, field, before, after, reason, event_time)
+                    VALUES ($1, $2, $3, 'score', $4, $5, $6, $7)",
+            )
+            .bind(gid)
+            .bind(IMPORT_ACTOR)
+            .bind(uid)
+            .bind(before)
+            .bind(after)
+            .bind(&reason)
+            .bind(current_time)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+            // Attendances the member already has are kept.
+            for entry in history.get(&uid).into_iter().flatten() {
+                let r = sqlx::query(
+                    "INSERT INTO AttendanceHistory (guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank, hit_point)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING",
+                )
+                .bind(gid)
+                .bind(uid)
+                .bind(&entry.hit_message)
+                .bind(entry.hit_time)
+                .bind(entry.hit_score)
+                .bind(entry.hit_combo)
+                .bind(entry.hit_rank)
+                .bind(entry.hit_point)
+                .execute(&mut transaction)
+                .await
+                .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                report.history_added += r.rows_affected() as i64;
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(report)
+    }
+
+    async fn restore_guild_attendance(
+        &self,
+        gid: i64,
+        attendance: &GuildAttendance,
+    ) -> Result<(), NalgangError> {
+        sqlx::query(
+            "UPDATE AttendanceTimeCount SET hit_count=$1, hit_time=$2 WHERE guild_id=$3 AND hit_time < $2",
+        )
+        .bind(attendance.hit_count)
+        .bind(attendance.hit_time)
+        .bind(gid)
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(())
+    }
+
+    async fn recorded_combos(&self, gid: i64) -> Result<Vec<(i64, i64, i64)>, NalgangError> {
+        sqlx::query_as::<_, (i64, i64, i64)>(RECORDED_COMBOS)
+            .bind(gid)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn update_members(&self, members: &[NalgangMember]) -> Result<(), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        for member in members {
+            self.update_member_info(&mut transaction, member).await?;
+        }
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attend(
+        &self,
+        member: &mut NalgangMember,
+        message: &str,
+        current_time: i64,
+    ) -> Result<Attendance, NalgangError> {
+        let (gid, uid) = (member.gid, member.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Locking the guild row serializes rank assignment of the guild.
+        let guild_entry = sqlx::query_as::<_, GuildAttendance>(
+            "SELECT hit_count, hit_time FROM AttendanceTimeCount WHERE guild_id=$1 FOR UPDATE",
+        )
+        .bind(gid)
+        .fetch_optional(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?
+        .ok_or_else(|| nalgang_error!(NalgangErrorInner::GuildNotExist))?;
+
+        if !self.lock_member_info(&mut transaction, member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+
+        let boundary = self.get_day_boundary(&mut transaction, gid).await?;
+        let scoring_rule = self.get_scoring_rule(&mut transaction, gid).await?;
+        let mut attendance =
+            resolve_attendance(member, &guild_entry, &boundary, &scoring_rule, current_time)?;
+
+        sqlx::query("UPDATE AttendanceTimeCount SET hit_count=$1, hit_time=$2 WHERE guild_id=$3")
+            .bind(attendance.rank)
+            .bind(current_time)
+            .bind(gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let new_score = member.score.unwrap() + attendance.earned_point;
+        member.update_data(new_score, attendance.combo, current_time);
+        member.freezes = Some(
+            member.freezes.unwrap() - attendance.frozen_days.len() as i64
+                + attendance.freeze_earned as i64,
+        );
+        self.update_member_info(&mut transaction, member).await?;
+
+        for frozen_day in attendance.frozen_days.iter() {
+            sqlx::query(
+                "INSERT INTO FreezeHistory (guild_id, user_id, kind, count, score, frozen_day, event_time)
+                    VALUES ($1, $2, 'use', 1, $3, $4, $5)",
+            )
+            .bind(gid)
+            .bind(uid)
+            .bind(new_score)
+            .bind(boundary.day_start(*frozen_day))
+            .bind(current_time)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+        if attendance.freeze_earned {
+            sqlx::query(
+                "INSERT INTO FreezeHistory (guild_id, user_id, kind, count, score, event_time)
+                    VALUES ($1, $2, 'earn', 1, $3, $4)",
+            )
+            .bind(gid)
+            .bind(uid)
+            .bind(new_score)
+            .bind(current_time)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+
+        sqlx::query(
+            "INSERT INTO DailyAttendance (guild_id, user_id, hit_message, hit_time)
+                VALUES ($1, $2, $3, $4)",
+        )
+        .bind(gid)
+        .bind(uid)
+        .bind(message)
+        .bind(current_time)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        sqlx::query(
+            "INSERT INTO AttendanceHistory (guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank, hit_point)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(gid)
+        .bind(uid)
+        .bind(message)
+        .bind(current_time)
+        .bind(new_score)
+        .bind(attendance.combo)
+        .bind(attendance.rank)
+        .bind(attendance.earned_point)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM AttendanceHistory WHERE guild_id=$1 AND user_id=$2",
+        )
+        .bind(gid)
+        .bind(uid)
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let record = AttendanceRecord {
+            rank: attendance.rank,
+            combo: attendance.combo,
+            count,
+            day_time: current_time - boundary.day_start(boundary.attendance_date(current_time)),
+        };
+        let achieved: Vec<(Achievement, i64)> = Achievement::evaluate(&record)
+            .into_iter()
+            .map(|achievement| (achievement, current_time))
+            .collect();
+        attendance.achievements = self
+            .insert_achievements(&mut transaction, member, &achieved)
+            .await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(attendance)
+    }
+
+    async fn grant_achievements(
+        &self,
+        member: &NalgangMember,
+        achievements: &[(Achievement, i64)],
+    ) -> Result<Vec<Achievement>, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let granted = self
+            .insert_achievements(&mut transaction, member, achievements)
+            .await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(granted)
+    }
+
+    async fn achievements(
+        &self,
+        member: &NalgangMember,
+    ) -> Result<Vec<(Achievement, i64)>, NalgangError> {
+        let rows = sqlx::query_as::<_, (String, i64)>(
+            "SELECT achievement, achieved_time FROM MemberAchievement WHERE guild_id=$1 AND user_id=$2
+                ORDER BY achieved_time, achievement",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Achievements removed from the catalog are skipped.
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, achieved_time)| {
+                Achievement::from_id(&id).map(|achievement| (achievement, achieved_time))
+            })
+            .collect())
+    }
+
+    async fn buy_freezes(
+        &self,
+        member: &mut NalgangMember,
+        count: i64,
+        rule: &FreezeRule,
+        current_time: i64,
+    ) -> Result<(), NalgangError> {
+        let (gid, uid, cost) = (member.gid, member.uid, rule.price * count);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let r = sqlx::query(
+            "UPDATE Member SET score=score-$1, freezes=freezes+$2
+                WHERE guild_id=$3 AND user_id=$4 AND score>=$1 AND freezes+$2<=$5",
+        )
+        .bind(cost)
+        .bind(count)
+        .bind(gid)
+        .bind(uid)
+        .bind(rule.max)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let exists = self.get_member_info(&mut transaction, member).await?;
+        if r.rows_affected() == 0 {
+            return Err(match exists {
+                false => nalgang_error!(NalgangErrorInner::MemberNotExist),
+                true if member.freezes.unwrap() + count > rule.max => {
+                    nalgang_error!(NalgangErrorInner::FreezeLimitExceeded)
+                }
+                true => nalgang_error!(NalgangErrorInner::InsufficientScore),
+            });
+        }
+
+        sqlx::query(
+            "INSERT INTO FreezeHistory (guild_id, user_id, kind, count, score, event_time)
+                VALUES ($1, $2, 'buy', $3, $4, $5)",
+        )
+        .bind(gid)
+        .bind(uid)
+        .bind(count)
+        .bind(member.score.unwrap())
+        .bind(current_time)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn set_shop_item(&self, gid: i64, item: &ShopItem) -> Result<(), NalgangError> {
+        // A redefined item gets a new item_id, which no earlier purchase refers to.
+        sqlx::query(
+            "INSERT INTO ShopItem (guild_id, name, price, stock, role_id, duration)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (guild_id, name) DO UPDATE SET price=excluded.price, stock=excluded.stock,
+                role_id=excluded.role_id, duration=excluded.duration, item_id=DEFAULT",
+        )
+        .bind(gid)
+        .bind(&item.name)
+        .bind(item.price)
+        .bind(item.stock)
+        .bind(item.role_id)
+        .bind(item.duration)
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn delete_shop_item(&self, gid: i64, name: &str) -> Result<bool, NalgangError> {
+        match sqlx::query("DELETE FROM ShopItem WHERE guild_id=$1 AND name=$2")
+            .bind(gid)
+            .bind(name)
+            .execute(&self.database)
+            .await
+        {
+            Ok(r) => Ok(r.rows_affected() > 0),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn shop_items(&self, gid: i64) -> Result<Vec<ShopItem>, NalgangError> {
+        // Names are compared by bytes as SQLite does, whatever the locale of the database is.
+        sqlx::query_as::<_, ShopItem>(
+            r#"SELECT name, price, stock, role_id, duration FROM ShopItem WHERE guild_id=$1
+                ORDER BY price, name COLLATE "C""#,
+        )
+        .bind(gid)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn buy_item(
+        &self,
+        member: &mut NalgangMember,
+        name: &str,
+        current_time: i64,
+    ) -> Result<InventoryItem, NalgangError> {
+        let (gid, uid) = (member.gid, member.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Unlimited stock stays NULL.
+        let r = sqlx::query(
+            "UPDATE ShopItem SET stock=stock-1
+                WHERE guild_id=$1 AND name=$2 AND (stock IS NULL OR stock>0)",
+        )
+        .bind(gid)
+        .bind(name)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let item = sqlx::query_as::<_, ShopItem>(
+            "SELECT name, price, stock, role_id, duration FROM ShopItem WHERE guild_id=$1 AND name=$2",
+        )
+        .bind(gid)
+        .bind(name)
+        .fetch_optional(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?
+        .ok_or_else(|| nalgang_error!(NalgangErrorInner::ShopItemNotExist))?;
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::OutOfStock));
+        }
+
+        let r = sqlx::query(
+            "UPDATE Member SET score=score-$1 WHERE guild_id=$2 AND user_id=$3 AND score>=$1",
+        )
+        .bind(item.price)
+        .bind(gid)
+        .bind(uid)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let exists = self.get_member_info(&mut transaction, member).await?;
+        if r.rows_affected() == 0 {
+            return Err(match exists {
+                false => nalgang_error!(NalgangErrorInner::MemberNotExist),
+                true => nalgang_error!(NalgangErrorInner::InsufficientScore),
+            });
+        }
+
+        let expire_time = item
+            .duration
+            .map(|duration| current_time + duration * 86400);
+        let purchase_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO Inventory (guild_id, user_id, item_id, name, price, role_id, score, purchase_time,
+                expire_time)
+                VALUES ($1, $2, (SELECT item_id FROM ShopItem WHERE guild_id=$1 AND name=$3), $3, $4, $5,
+                $6, $7, $8)
+                RETURNING purchase_id",
+        )
+        .bind(gid)
+        .bind(uid)
+        .bind(&item.name)
+        .bind(item.price)
+        .bind(item.role_id)
+        .bind(member.score.unwrap())
+        .bind(current_time)
+        .bind(expire_time)
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(InventoryItem {
+            purchase_id,
+            name: item.name,
+            price: item.price,
+            role_id: item.role_id,
+            purchase_time: current_time,
+            expire_time,
+        })
+    }
+
+    async fn cancel_purchase(
+        &self,
+        member: &mut NalgangMember,
+        purchase_id: i64,
+    ) -> Result<bool, NalgangError> {
+        let (gid, uid) = (member.gid, member.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Locks in the order of `buy_item`, the item before the member.
+        let purchase = sqlx::query_as::<_, (Option<i64>, i64)>(
+            "SELECT item_id, price FROM Inventory WHERE purchase_id=$1 AND guild_id=$2 AND user_id=$3
+                FOR UPDATE",
+        )
+        .bind(purchase_id)
+        .bind(gid)
+        .bind(uid)
+        .fetch_optional(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let (item_id, price) = match purchase {
+            Some(purchase) => purchase,
+            None => return Ok(false),
+        };
+
+        sqlx::query("UPDATE ShopItem SET stock=stock+1 WHERE item_id=$1")
+            .bind(item_id)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        sqlx::query("UPDATE Member SET score=score+$1 WHERE guild_id=$2 AND user_id=$3")
+            .bind(price)
+            .bind(gid)
+            .bind(uid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        sqlx::query("DELETE FROM Inventory WHERE purchase_id=$1")
+            .bind(purchase_id)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        self.get_member_info(&mut transaction, member).await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(true)
+    }
+
+    async fn inventory(
+        &self,
+        member: &NalgangMember,
+        current_time: i64,
+    ) -> Result<Vec<InventoryItem>, NalgangError> {
+        sqlx::query_as::<_, InventoryItem>(
+            "SELECT purchase_id, name, price, role_id, purchase_time, expire_time
+                FROM Inventory WHERE guild_id=$1 AND user_id=$2 AND NOT expired
+                AND (expire_time IS NULL OR expire_time > $3)
+                ORDER BY purchase_id DESC",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .bind(current_time)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn expire_items(
+        &self,
+        gid: i64,
+        current_time: i64,
+    ) -> Result<Vec<ExpiredRole>, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Marking and reading at once, so that only one instance takes back each role.
+        let expired = sqlx::query_as::<_, (i64, Option<i64>)>(
+            "UPDATE Inventory SET expired=TRUE
+                WHERE guild_id=$1 AND NOT expired AND expire_time <= $2
+                RETURNING user_id, role_id",
+        )
+        .bind(gid)
+        .bind(current_time)
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let mut roles: Vec<ExpiredRole> = Vec::new();
+        for (user_id, role_id) in expired {
+            let role_id = match role_id {
+                Some(role_id) => role_id,
+                None => continue,
+            };
+            if roles
+                .iter()
+                .any(|role| role.user_id == user_id && role.role_id == role_id)
+            {
+                continue;
+            }
+            let still_granted = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS (SELECT (1) FROM Inventory WHERE guild_id=$1 AND user_id=$2 AND role_id=$3
+                    AND NOT expired AND (expire_time IS NULL OR expire_time > $4))",
+            )
+            .bind(gid)
+            .bind(user_id)
+            .bind(role_id)
+            .bind(current_time)
+            .fetch_one(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            if !still_granted {
+                roles.push(ExpiredRole { user_id, role_id });
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(roles)
+    }
+
+    async fn transfer(
+        &self,
+        sender: &mut NalgangMember,
+        receiver: &mut NalgangMember,
+        amount: i64,
+        current_time: i64,
+    ) -> Result<(), NalgangError> {
+        let gid = sender.gid;
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Both members are locked in the order of user id, so that opposite transfers can not
+        // deadlock.
+        sqlx::query(
+            "SELECT (1) FROM Member WHERE guild_id=$1 AND user_id IN ($2, $3)
+                ORDER BY user_id FOR UPDATE",
+        )
+        .bind(gid)
+        .bind(sender.uid)
+        .bind(receiver.uid)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let r = sqlx::query(
+            "UPDATE Member SET score=score-$1 WHERE guild_id=$2 AND user_id=$3 AND score>=$1",
+        )
+        .bind(amount)
+        .bind(gid)
+        .bind(sender.uid)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        if r.rows_affected() == 0 {
+            return match self.get_member_info(&mut transaction, sender).await? {
+                false => Err(nalgang_error!(NalgangErrorInner::MemberNotExist)),
+                true => Err(nalgang_error!(NalgangErrorInner::InsufficientScore)),
+            };
+        }
+
+        let r = sqlx::query("UPDATE Member SET score=score+$1 WHERE guild_id=$2 AND user_id=$3")
+            .bind(amount)
+            .bind(gid)
+            .bind(receiver.uid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::ReceiverNotExist));
+        }
+
+        self.get_member_info(&mut transaction, sender).await?;
+        self.get_member_info(&mut transaction, receiver).await?;
+
+        sqlx::query(
+            "INSERT INTO TransferHistory (guild_id, sender_id, receiver_id, amount, sender_score, receiver_score, transfer_time)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(gid)
+        .bind(sender.uid)
+        .bind(receiver.uid)
+        .bind(amount)
+        .bind(sender.score.unwrap())
+        .bind(receiver.score.unwrap())
+        .bind(current_time)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn adjust_member(
+        &self,
+        actor_id: i64,
+        target: &mut NalgangMember,
+        field: AdjustField,
+        adjustment: Adjustment,
+        reason: &str,
+        current_time: i64,
+    ) -> Result<AuditEntry, NalgangError> {
+        let (gid, uid) = (target.gid, target.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        if !self.lock_member_info(&mut transaction, target).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+
+        let before = match field {
+            AdjustField::Score => target.score.unwrap(),
+            AdjustField::Combo => target.combo.unwrap(),
+        };
+        let after = adjustment
+            .apply(before)
+            .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidAdjustment))?;
+        match field {
+            AdjustField::Score => target.score = Some(after),
+            AdjustField::Combo if after < 0 => {
+                return Err(nalgang_error!(NalgangErrorInner::InvalidAdjustment))
+            }
+            AdjustField::Combo => target.combo = Some(after),
+        }
+        self.update_member_info(&mut transaction, target).await?;
+
+        let audit_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO AuditLog (guild_id, actor_id, target_id, field, before, after, reason, event_time)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING audit_id",
+        )
+        .bind(gid)
+        .bind(actor_id)
+        .bind(uid)
+        .bind(field.kind())
+        .bind(before)
+        .bind(after)
+        .bind(reason)
+        .bind(current_time)
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(AuditEntry {
+            audit_id,
+            actor_id,
+            target_id: uid,
+            field,
+            before,
+            after,
+            reason: reason.to_string(),
+            event_time: current_time,
+        })
+    }
+
+    async fn audit_log(&self, gid: i64, limit: i64) -> Result<Vec<AuditEntry>, NalgangError> {
+        let rows = sqlx::query_as::<_, (i64, i64, i64, String, i64, i64, String, i64)>(
+            "SELECT audit_id, actor_id, target_id, field, before, after, reason, event_time
+                FROM AuditLog WHERE guild_id=$1 ORDER BY audit_id DESC LIMIT $2",
+        )
+        .bind(gid)
+        .bind(limit)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        rows.into_iter()
+            .map(
+                |(audit_id, actor_id, target_id, field, before, after, reason, event_time)| {
+                    Ok(AuditEntry {
+                        audit_id,
+                        actor_id,
+                        target_id,
+                        field: AdjustField::from_kind(&field)
+                            .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidAdjustment))?,
+                        before,
+                        after,
+                        reason,
+                        event_time,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    async fn insert_token(
+        &self,
+        member: &NalgangMember,
+        token: &str,
+    ) -> Result<bool, NalgangError> {
+        let r = sqlx::query(
+            "INSERT INTO Token (guild_id, user_id, token) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .bind(token)
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(r.rows_affected() > 0)
+    }
+
+    async fn delete_token(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        let r = sqlx::query("DELETE FROM Token WHERE guild_id=$1 AND user_id=$2")
+            .bind(member.gid)
+            .bind(member.uid)
+            .execute(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(r.rows_affected() > 0)
+    }
+
+    async fn token_owner(&self, token: &str) -> Result<Option<NalgangMember>, NalgangError> {
+        let row = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT guild_id, user_id FROM Token WHERE token=$1 LIMIT 1",
+        )
+        .bind(token)
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        Ok(row.map(|(guild_id, user_id)| NalgangMember::new(user_id, guild_id)))
+    }
+
+    async fn update_profile(
+        &self,
+        member: &NalgangMember,
+        display_name: &str,
+        departed: bool,
+    ) -> Result<(), NalgangError> {
+        sqlx::query(
+            "UPDATE Member SET display_name=$1, departed=$2 WHERE guild_id=$3 AND user_id=$4",
+        )
+        .bind(display_name)
+        .bind(departed)
+        .bind(member.gid)
+        .bind(member.uid)
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn ranking(&self, gid: i64) -> Result<Vec<RankingEntry>, NalgangError> {
+        sqlx::query_as::<_, RankingEntry>(
+            "SELECT user_id, score, display_name, departed FROM Member
+                WHERE guild_id=$1 ORDER BY score DESC, user_id",
+        )
+        .bind(gid)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn ranking_page(
+        &self,
+        filter: &RankingFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<RankingEntry>, NalgangError> {
+        let sql = format!(
+            "{} SELECT s.user_id, s.score, m.display_name, m.departed FROM Scores s
+                JOIN Member m ON m.guild_id=$1 AND m.user_id=s.user_id
+                ORDER BY s.sort_key DESC, s.user_id LIMIT $8 OFFSET $9",
+            ranking_scores(filter.kind, filter.window.is_some())
+        );
+        bind_filter!(sqlx::query_as::<_, RankingEntry>(&sql), filter)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn ranked_count(&self, filter: &RankingFilter) -> Result<i64, NalgangError> {
+        let sql = format!(
+            "{} SELECT COUNT(*) FROM Scores",
+            ranking_scores(filter.kind, filter.window.is_some())
+        );
+        bind_filter!(sqlx::query_scalar::<_, i64>(&sql), filter)
+            .fetch_one(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn member_rank(
+        &self,
+        filter: &RankingFilter,
+        uid: i64,
+    ) -> Result<Option<i64>, NalgangError> {
+        let sql = format!(
+            "{} SELECT (SELECT COUNT(*) FROM Scores o WHERE o.sort_key > s.sort_key
+                OR (o.sort_key = s.sort_key AND o.user_id < s.user_id))
+                FROM Scores s WHERE s.user_id=$8",
+            ranking_scores(filter.kind, filter.window.is_some())
+        );
+        bind_filter!(sqlx::query_scalar::<_, i64>(&sql), filter)
+            .bind(uid)
+            .fetch_optional(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attendance_history(
+        &self,
+        member: &NalgangMember,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError> {
+        sqlx::query_as::<_, HistoryEntry>(
+            "SELECT hit_time, hit_rank, hit_point, hit_score, hit_combo, hit_message FROM AttendanceHistory
+                WHERE guild_id=$1 AND user_id=$2 ORDER BY hit_time DESC LIMIT $3 OFFSET $4",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attendance_count(&self, member: &NalgangMember) -> Result<i64, NalgangError> {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM AttendanceHistory WHERE guild_id=$1 AND user_id=$2",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .fetch_one(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attendance_history_since(
+        &self,
+        member: &NalgangMember,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError> {
+        sqlx::query_as::<_, HistoryEntry>(
+            "SELECT hit_time, hit_rank, hit_point, hit_score, hit_combo, hit_message FROM AttendanceHistory
+                WHERE guild_id=$1 AND user_id=$2 AND hit_time >= $3 ORDER BY hit_time",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .bind(since)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn daily_attendance(
+        &self,
+        gid: i64,
+        since: i64,
+    ) -> Result<Vec<AttendanceEntry>, NalgangError> {
+        sqlx::query_as::<_, AttendanceEntry>(
+            "SELECT d.user_id, d.hit_message, d.hit_time, m.display_name, m.departed
+                FROM DailyAttendance d
+                JOIN Member m ON d.guild_id=m.guild_id AND d.user_id=m.user_id
+                WHERE d.guild_id=$1 AND d.hit_time >= $2",
+        )
+        .bind(gid)
+        .bind(since)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn reset_day(
+        &self,
+        gid: i64,
+        day_start: i64,
+        since: i64,
+    ) -> Result<Option<Vec<AttendanceEntry>>, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Marking the day first locks the guild, so attendances and other resets wait.
+        let r = sqlx::query(
+            "UPDATE AttendanceTimeCount SET reset_time=$1 WHERE guild_id=$2 AND reset_time < $1",
+        )
+        .bind(day_start)
+        .bind(gid)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let entries = sqlx::query_as::<_, AttendanceEntry>(
+            "SELECT d.user_id, d.hit_message, d.hit_time, m.display_name, m.departed
+                FROM DailyAttendance d
+                JOIN Member m ON d.guild_id=m.guild_id AND d.user_id=m.user_id
+                WHERE d.guild_id=$1 AND d.hit_time >= $2 AND d.hit_time < $3 ORDER BY d.hit_time",
+        )
+        .bind(gid)
+        .bind(since)
+        .bind(day_start)
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Attendances of the new day may already be in when the reset runs late.
+        sqlx::query("DELETE FROM DailyAttendance WHERE guild_id=$1 AND hit_time < $2")
+            .bind(gid)
+            .bind(day_start)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Same state as a newly registered guild, so the next attendance takes the first rank.
+        sqlx::query(
+            "UPDATE AttendanceTimeCount SET hit_count=0, hit_time=0 WHERE guild_id=$1 AND hit_time < $2",
+        )
+        .bind(gid)
+        .bind(day_start)
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(Some(entries))
+    }
+
+    async fn attendance_combos(
+        &self,
+        gid: i64,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError> {
+        sqlx::query_as::<_, (i64, i64)>(
+            "SELECT user_id, hit_combo FROM AttendanceHistory
+                WHERE guild_id=$1 AND hit_time >= $2 AND hit_time < $3 ORDER BY hit_time",
+        )
+        .bind(gid)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn scores_at(
+        &self,
+        gid: i64,
+        include_departed: bool,
+        time: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError> {
+        sqlx::query_as::<_, (i64, i64)>(SCORES_AT)
+            .bind(gid)
+            .bind(include_departed)
+            .bind(time)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn day_boundary(&self, gid: i64) -> Result<DayBoundary, NalgangError> {
+        self.get_day_boundary(&self.database, gid).await
+    }
+
+    async fn set_day_boundary(&self, gid: i64, boundary: &DayBoundary) -> Result<(), NalgangError> {
+        sqlx::query(
+            "INSERT INTO GuildSetting (guild_id, timezone, day_start_hour) VALUES ($1, $2, $3)
+                ON CONFLICT (guild_id) DO UPDATE SET timezone=excluded.timezone, day_start_hour=excluded.day_start_hour",
+        )
+        .bind(gid)
+        .bind(boundary.timezone.to_string())
+        .bind(i64::from(boundary.day_start_hour))
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn hide_departed(&self, gid: i64) -> Result<bool, NalgangError> {
+        let row = sqlx::query_scalar::<_, bool>(
+            "SELECT hide_departed FROM GuildSetting WHERE guild_id=$1",
+        )
+        .bind(gid)
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(row.unwrap_or(false))
+    }
+
+    async fn set_hide_departed(&self, gid: i64, hide_departed: bool) -> Result<(), NalgangError> {
+        sqlx::query(
+            "INSERT INTO GuildSetting (guild_id, hide_departed) VALUES ($1, $2)
+                ON CONFLICT (guild_id) DO UPDATE SET hide_departed=excluded.hide_departed",
+        )
+        .bind(gid)
+        .bind(hide_departed)
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn summary_channel(&self, gid: i64) -> Result<Option<i64>, NalgangError> {
+        let row = sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT summary_channel FROM GuildSetting WHERE guild_id=$1",
+        )
+        .bind(gid)
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(row.flatten())
+    }
+
+    async fn set_summary_channel(
+        &self,
+        gid: i64,
+        channel_id: Option<i64>,
+    ) -> Result<(), NalgangError> {
+        sqlx::query(
+            "INSERT INTO GuildSetting (guild_id, summary_channel) VALUES ($1, $2)
+                ON CONFLICT (guild_id) DO UPDATE SET summary_channel=excluded.summary_channel",
+        )
+        .bind(gid)
+        .bind(channel_id)
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn set_reminder(
+        &self,
+        member: &NalgangMember,
+        remind_time: RemindTime,
+        delivery: ReminderDelivery,
+    ) -> Result<(), NalgangError> {
+        sqlx::query(
+            "INSERT INTO Reminder (guild_id, user_id, remind_time, delivery) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (guild_id, user_id) DO UPDATE SET remind_time=excluded.remind_time, delivery=excluded.delivery",
+        )
+        .bind(member.gid)
+        .bind(member.uid)
+        .bind(remind_time.minutes())
+        .bind(delivery.kind())
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn delete_reminder(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        match sqlx::query("DELETE FROM Reminder WHERE guild_id=$1 AND user_id=$2")
+            .bind(member.gid)
+            .bind(member.uid)
+            .execute(&self.database)
+            .await
+        {
+            Ok(r) => Ok(r.rows_affected() > 0),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn reminders(&self, gid: i64) -> Result<Vec<Reminder>, NalgangError> {
+        let rows = sqlx::query_as::<_, (i64, i64, String, i64, i64, i64, i64)>(
+            "SELECT r.user_id, r.remind_time, r.delivery, r.last_reminded, m.combo, m.hit_time,
+                m.freezes
+                FROM Reminder r JOIN Member m ON r.guild_id=m.guild_id AND r.user_id=m.user_id
+                WHERE r.guild_id=$1 AND NOT m.departed",
+        )
+        .bind(gid)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        rows.into_iter()
+            .map(
+                |(user_id, remind_time, delivery, last_reminded, combo, hit_time, freezes)| {
+                    Ok(Reminder {
+                        user_id,
+                        remind_time: RemindTime::from_minutes(remind_time)
+                            .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidRemindTime))?,
+                        delivery: ReminderDelivery::from_kind(&delivery)
+                            .unwrap_or(ReminderDelivery::DirectMessage),
+                        last_reminded,
+                        combo,
+                        hit_time,
+                        freezes,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    async fn mark_reminded(&self, gid: i64, uid: i64, time: i64) -> Result<(), NalgangError> {
+        sqlx::query("UPDATE Reminder SET last_reminded=$1 WHERE guild_id=$2 AND user_id=$3")
+            .bind(time)
+            .bind(gid)
+            .bind(uid)
+            .execute(&self.database)
+            .await
+            .map(|_| ())
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn reminder_setting(&self, gid: i64) -> Result<ReminderSetting, NalgangError> {
+        let row = sqlx::query_as::<_, (bool, Option<i64>, i64)>(
+            "SELECT reminder_enabled, reminder_channel, reminder_min_combo FROM GuildSetting
+                WHERE guild_id=$1",
+        )
+        .bind(gid)
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        Ok(match row {
+            Some((enabled, channel_id, min_combo)) => ReminderSetting {
+                enabled,
+                channel_id,
+                min_combo,
+            },
+            None => ReminderSetting::default(),
+        })
+    }
+
+    async fn set_reminder_setting(
+        &self,
+        gid: i64,
+        setting: &ReminderSetting,
+    ) -> Result<(), NalgangError> {
+        sqlx::query(
+            "INSERT INTO GuildSetting (guild_id, reminder_enabled, reminder_channel, reminder_min_combo)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (guild_id) DO UPDATE SET reminder_enabled=excluded.reminder_enabled,
+                reminder_channel=excluded.reminder_channel, reminder_min_combo=excluded.reminder_min_combo",
+        )
+        .bind(gid)
+        .bind(setting.enabled)
+        .bind(setting.channel_id)
+        .bind(setting.min_combo)
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn role_rewards(&self, gid: i64) -> Result<Vec<RoleReward>, NalgangError> {
+        let rows = sqlx::query_as::<_, (i64, String, i64, bool)>(
+            "SELECT role_id, kind, threshold, revoke FROM RoleReward WHERE guild_id=$1
+                ORDER BY kind DESC, threshold, role_id",
+        )
+        .bind(gid)
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        rows.into_iter()
+            .map(|(role_id, kind, threshold, revoke)| {
+                Ok(RoleReward {
+                    role_id,
+                    kind: RewardKind::from_kind(&kind)
+                        .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidRoleReward))?,
+                    threshold,
+                    revoke,
+                })
+            })
+            .collect()
+    }
+
+    async fn set_role_reward(&self, gid: i64, reward: &RoleReward) -> Result<(), NalgangError> {
+        sqlx::query(
+            "INSERT INTO RoleReward (guild_id, role_id, kind, threshold, revoke) VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (guild_id, role_id) DO UPDATE SET kind=excluded.kind,
+                threshold=excluded.threshold, revoke=excluded.revoke",
+        )
+        .bind(gid)
+        .bind(reward.role_id)
+        .bind(reward.kind.kind())
+        .bind(reward.threshold)
+        .bind(reward.revoke)
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn delete_role_reward(&self, gid: i64, role_id: i64) -> Result<bool, NalgangError> {
+        match sqlx::query("DELETE FROM RoleReward WHERE guild_id=$1 AND role_id=$2")
+            .bind(gid)
+            .bind(role_id)
+            .execute(&self.database)
+            .await
+        {
+            Ok(r) => Ok(r.rows_affected() > 0),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn scoring_rule(&self, gid: i64) -> Result<ScoringRule, NalgangError> {
+        self.get_scoring_rule(&self.database, gid).await
+    }
+
+    async fn set_scoring_rule(&self, gid: i64, rule: &ScoringRule) -> Result<(), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        sqlx::query(
+            "INSERT INTO ScoringRule (guild_id, rank_points, anniversary_bonus, freeze_price, freeze_every, max_freezes)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (guild_id) DO UPDATE SET rank_points=excluded.rank_points, anniversary_bonus=excluded.anniversary_bonus,
+                freeze_price=excluded.freeze_price, freeze_every=excluded.freeze_every, max_freezes=excluded.max_freezes",
+        )
+        .bind(gid)
+        .bind(rule.rank_points.to_string())
+        .bind(rule.anniversary_bonus)
+        .bind(rule.freeze.price)
+        .bind(rule.freeze.every)
+        .bind(rule.freeze.max)
+        .execute(&mut transaction)
+        .await
+        .map_err(guild_write_error)?;
+
+        sqlx::query("DELETE FROM ComboBonus WHERE guild_id=$1")
+            .bind(gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        for combo_bonus in rule.combo_bonuses.iter() {
+            sqlx::query(
+                "INSERT INTO ComboBonus (guild_id, kind, combo, bonus) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(gid)
+            .bind(combo_bonus.milestone.kind())
+            .bind(combo_bonus.milestone.days())
+            .bind(combo_bonus.bonus)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+}
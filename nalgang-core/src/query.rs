@@ -0,0 +1,134 @@
+// Queries built at runtime which every storage shares. They only use SQL understood by both SQLite
+// and PostgreSQL, with $N parameters.
+use crate::RankingKind;
+
+// Binds $1 to $7 of `ranking_scores`.
+macro_rules! bind_filter {
+    ($query: expr, $filter: expr) => {{
+        let window = $filter.window.unwrap_or($crate::period::RankingWindow {
+            since: i64::MIN,
+            until: i64::MAX,
+            days: 0,
+        });
+        $query
+            .bind($filter.gid)
+            .bind($filter.include_departed)
+            .bind(window.since)
+            .bind(window.until)
+            .bind(window.days)
+            .bind($filter.alive_since)
+            .bind($filter.day_end)
+    }};
+}
+pub(crate) use bind_filter;
+
+// Builds the Scores table of a ranking with user_id, score and sort_key columns, ranked by
+// sort_key descending. Parameters are $1 guild_id, $2 include_departed, $3 since, $4 until,
+// $5 days, $6 alive_since and $7 day_end of `RankingFilter`.
+pub fn ranking_scores(kind: RankingKind, windowed: bool) -> String {
+    const MEMBER: &str =
+        "FROM Params p JOIN Member m ON m.guild_id=p.gid AND (NOT m.departed OR p.include_departed)";
+    const HISTORY: &str =
+        "JOIN AttendanceHistory h ON h.guild_id=m.guild_id AND h.user_id=m.user_id
+        AND h.hit_time >= p.since AND h.hit_time < p.until";
+
+    // Sums are cast back, since PostgreSQL sums bigint into numeric.
+    let scores = match (kind, windowed) {
+        (RankingKind::Score, false) => format!("SELECT m.user_id, m.score {}", MEMBER),
+        (RankingKind::Score, true) => format!(
+            "SELECT m.user_id, CAST(SUM(h.hit_point) AS bigint) AS score {} {} GROUP BY m.user_id",
+            MEMBER, HISTORY
+        ),
+        (RankingKind::Combo, _) => format!(
+            // Each freeze keeps the combo alive for one more missed day.
+            "SELECT m.user_id, m.combo AS score {} WHERE m.combo > 0
+                AND m.hit_time >= p.alive_since - m.freezes * 86400",
+            MEMBER
+        ),
+        (RankingKind::MaxCombo, _) => format!(
+            "SELECT m.user_id, MAX(h.hit_combo) AS score {} {} GROUP BY m.user_id",
+            MEMBER, HISTORY
+        ),
+        (RankingKind::FirstPlace, _) => format!(
+            "SELECT m.user_id, COUNT(*) AS score {} {} AND h.hit_rank = 0 GROUP BY m.user_id",
+            MEMBER, HISTORY
+        ),
+        (RankingKind::AverageRank, _) => format!(
+            "SELECT m.user_id, CAST(ROUND(AVG(h.hit_rank + 1) * 100) AS bigint) AS score {} {}
+                GROUP BY m.user_id",
+            MEMBER, HISTORY
+        ),
+        // Without a window, count the days since the first attendance of each member.
+        (RankingKind::AttendanceRate, _) => format!(
+            "SELECT m.user_id, CAST(ROUND(COUNT(*) * 10000.0 / CASE WHEN p.days > 0 THEN p.days
+                ELSE (p.day_end - MIN(h.hit_time) - 1) / 86400 + 1 END) AS bigint) AS score {} {}
+                GROUP BY m.user_id, p.days, p.day_end",
+            MEMBER, HISTORY
+        ),
+    };
+    let sort_key = match kind {
+        RankingKind::AverageRank => "-score",
+        _ => "score",
+    };
+    format!(
+        "WITH Params AS (SELECT $1 AS gid, $2 AS include_departed, $3 AS since, $4 AS until,
+            $5 AS days, $6 AS alive_since, $7 AS day_end),
+        Ranked AS ({}),
+        Scores AS (SELECT user_id, score, {} AS sort_key FROM Ranked)",
+        scores, sort_key
+    )
+}
+
+// (user_id, score) of every member just before a time. Parameters are $1 guild_id,
+// $2 include_departed and $3 time.
+// Every attendance, transfer, purchase and score adjustment records the score after it, so the
+// latest one before the time holds the score at that time. The others can not be made within an
+// attendance, so they come after the attendances of the same second, and adjustments of the same
+// second come in the order they were made.
+pub const SCORES_AT: &str = "WITH Events AS (
+        SELECT user_id, hit_time AS event_time, 0 AS event_order, 0 AS event_id, hit_score AS score
+            FROM AttendanceHistory WHERE guild_id=$1 AND hit_time < $3
+        UNION ALL SELECT sender_id, transfer_time, 1, 0, sender_score FROM TransferHistory
+            WHERE guild_id=$1 AND transfer_time < $3
+        UNION ALL SELECT receiver_id, transfer_time, 1, 0, receiver_score FROM TransferHistory
+            WHERE guild_id=$1 AND transfer_time < $3
+        UNION ALL SELECT user_id, event_time, 1, 0, score FROM FreezeHistory
+            WHERE guild_id=$1 AND kind='buy' AND event_time < $3
+        UNION ALL SELECT user_id, purchase_time, 1, 0, score FROM Inventory
+            WHERE guild_id=$1 AND purchase_time < $3
+        UNION ALL SELECT target_id, event_time, 1, audit_id, after FROM AuditLog
+            WHERE guild_id=$1 AND field='score' AND event_time < $3
+    ), Latest AS (
+        SELECT user_id, score, ROW_NUMBER() OVER (
+            PARTITION BY user_id ORDER BY event_time DESC, event_order DESC, event_id DESC
+        ) AS n
+        FROM Events
+    )
+    SELECT m.user_id, COALESCE(l.score, 0) FROM Member m
+        LEFT JOIN Latest l ON l.user_id=m.user_id AND l.n=1
+        WHERE m.guild_id=$1 AND (NOT m.departed OR $2)
+        ORDER BY 2 DESC, m.user_id";
+
+// (user_id, combo, hit_time) of every member as its last attendance records them. Parameter is $1
+// guild_id. An admin change of the combo after the last attendance takes its place, and a member
+// who never attended gets 0 for both.
+pub const RECORDED_COMBOS: &str = "WITH LastHit AS (
+        SELECT user_id, hit_time, hit_combo, ROW_NUMBER() OVER (
+            PARTITION BY user_id ORDER BY hit_time DESC
+        ) AS n
+        FROM AttendanceHistory WHERE guild_id=$1
+    ), LastChange AS (
+        SELECT target_id AS user_id, event_time, after, ROW_NUMBER() OVER (
+            PARTITION BY target_id ORDER BY audit_id DESC
+        ) AS n
+        FROM AuditLog WHERE guild_id=$1 AND field='combo'
+    )
+    SELECT m.user_id,
+        CASE WHEN c.event_time >= COALESCE(h.hit_time, 0) THEN c.after
+            ELSE COALESCE(h.hit_combo, 0) END,
+        COALESCE(h.hit_time, 0)
+    FROM Member m
+        LEFT JOIN LastHit h ON h.user_id=m.user_id AND h.n=1
+        LEFT JOIN LastChange c ON c.user_id=m.user_id AND c.n=1
+        WHERE m.guild_id=$1
+        ORDER BY m.user_id";
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::period::{Period, RankingWindow};
+
+// What a leaderboard ranks members by
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RankingKind {
+    // Current score, or points earned in the period
+    Score,
+    // Combo that is still alive
+    Combo,
+    MaxCombo,
+    FirstPlace,
+    // Average rank in hundredths, lower is better
+    AverageRank,
+    // Attended days per day in hundredths of percent
+    AttendanceRate,
+}
+
+impl FromStr for RankingKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim() {
+            "점수" => Ok(RankingKind::Score),
+            "연속" => Ok(RankingKind::Combo),
+            "최장연속" => Ok(RankingKind::MaxCombo),
+            "1등" => Ok(RankingKind::FirstPlace),
+            "평균순위" => Ok(RankingKind::AverageRank),
+            "출석률" => Ok(RankingKind::AttendanceRate),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for RankingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            RankingKind::Score => "점수",
+            RankingKind::Combo => "연속",
+            RankingKind::MaxCombo => "최장연속",
+            RankingKind::FirstPlace => "1등",
+            RankingKind::AverageRank => "평균순위",
+            RankingKind::AttendanceRate => "출석률",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl RankingKind {
+    pub const ALL: [RankingKind; 6] = [
+        RankingKind::Score,
+        RankingKind::Combo,
+        RankingKind::MaxCombo,
+        RankingKind::FirstPlace,
+        RankingKind::AverageRank,
+        RankingKind::AttendanceRate,
+    ];
+
+    // Formats the `score` of a `RankingEntry` of this kind.
+    pub fn format(&self, score: i64) -> String {
+        match self {
+            RankingKind::Score => format!("{}점", score),
+            RankingKind::Combo | RankingKind::MaxCombo => format!("{}일 연속", score),
+            RankingKind::FirstPlace => format!("{}회", score),
+            RankingKind::AverageRank => format!("평균 {:.2}위", score as f64 / 100.0),
+            RankingKind::AttendanceRate => format!("{:.2}%", score as f64 / 100.0),
+        }
+    }
+
+    // Whether the combo ranking ignores the period, since it only counts alive combos.
+    pub fn uses_period(&self) -> bool {
+        !matches!(self, RankingKind::Combo)
+    }
+}
+
+// `score` is the value ranked by, see `RankingKind`.
+#[derive(sqlx::FromRow)]
+pub struct RankingEntry {
+    pub user_id: i64,
+    pub score: i64,
+    pub display_name: Option<String>,
+    pub departed: bool,
+}
+
+// Which members a ranking covers and how their score is counted
+pub struct RankingFilter {
+    pub gid: i64,
+    pub kind: RankingKind,
+    // Whole history if not given
+    pub window: Option<RankingWindow>,
+    pub include_departed: bool,
+    // Start of yesterday. Combos of members who did not attend since then are broken.
+    pub alive_since: i64,
+    // Start of tomorrow
+    pub day_end: i64,
+}
+
+// One page of the ranking. `offset` is the rank of the first entry, starting from 0.
+pub struct RankingPage {
+    pub kind: RankingKind,
+    pub period: Period,
+    pub entries: Vec<RankingEntry>,
+    pub page: i64,
+    pub page_count: i64,
+    pub offset: i64,
+}
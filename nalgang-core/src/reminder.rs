@@ -0,0 +1,106 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{NaiveTime, Timelike};
+
+pub const DEFAULT_REMINDER_MIN_COMBO: i64 = 3;
+
+// Where a reminder is sent
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReminderDelivery {
+    DirectMessage,
+    // Mention in the reminder channel of the guild, falling back to a direct message without one
+    Channel,
+}
+
+impl ReminderDelivery {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ReminderDelivery::DirectMessage => "dm",
+            ReminderDelivery::Channel => "channel",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "dm" => Some(ReminderDelivery::DirectMessage),
+            "channel" => Some(ReminderDelivery::Channel),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ReminderDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReminderDelivery::DirectMessage => write!(f, "DM"),
+            ReminderDelivery::Channel => write!(f, "채널 멘션"),
+        }
+    }
+}
+
+// Local time of day of a reminder such as "21:30"
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RemindTime(pub NaiveTime);
+
+impl FromStr for RemindTime {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        NaiveTime::parse_from_str(s.trim(), "%H:%M")
+            .map(RemindTime)
+            .map_err(|_| ())
+    }
+}
+
+impl fmt::Display for RemindTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+impl RemindTime {
+    // Minutes from midnight, as stored
+    pub fn minutes(&self) -> i64 {
+        (self.0.hour() * 60 + self.0.minute()) as i64
+    }
+
+    pub fn from_minutes(minutes: i64) -> Option<Self> {
+        NaiveTime::from_hms_opt((minutes / 60) as u32, (minutes % 60) as u32, 0).map(RemindTime)
+    }
+}
+
+// Reminder of a member with the member's current combo
+pub struct Reminder {
+    pub user_id: i64,
+    pub remind_time: RemindTime,
+    pub delivery: ReminderDelivery,
+    pub last_reminded: i64,
+    pub combo: i64,
+    pub hit_time: i64,
+    pub freezes: i64,
+}
+
+pub struct ReminderSetting {
+    pub enabled: bool,
+    pub channel_id: Option<i64>,
+    // Members with a shorter combo are not reminded.
+    pub min_combo: i64,
+}
+
+impl Default for ReminderSetting {
+    fn default() -> Self {
+        ReminderSetting {
+            enabled: false,
+            channel_id: None,
+            min_combo: DEFAULT_REMINDER_MIN_COMBO,
+        }
+    }
+}
+
+// Reminder to send now
+pub struct DueReminder {
+    pub user_id: i64,
+    pub combo: i64,
+    pub delivery: ReminderDelivery,
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+
+// What a role reward is given for
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RewardKind {
+    Score,
+    // Combo that is still alive
+    Combo,
+}
+
+impl RewardKind {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RewardKind::Score => "score",
+            RewardKind::Combo => "combo",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "score" => Some(RewardKind::Score),
+            "combo" => Some(RewardKind::Combo),
+            _ => None,
+        }
+    }
+}
+
+// Discord role given to the members who reached `threshold`
+pub struct RoleReward {
+    pub role_id: i64,
+    pub kind: RewardKind,
+    pub threshold: i64,
+    // Whether the role is taken back when the condition no longer holds
+    pub revoke: bool,
+}
+
+impl RoleReward {
+    pub fn holds(&self, score: i64, combo: i64) -> bool {
+        match self.kind {
+            RewardKind::Score => score >= self.threshold,
+            RewardKind::Combo => combo >= self.threshold,
+        }
+    }
+}
+
+impl fmt::Display for RoleReward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RewardKind::Score => write!(f, "<@&{}>: {}점 이상", self.role_id, self.threshold)?,
+            RewardKind::Combo => write!(
+                f,
+                "<@&{}>: {}일 이상 연속 출석",
+                self.role_id, self.threshold
+            )?,
+        }
+        if self.revoke {
+            write!(f, ", 조건이 깨지면 회수")?;
+        }
+        Ok(())
+    }
+}
+
+// Roles to give to and take from a member
+#[derive(Default)]
+pub struct RoleChanges {
+    pub grant: Vec<i64>,
+    pub revoke: Vec<i64>,
+}
+
+impl RoleChanges {
+    // `roles` are the roles the member has now.
+    pub fn new(rewards: &[RoleReward], score: i64, combo: i64, roles: &[i64]) -> Self {
+        let mut changes = RoleChanges::default();
+        for reward in rewards {
+            let has_role = roles.contains(&reward.role_id);
+            if reward.holds(score, combo) {
+                if !has_role {
+                    changes.grant.push(reward.role_id);
+                }
+            } else if reward.revoke && has_role {
+                changes.revoke.push(reward.role_id);
+            }
+        }
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grant.is_empty() && self.revoke.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reward(role_id: i64, kind: RewardKind, threshold: i64, revoke: bool) -> RoleReward {
+        RoleReward {
+            role_id,
+            kind,
+            threshold,
+            revoke,
+        }
+    }
+
+    #[test]
+    fn grant_reached_rewards() {
+        let rewards = [
+            reward(1, RewardKind::Score, 100, false),
+            reward(2, RewardKind::Score, 1000, false),
+            reward(3, RewardKind::Combo, 30, false),
+            reward(4, RewardKind::Combo, 7, false),
+        ];
+        let changes = RoleChanges::new(&rewards, 150, 10, &[4]);
+        assert_eq!(changes.grant, vec![1]);
+        assert!(changes.revoke.is_empty());
+    }
+
+    #[test]
+    fn revoke_only_revocable_rewards() {
+        let rewards = [
+            reward(1, RewardKind::Combo, 7, true),
+            reward(2, RewardKind::Combo, 30, false),
+            reward(3, RewardKind::Score, 100, true),
+        ];
+        let changes = RoleChanges::new(&rewards, 100, 1, &[1, 2, 3]);
+        assert!(changes.grant.is_empty());
+        assert_eq!(changes.revoke, vec![1]);
+        assert!(RoleChanges::new(&rewards, 100, 30, &[1, 2, 3]).is_empty());
+    }
+}
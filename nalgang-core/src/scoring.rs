@@ -0,0 +1,316 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Combo condition which awards a bonus
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ComboMilestone {
+    // Every `n` days of combo
+    Every(i64),
+    // Exactly at `n` days of combo
+    Exact(i64),
+}
+
+impl ComboMilestone {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ComboMilestone::Every(_) => "modulo",
+            ComboMilestone::Exact(_) => "exact",
+        }
+    }
+
+    pub fn days(&self) -> i64 {
+        match self {
+            ComboMilestone::Every(n) | ComboMilestone::Exact(n) => *n,
+        }
+    }
+
+    pub fn from_kind(kind: &str, days: i64) -> Option<Self> {
+        if days <= 0 {
+            return None;
+        }
+        match kind {
+            "modulo" => Some(ComboMilestone::Every(days)),
+            "exact" => Some(ComboMilestone::Exact(days)),
+            _ => None,
+        }
+    }
+
+    pub fn reached(&self, combo: i64) -> bool {
+        match self {
+            ComboMilestone::Every(n) => combo % n == 0,
+            ComboMilestone::Exact(n) => combo == *n,
+        }
+    }
+}
+
+impl fmt::Display for ComboMilestone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComboMilestone::Every(n) => write!(f, "{}일마다", n),
+            ComboMilestone::Exact(n) => write!(f, "{}일째", n),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ComboBonus {
+    pub milestone: ComboMilestone,
+    pub bonus: i64,
+}
+
+// Points of each attendance rank. Ranks past the end of the list earn the last entry.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RankPoints(pub Vec<i64>);
+
+impl FromStr for RankPoints {
+    type Err = ();
+
+    // Parses comma separated points such as "10,5,3,1".
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let points = s
+            .split(',')
+            .map(|point| point.trim().parse::<i64>().map_err(|_| ()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if points.iter().any(|point| *point < 0) {
+            return Err(());
+        }
+        Ok(RankPoints(points))
+    }
+}
+
+impl fmt::Display for RankPoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let points: Vec<String> = self.0.iter().map(|point| point.to_string()).collect();
+        write!(f, "{}", points.join(","))
+    }
+}
+
+// How streak freezes are bought and earned
+#[derive(Clone, Copy)]
+pub struct FreezeRule {
+    // Score of a freeze
+    pub price: i64,
+    // A freeze is earned every `every` days of combo, never if 0.
+    pub every: i64,
+    // Freezes a member can hold at most
+    pub max: i64,
+}
+
+impl Default for FreezeRule {
+    fn default() -> Self {
+        FreezeRule {
+            price: 100,
+            every: 30,
+            max: 2,
+        }
+    }
+}
+
+impl FreezeRule {
+    pub fn earned(&self, combo: i64) -> bool {
+        self.every > 0 && combo > 0 && combo % self.every == 0
+    }
+}
+
+#[derive(Clone)]
+pub struct ScoringRule {
+    pub rank_points: RankPoints,
+    pub combo_bonuses: Vec<ComboBonus>,
+    // Bonus for every full year of combo
+    pub anniversary_bonus: i64,
+    pub freeze: FreezeRule,
+}
+
+impl Default for ScoringRule {
+    fn default() -> Self {
+        ScoringRule {
+            rank_points: RankPoints(vec![10, 5, 3, 1]),
+            combo_bonuses: vec![
+                ComboBonus {
+                    milestone: ComboMilestone::Every(7),
+                    bonus: 20,
+                },
+                ComboBonus {
+                    milestone: ComboMilestone::Every(30),
+                    bonus: 100,
+                },
+            ],
+            anniversary_bonus: 1500,
+            freeze: FreezeRule::default(),
+        }
+    }
+}
+
+impl ScoringRule {
+    pub fn rank_point(&self, rank: i64) -> i64 {
+        let points = &self.rank_points.0;
+        match points.get(rank as usize) {
+            Some(point) => *point,
+            None => points.last().copied().unwrap_or(0),
+        }
+    }
+
+    pub fn combo_bonus(&self, combo: i64) -> i64 {
+        let mut bonus: i64 = self
+            .combo_bonuses
+            .iter()
+            .filter(|combo_bonus| combo_bonus.milestone.reached(combo))
+            .map(|combo_bonus| combo_bonus.bonus)
+            .sum();
+        if combo > 0 && combo % 365 == 0 {
+            bonus += self.anniversary_bonus;
+        }
+        bonus
+    }
+
+    pub fn earned_point(&self, rank: i64, combo: i64) -> i64 {
+        self.rank_point(rank) + self.combo_bonus(combo)
+    }
+
+    // Adds a combo bonus, replacing the bonus of the same milestone.
+    pub fn set_combo_bonus(&mut self, milestone: ComboMilestone, bonus: i64) {
+        self.remove_combo_bonus(milestone);
+        self.combo_bonuses.push(ComboBonus { milestone, bonus });
+    }
+
+    pub fn remove_combo_bonus(&mut self, milestone: ComboMilestone) -> bool {
+        let length = self.combo_bonuses.len();
+        self.combo_bonuses
+            .retain(|combo_bonus| combo_bonus.milestone != milestone);
+        length != self.combo_bonuses.len()
+    }
+}
+
+impl fmt::Display for ScoringRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "순위별 점수: {}", self.rank_points)?;
+        for combo_bonus in self.combo_bonuses.iter() {
+            writeln!(
+                f,
+                "연속 출석 {}: {}점",
+                combo_bonus.milestone, combo_bonus.bonus
+            )?;
+        }
+        writeln!(f, "연속 출석 1년마다: {}점", self.anniversary_bonus)?;
+        write!(
+            f,
+            "휴가: {}점, 최대 {}개",
+            self.freeze.price, self.freeze.max
+        )?;
+        match self.freeze.every {
+            0 => Ok(()),
+            every => write!(f, ", 연속 출석 {}일마다 1개 지급", every),
+        }
+    }
+}
+
+pub enum ScoringRuleChange {
+    Show,
+    RankPoints(RankPoints),
+    SetComboBonus(ComboMilestone, i64),
+    RemoveComboBonus(ComboMilestone),
+    AnniversaryBonus(i64),
+    // Changes the given fields of the freeze rule.
+    Freeze {
+        price: Option<i64>,
+        every: Option<i64>,
+        max: Option<i64>,
+    },
+    Reset,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_point() {
+        let rule = ScoringRule::default();
+        let cases = [(0, 10), (1, 5), (2, 3), (3, 1), (4, 1), (100, 1)];
+        for (rank, expected) in cases {
+            assert_eq!(rule.rank_point(rank), expected, "rank {}", rank);
+        }
+        let empty = ScoringRule {
+            rank_points: RankPoints(vec![]),
+            ..ScoringRule::default()
+        };
+        assert_eq!(empty.rank_point(0), 0);
+    }
+
+    #[test]
+    fn milestone_reached() {
+        let cases = [
+            (ComboMilestone::Every(7), 7, true),
+            (ComboMilestone::Every(7), 14, true),
+            (ComboMilestone::Every(7), 8, false),
+            (ComboMilestone::Exact(7), 7, true),
+            (ComboMilestone::Exact(7), 14, false),
+            (ComboMilestone::Exact(7), 6, false),
+        ];
+        for (milestone, combo, expected) in cases {
+            assert_eq!(
+                milestone.reached(combo),
+                expected,
+                "{} {}",
+                milestone,
+                combo
+            );
+        }
+    }
+
+    #[test]
+    fn earned_point() {
+        let mut rule = ScoringRule::default();
+        rule.set_combo_bonus(ComboMilestone::Exact(3), 7);
+        // (rank, combo, earned point)
+        let cases = [
+            (0, 1, 10),
+            (0, 3, 17),
+            (1, 7, 25),
+            (3, 30, 101),
+            (5, 210, 121),
+            (0, 365, 1510),
+            (2, 730, 1503),
+            (2, 2555, 1523),
+        ];
+        for (rank, combo, expected) in cases {
+            assert_eq!(
+                rule.earned_point(rank, combo),
+                expected,
+                "rank {} combo {}",
+                rank,
+                combo
+            );
+        }
+    }
+
+    #[test]
+    fn anniversary_bonus() {
+        let rule = ScoringRule {
+            combo_bonuses: vec![],
+            ..ScoringRule::default()
+        };
+        let cases = [(0, 0), (364, 0), (365, 1500), (366, 0), (730, 1500)];
+        for (combo, expected) in cases {
+            assert_eq!(rule.combo_bonus(combo), expected, "combo {}", combo);
+        }
+    }
+
+    #[test]
+    fn set_and_remove_combo_bonus() {
+        let mut rule = ScoringRule::default();
+        rule.set_combo_bonus(ComboMilestone::Every(7), 50);
+        assert_eq!(rule.combo_bonus(7), 50);
+        assert!(rule.remove_combo_bonus(ComboMilestone::Every(7)));
+        assert!(!rule.remove_combo_bonus(ComboMilestone::Exact(7)));
+        assert_eq!(rule.combo_bonus(7), 0);
+    }
+
+    #[test]
+    fn parse_rank_points() {
+        assert!("10, 5,3".parse::<RankPoints>() == Ok(RankPoints(vec![10, 5, 3])));
+        for invalid in ["", "10,,5", "10,-1", "a"] {
+            assert!(invalid.parse::<RankPoints>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+
+// Days a shop item can last at most, about ten years
+pub const MAX_ITEM_DURATION: i64 = 3650;
+
+// Item sold in the shop of a guild, identified by its name
+#[derive(Clone, sqlx::FromRow)]
+pub struct ShopItem {
+    pub name: String,
+    pub price: i64,
+    // Items left, None if unlimited
+    pub stock: Option<i64>,
+    // Discord role granted to the buyer
+    pub role_id: Option<i64>,
+    // Days a purchase lasts, None if it never expires
+    pub duration: Option<i64>,
+}
+
+impl fmt::Display for ShopItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}점", self.name, self.price)?;
+        if let Some(stock) = self.stock {
+            write!(f, ", {}개 남음", stock)?;
+        }
+        if let Some(role_id) = self.role_id {
+            write!(f, ", <@&{}> 역할", role_id)?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, ", {}일 동안", duration)?;
+        }
+        Ok(())
+    }
+}
+
+// Item owned by a member, as it was when bought
+#[derive(sqlx::FromRow)]
+pub struct InventoryItem {
+    pub purchase_id: i64,
+    pub name: String,
+    pub price: i64,
+    pub role_id: Option<i64>,
+    pub purchase_time: i64,
+    pub expire_time: Option<i64>,
+}
+
+// Role of an expired purchase which the member has to lose
+pub struct ExpiredRole {
+    pub user_id: i64,
+    pub role_id: i64,
+}
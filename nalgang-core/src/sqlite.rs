@@ -0,0 +1,1826 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+use crate::achievement::{Achievement, AttendanceRecord};
+use crate::archive::{
+    ArchivedAttendance, ArchivedMember, ConflictPolicy, GuildArchive, ImportReport,
+};
+use crate::audit::{AdjustField, Adjustment, AuditEntry, IMPORT_ACTOR};
+use crate::engine::{resolve_attendance, Attendance, GuildAttendance};
+use crate::query::{bind_filter, ranking_scores, RECORDED_COMBOS, SCORES_AT};
+use crate::reminder::{RemindTime, Reminder, ReminderDelivery, ReminderSetting};
+use crate::role_reward::{RewardKind, RoleReward};
+use crate::scoring::{ComboMilestone, FreezeRule, ScoringRule};
+use crate::shop::{ExpiredRole, InventoryItem, ShopItem};
+use crate::storage::Storage;
+use crate::timezone::DayBoundary;
+use crate::{
+    nalgang_error, AttendanceEntry, HistoryEntry, NalgangError, NalgangErrorInner, NalgangMember,
+    RankingEntry, RankingFilter,
+};
+
+// Error of a write to a per guild table. The foreign key to Guild fails if the guild is not
+// registered.
+fn guild_write_error(e: sqlx::Error) -> NalgangError {
+    match e {
+        // SQLITE_CONSTRAINT_FOREIGNKEY
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("787") => {
+            nalgang_error!(NalgangErrorInner::GuildNotExist)
+        }
+        e => nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)),
+    }
+}
+
+// Storage backed by the SQLite database of the bot
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pub database: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub fn new(database: SqlitePool) -> Self {
+        SqliteStorage { database }
+    }
+
+    pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        sqlx::migrate!("../migrations").run(&self.database).await
+    }
+
+    // Rebuilds the database file to give back the space of deleted rows.
+    pub async fn vacuum(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM")
+            .execute(&self.database)
+            .await
+            .map(|_| ())
+    }
+
+    // Writes a consistent copy of the database to a new file at `path`.
+    pub async fn backup(&self, path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path)
+            .execute(&self.database)
+            .await
+            .map(|_| ())
+    }
+
+    async fn insert_achievements(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        member: &NalgangMember,
+        achievements: &[(Achievement, i64)],
+    ) -> Result<Vec<Achievement>, NalgangError> {
+        let mut granted = Vec::new();
+        for (achievement, achieved_time) in achievements.iter() {
+            let id = achievement.id();
+            let r = sqlx::query!(
+                "INSERT OR IGNORE INTO MemberAchievement (guild_id, user_id, achievement, achieved_time)
+                    VALUES (?, ?, ?, ?)",
+                member.gid,
+                member.uid,
+                id,
+                achieved_time
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            if r.rows_affected() > 0 {
+                granted.push(*achievement);
+            }
+        }
+        Ok(granted)
+    }
+
+    async fn get_member_info<'e, E>(
+        &self,
+        executor: E,
+        member: &mut NalgangMember,
+    ) -> Result<bool, NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let row = sqlx::query!(
+            "SELECT score, combo, hit_time, freezes FROM Member WHERE user_id=? AND guild_id=? LIMIT 1",
+            member.uid,
+            member.gid
+        )
+        .fetch_one(executor)
+        .await;
+
+        match row {
+            Ok(record) => {
+                member.update_data(record.score, record.combo, record.hit_time);
+                member.freezes = Some(record.freezes);
+                Ok(true)
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(false),
+                _ => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+            },
+        }
+    }
+
+    async fn update_member_info<'e, E>(
+        &self,
+        executor: E,
+        member: &NalgangMember,
+    ) -> Result<(), NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let (score, combo, hit_time, freezes) = (
+            member.score.unwrap(),
+            member.combo.unwrap(),
+            member.hit_time.unwrap(),
+            member.freezes.unwrap(),
+        );
+        match sqlx::query!(
+            "UPDATE Member SET score=?, combo=?, hit_time=?, freezes=? WHERE guild_id=? AND user_id=?",
+            score,
+            combo,
+            hit_time,
+            freezes,
+            member.gid,
+            member.uid
+        )
+        .execute(executor)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn get_day_boundary<'e, E>(
+        &self,
+        executor: E,
+        gid: i64,
+    ) -> Result<DayBoundary, NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let row = sqlx::query!(
+            "SELECT timezone, day_start_hour FROM GuildSetting WHERE guild_id=? LIMIT 1",
+            gid
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        match row {
+            Some(record) => Ok(DayBoundary {
+                timezone: record
+                    .timezone
+                    .parse()
+                    .map_err(|_| nalgang_error!(NalgangErrorInner::InvalidTimezone))?,
+                day_start_hour: record.day_start_hour as u32,
+            }),
+            None => Ok(DayBoundary::default()),
+        }
+    }
+
+    async fn get_scoring_rule<'e, E>(
+        &self,
+        executor: E,
+        gid: i64,
+    ) -> Result<ScoringRule, NalgangError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let rows = sqlx::query!(
+            r#"SELECT s.rank_points, s.anniversary_bonus, s.freeze_price, s.freeze_every, s.max_freezes,
+                b.kind AS "kind?", b.combo AS "combo?", b.bonus AS "bonus?"
+                FROM ScoringRule s LEFT JOIN ComboBonus b ON s.guild_id=b.guild_id
+                WHERE s.guild_id=? ORDER BY b.combo"#,
+            gid
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let first = match rows.first() {
+            Some(record) => record,
+            None => return Ok(ScoringRule::default()),
+        };
+        let mut rule = ScoringRule {
+            rank_points: first
+                .rank_points
+                .parse()
+                .map_err(|_| nalgang_error!(NalgangErrorInner::InvalidScoringRule))?,
+            combo_bonuses: Vec::new(),
+            anniversary_bonus: first.anniversary_bonus,
+            freeze: FreezeRule {
+                price: first.freeze_price,
+                every: first.freeze_every,
+                max: first.max_freezes,
+            },
+        };
+        for record in rows.iter() {
+            if let (Some(kind), Some(combo), Some(bonus)) =
+                (record.kind.as_ref(), record.combo, record.bonus)
+            {
+                let milestone = ComboMilestone::from_kind(kind, combo)
+                    .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidScoringRule))?;
+                rule.set_combo_bonus(milestone, bonus);
+            }
+        }
+        Ok(rule)
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn register_guild(&self, gid: i64) -> Result<(), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // The key on Guild decides concurrent registrations, so only one of them inserts.
+        let r = sqlx::query!("INSERT OR IGNORE INTO Guild (guild_id) VALUES (?)", gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::DuplicateGuildRegister));
+        }
+
+        sqlx::query!("INSERT INTO AttendanceTimeCount (guild_id) VALUES (?)", gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn guilds(&self) -> Result<Vec<i64>, NalgangError> {
+        sqlx::query_scalar!("SELECT guild_id FROM Guild")
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn register_member(&self, gid: i64, uid: i64) -> Result<(), NalgangError> {
+        match sqlx::query!(
+            "INSERT OR IGNORE INTO Member (user_id, guild_id) VALUES (?, ?)",
+            uid,
+            gid
+        )
+        .execute(&self.database)
+        .await
+        {
+            Ok(r) if r.rows_affected() == 0 => {
+                Err(nalgang_error!(NalgangErrorInner::DuplicateMemberRegister))
+            }
+            Ok(_) => Ok(()),
+            // SQLITE_CONSTRAINT_FOREIGNKEY, the guild is not registered
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("787") => {
+                Err(nalgang_error!(NalgangErrorInner::GuildNotExist))
+            }
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn member_info(&self, member: &mut NalgangMember) -> Result<bool, NalgangError> {
+        self.get_member_info(&self.database, member).await
+    }
+
+    async fn members(&self, gid: i64) -> Result<Vec<NalgangMember>, NalgangError> {
+        let rows = sqlx::query!(
+            "SELECT user_id, score, combo, hit_time, freezes FROM Member WHERE guild_id=?",
+            gid
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|record| {
+                let mut member = NalgangMember::new(record.user_id, gid);
+                member.update_data(record.score, record.combo, record.hit_time);
+                member.freezes = Some(record.freezes);
+                member
+            })
+            .collect())
+    }
+
+    async fn archive_members(
+        &self,
+        gid: i64,
+    ) -> Result<(Vec<ArchivedMember>, Vec<ArchivedAttendance>), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let members = sqlx::query_as!(
+            ArchivedMember,
+            r#"SELECT user_id, score, combo, hit_time, freezes, display_name, departed AS "departed: bool"
+                FROM Member WHERE guild_id=? ORDER BY user_id"#,
+            gid
+        )
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let history = sqlx::query_as!(
+            ArchivedAttendance,
+            "SELECT user_id, hit_time, hit_rank, hit_point, hit_score, hit_combo, hit_message
+                FROM AttendanceHistory WHERE guild_id=? ORDER BY hit_time, user_id",
+            gid
+        )
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok((members, history))
+    }
+
+    async fn import_members(
+        &self,
+        gid: i64,
+        archive: &GuildArchive,
+        policy: ConflictPolicy,
+        current_time: i64,
+    ) -> Result<ImportReport, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let mut report = ImportReport::default();
+
+        let r = sqlx::query!("INSERT OR IGNORE INTO Guild (guild_id) VALUES (?)", gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() > 0 {
+            sqlx::query!("INSERT INTO AttendanceTimeCount (guild_id) VALUES (?)", gid)
+                .execute(&mut transaction)
+                .await
+                .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            report.guild_created = true;
+        }
+
+        let history = archive.history_by_member();
+        let reason = format!("{} 서버에서 가져오기", archive.guild_id);
+        for member in archive.members.iter() {
+            let uid = member.user_id;
+            let existing = sqlx::query!(
+                "SELECT score, combo, hit_time, freezes FROM Member WHERE guild_id=? AND user_id=?",
+                gid,
+                uid
+            )
+            .fetch_optional(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+            let (before, after) = match (existing, policy) {
+                (None, _) => {
+                    sqlx::query!(
+                        "INSERT INTO Member (guild_id, user_id, score, combo, hit_time, freezes, display_name, departed)
+                            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                        gid, uid, member.score, member.combo, member.hit_time, member.freezes,
+                        member.display_name, member.departed
+                    )
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    report.added += 1;
+                    (0, member.score)
+                }
+                (Some(_), ConflictPolicy::Skip) => {
+                    report.skipped += 1;
+                    continue;
+                }
+                (Some(record), ConflictPolicy::Overwrite) => {
+                    sqlx::query!(
+                        "UPDATE Member SET score=?, combo=?, hit_time=?, freezes=?, display_name=?, departed=?
+                            WHERE guild_id=? AND user_id=?",
+                        member.score, member.combo, member.hit_time, member.freezes,
+                        member.display_name, member.departed, gid, uid
+                    )
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    sqlx::query!(
+                        "DELETE FROM AttendanceHistory WHERE guild_id=? AND user_id=?",
+                        gid,
+                        uid
+                    )
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    report.overwritten += 1;
+                    (record.score, member.score)
+                }
+                (Some(record), ConflictPolicy::MergeScores) => {
+                    let score = record.score + member.score;
+                    let (combo, hit_time, freezes) = match member.hit_time > record.hit_time {
+                        true => (member.combo, member.hit_time, member.freezes),
+                        false => (record.combo, record.hit_time, record.freezes),
+                    };
+                    sqlx::query!(
+                        "UPDATE Member SET score=?, combo=?, hit_time=?, freezes=?,
+                            display_name=COALESCE(display_name, ?) WHERE guild_id=? AND user_id=?",
+                        score,
+                        combo,
+                        hit_time,
+                        freezes,
+                        member.display_name,
+                        gid,
+                        uid
+                    )
+                    .execute(&mut transaction)
+                    .await
+                    .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                    report.merged += 1;
+                    (record.score, score)
+                }
+            };
+            sqlx::query!(
+                "INSERT INTO AuditLog (guild_id, actor_id, target_id, field, before, after, reason, event_time)
+                    VALUES (?, ?, ?, 'score', ?, ?, ?, ?)",
+                gid, IMPORT_ACTOR, uid, before, after, reason, current_time
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+            // Attendances the member already has are kept.
+            for entry in history.get(&uid).into_iter().flatten() {
+                let r = sqlx::query!(
+                    "INSERT OR IGNORE INTO AttendanceHistory (guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank, hit_point)
+                        VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                    gid, uid, entry.hit_message, entry.hit_time, entry.hit_score, entry.hit_combo,
+                    entry.hit_rank, entry.hit_point
+                )
+                .execute(&mut transaction)
+                .await
+                .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+                report.history_added += r.rows_affected() as i64;
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(report)
+    }
+
+    async fn restore_guild_attendance(
+        &self,
+        gid: i64,
+        attendance: &GuildAttendance,
+    ) -> Result<(), NalgangError> {
+        sqlx::query!(
+            "UPDATE AttendanceTimeCount SET hit_count=?, hit_time=? WHERE guild_id=? AND hit_time < ?",
+            attendance.hit_count,
+            attendance.hit_time,
+            gid,
+            attendance.hit_time
+        )
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(())
+    }
+
+    async fn recorded_combos(&self, gid: i64) -> Result<Vec<(i64, i64, i64)>, NalgangError> {
+        sqlx::query_as::<_, (i64, i64, i64)>(RECORDED_COMBOS)
+            .bind(gid)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn update_members(&self, members: &[NalgangMember]) -> Result<(), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        for member in members {
+            self.update_member_info(&mut transaction, member).await?;
+        }
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attend(
+        &self,
+        member: &mut NalgangMember,
+        message: &str,
+        current_time: i64,
+    ) -> Result<Attendance, NalgangError> {
+        let (gid, uid) = (member.gid, member.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // sqlx only issues a deferred BEGIN, so take the write lock with a no-op write before
+        // any read. This gives BEGIN IMMEDIATE semantics and serializes rank assignment.
+        let r = sqlx::query!(
+            "UPDATE AttendanceTimeCount SET hit_count=hit_count WHERE guild_id=?",
+            gid
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::GuildNotExist));
+        }
+
+        if !self.get_member_info(&mut transaction, member).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+
+        // Get last hit_count, hit_timestamp from AttendanceTimeCount by guild_id
+        let guild_entry = sqlx::query_as!(
+            GuildAttendance,
+            "SELECT hit_count, hit_time FROM
+                AttendanceTimeCount WHERE guild_id=? LIMIT 1",
+            gid
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => nalgang_error!(NalgangErrorInner::GuildNotExist),
+            _ => nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)),
+        })?;
+
+        let boundary = self.get_day_boundary(&mut transaction, gid).await?;
+        let scoring_rule = self.get_scoring_rule(&mut transaction, gid).await?;
+        let mut attendance =
+            resolve_attendance(member, &guild_entry, &boundary, &scoring_rule, current_time)?;
+
+        let _ = sqlx::query!(
+            "UPDATE AttendanceTimeCount SET hit_count=?, hit_time=? WHERE guild_id=?",
+            attendance.rank,
+            current_time,
+            gid
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let new_score = member.score.unwrap() + attendance.earned_point;
+        // Update Member
+        member.update_data(new_score, attendance.combo, current_time);
+        member.freezes = Some(
+            member.freezes.unwrap() - attendance.frozen_days.len() as i64
+                + attendance.freeze_earned as i64,
+        );
+        self.update_member_info(&mut transaction, member).await?;
+
+        for frozen_day in attendance.frozen_days.iter() {
+            let frozen_day = boundary.day_start(*frozen_day);
+            sqlx::query!(
+                "INSERT INTO FreezeHistory (guild_id, user_id, kind, count, score, frozen_day, event_time)
+                    VALUES (?, ?, 'use', 1, ?, ?, ?)",
+                gid,
+                uid,
+                new_score,
+                frozen_day,
+                current_time
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+        if attendance.freeze_earned {
+            sqlx::query!(
+                "INSERT INTO FreezeHistory (guild_id, user_id, kind, count, score, event_time)
+                    VALUES (?, ?, 'earn', 1, ?, ?)",
+                gid,
+                uid,
+                new_score,
+                current_time
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+
+        // Update DailyAttendance
+        let _ = sqlx::query!(
+            "INSERT INTO DailyAttendance (guild_id, user_id, hit_message, hit_time) VALUES (?, ?, ?, ?)",
+            gid, uid, message, current_time
+        ).execute(&mut transaction).await.map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Insert AttendanceHistory
+        let _ = sqlx::query!(
+            "INSERT INTO AttendanceHistory (guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank, hit_point)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            gid, uid, message, current_time, new_score, attendance.combo, attendance.rank, attendance.earned_point
+        ).execute(&mut transaction).await.map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Grant achievements
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!: i64" FROM AttendanceHistory WHERE guild_id=? AND user_id=?"#,
+            gid,
+            uid
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let record = AttendanceRecord {
+            rank: attendance.rank,
+            combo: attendance.combo,
+            count,
+            day_time: current_time - boundary.day_start(boundary.attendance_date(current_time)),
+        };
+        let achieved: Vec<(Achievement, i64)> = Achievement::evaluate(&record)
+            .into_iter()
+            .map(|achievement| (achievement, current_time))
+            .collect();
+        attendance.achievements = self
+            .insert_achievements(&mut transaction, member, &achieved)
+            .await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(attendance)
+    }
+
+    async fn grant_achievements(
+        &self,
+        member: &NalgangMember,
+        achievements: &[(Achievement, i64)],
+    ) -> Result<Vec<Achievement>, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        let granted = self
+            .insert_achievements(&mut transaction, member, achievements)
+            .await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(granted)
+    }
+
+    async fn achievements(
+        &self,
+        member: &NalgangMember,
+    ) -> Result<Vec<(Achievement, i64)>, NalgangError> {
+        let rows = sqlx::query!(
+            "SELECT achievement, achieved_time FROM MemberAchievement WHERE guild_id=? AND user_id=?
+                ORDER BY achieved_time, achievement",
+            member.gid,
+            member.uid
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Achievements removed from the catalog are skipped.
+        Ok(rows
+            .into_iter()
+            .filter_map(|record| {
+                Achievement::from_id(&record.achievement)
+                    .map(|achievement| (achievement, record.achieved_time))
+            })
+            .collect())
+    }
+
+    async fn buy_freezes(
+        &self,
+        member: &mut NalgangMember,
+        count: i64,
+        rule: &FreezeRule,
+        current_time: i64,
+    ) -> Result<(), NalgangError> {
+        let (gid, uid, cost) = (member.gid, member.uid, rule.price * count);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let r = sqlx::query!(
+            "UPDATE Member SET score=score-?1, freezes=freezes+?2
+                WHERE guild_id=?3 AND user_id=?4 AND score>=?1 AND freezes+?2<=?5",
+            cost,
+            count,
+            gid,
+            uid,
+            rule.max
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let exists = self.get_member_info(&mut transaction, member).await?;
+        if r.rows_affected() == 0 {
+            return Err(match exists {
+                false => nalgang_error!(NalgangErrorInner::MemberNotExist),
+                true if member.freezes.unwrap() + count > rule.max => {
+                    nalgang_error!(NalgangErrorInner::FreezeLimitExceeded)
+                }
+                true => nalgang_error!(NalgangErrorInner::InsufficientScore),
+            });
+        }
+
+        let score = member.score.unwrap();
+        sqlx::query!(
+            "INSERT INTO FreezeHistory (guild_id, user_id, kind, count, score, event_time)
+                VALUES (?, ?, 'buy', ?, ?, ?)",
+            gid,
+            uid,
+            count,
+            score,
+            current_time
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn set_shop_item(&self, gid: i64, item: &ShopItem) -> Result<(), NalgangError> {
+        // Replacing gives the item a new item_id, which no earlier purchase refers to.
+        sqlx::query!(
+            "INSERT OR REPLACE INTO ShopItem (guild_id, name, price, stock, role_id, duration)
+                VALUES (?, ?, ?, ?, ?, ?)",
+            gid,
+            item.name,
+            item.price,
+            item.stock,
+            item.role_id,
+            item.duration
+        )
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn delete_shop_item(&self, gid: i64, name: &str) -> Result<bool, NalgangError> {
+        match sqlx::query!(
+            "DELETE FROM ShopItem WHERE guild_id=? AND name=?",
+            gid,
+            name
+        )
+        .execute(&self.database)
+        .await
+        {
+            Ok(r) => Ok(r.rows_affected() > 0),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn shop_items(&self, gid: i64) -> Result<Vec<ShopItem>, NalgangError> {
+        sqlx::query_as!(
+            ShopItem,
+            "SELECT name, price, stock, role_id, duration FROM ShopItem WHERE guild_id=? ORDER BY price, name",
+            gid
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn buy_item(
+        &self,
+        member: &mut NalgangMember,
+        name: &str,
+        current_time: i64,
+    ) -> Result<InventoryItem, NalgangError> {
+        let (gid, uid) = (member.gid, member.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Write first so that the transaction takes the database write lock before any read.
+        // Unlimited stock stays NULL.
+        let r = sqlx::query!(
+            "UPDATE ShopItem SET stock=stock-1 WHERE guild_id=? AND name=? AND (stock IS NULL OR stock>0)",
+            gid,
+            name
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let item = sqlx::query_as!(
+            ShopItem,
+            "SELECT name, price, stock, role_id, duration FROM ShopItem WHERE guild_id=? AND name=?",
+            gid,
+            name
+        )
+        .fetch_optional(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?
+        .ok_or_else(|| nalgang_error!(NalgangErrorInner::ShopItemNotExist))?;
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::OutOfStock));
+        }
+
+        let r = sqlx::query!(
+            "UPDATE Member SET score=score-?1 WHERE guild_id=?2 AND user_id=?3 AND score>=?1",
+            item.price,
+            gid,
+            uid
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let exists = self.get_member_info(&mut transaction, member).await?;
+        if r.rows_affected() == 0 {
+            return Err(match exists {
+                false => nalgang_error!(NalgangErrorInner::MemberNotExist),
+                true => nalgang_error!(NalgangErrorInner::InsufficientScore),
+            });
+        }
+
+        let score = member.score.unwrap();
+        let expire_time = item
+            .duration
+            .map(|duration| current_time + duration * 86400);
+        let r = sqlx::query!(
+            "INSERT INTO Inventory (guild_id, user_id, item_id, name, price, role_id, score, purchase_time,
+                expire_time)
+                VALUES (?1, ?2, (SELECT item_id FROM ShopItem WHERE guild_id=?1 AND name=?3), ?3, ?4, ?5,
+                ?6, ?7, ?8)",
+            gid,
+            uid,
+            item.name,
+            item.price,
+            item.role_id,
+            score,
+            current_time,
+            expire_time
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(InventoryItem {
+            purchase_id: r.last_insert_rowid(),
+            name: item.name,
+            price: item.price,
+            role_id: item.role_id,
+            purchase_time: current_time,
+            expire_time,
+        })
+    }
+
+    async fn cancel_purchase(
+        &self,
+        member: &mut NalgangMember,
+        purchase_id: i64,
+    ) -> Result<bool, NalgangError> {
+        let (gid, uid) = (member.gid, member.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let r = sqlx::query!(
+            "UPDATE Member SET score=score+(
+                SELECT price FROM Inventory WHERE purchase_id=?1 AND guild_id=?2 AND user_id=?3
+            ) WHERE guild_id=?2 AND user_id=?3 AND EXISTS (
+                SELECT (1) FROM Inventory WHERE purchase_id=?1 AND guild_id=?2 AND user_id=?3
+            )",
+            purchase_id,
+            gid,
+            uid
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            "UPDATE ShopItem SET stock=stock+1 WHERE item_id=(
+                SELECT item_id FROM Inventory WHERE purchase_id=?
+            )",
+            purchase_id
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        sqlx::query!("DELETE FROM Inventory WHERE purchase_id=?", purchase_id)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        self.get_member_info(&mut transaction, member).await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(true)
+    }
+
+    async fn inventory(
+        &self,
+        member: &NalgangMember,
+        current_time: i64,
+    ) -> Result<Vec<InventoryItem>, NalgangError> {
+        sqlx::query_as!(
+            InventoryItem,
+            r#"SELECT purchase_id AS "purchase_id!", name, price, role_id, purchase_time, expire_time
+                FROM Inventory WHERE guild_id=?1 AND user_id=?2 AND expired=0
+                AND (expire_time IS NULL OR expire_time > ?3)
+                ORDER BY purchase_id DESC"#,
+            member.gid,
+            member.uid,
+            current_time
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn expire_items(
+        &self,
+        gid: i64,
+        current_time: i64,
+    ) -> Result<Vec<ExpiredRole>, NalgangError> {
+        let expired = sqlx::query!(
+            r#"SELECT purchase_id AS "purchase_id!", user_id, role_id FROM Inventory
+                WHERE guild_id=? AND expired=0 AND expire_time <= ?"#,
+            gid,
+            current_time
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        for record in expired.iter() {
+            sqlx::query!(
+                "UPDATE Inventory SET expired=1 WHERE purchase_id=?",
+                record.purchase_id
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+
+        let mut roles: Vec<ExpiredRole> = Vec::new();
+        for record in expired.iter() {
+            let role_id = match record.role_id {
+                Some(role_id) => role_id,
+                None => continue,
+            };
+            if roles
+                .iter()
+                .any(|role| role.user_id == record.user_id && role.role_id == role_id)
+            {
+                continue;
+            }
+            let still_granted = sqlx::query_scalar!(
+                "SELECT EXISTS (SELECT (1) FROM Inventory WHERE guild_id=? AND user_id=? AND role_id=?
+                    AND expired=0 AND (expire_time IS NULL OR expire_time > ?) LIMIT 1)",
+                gid,
+                record.user_id,
+                role_id,
+                current_time
+            )
+            .fetch_one(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            if still_granted == 0 {
+                roles.push(ExpiredRole {
+                    user_id: record.user_id,
+                    role_id,
+                });
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(roles)
+    }
+
+    async fn transfer(
+        &self,
+        sender: &mut NalgangMember,
+        receiver: &mut NalgangMember,
+        amount: i64,
+        current_time: i64,
+    ) -> Result<(), NalgangError> {
+        let gid = sender.gid;
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Write first so that the transaction takes the database write lock before any read.
+        let r = sqlx::query!(
+            "UPDATE Member SET score=score-? WHERE guild_id=? AND user_id=? AND score>=?",
+            amount,
+            gid,
+            sender.uid,
+            amount
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        if r.rows_affected() == 0 {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS (SELECT (1) FROM Member WHERE guild_id=? AND user_id=? LIMIT 1)",
+                gid,
+                sender.uid
+            )
+            .fetch_one(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            return match exists {
+                0 => Err(nalgang_error!(NalgangErrorInner::MemberNotExist)),
+                _ => Err(nalgang_error!(NalgangErrorInner::InsufficientScore)),
+            };
+        }
+
+        let r = sqlx::query!(
+            "UPDATE Member SET score=score+? WHERE guild_id=? AND user_id=?",
+            amount,
+            gid,
+            receiver.uid
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        if r.rows_affected() == 0 {
+            return Err(nalgang_error!(NalgangErrorInner::ReceiverNotExist));
+        }
+
+        self.get_member_info(&mut transaction, sender).await?;
+        self.get_member_info(&mut transaction, receiver).await?;
+
+        // Insert TransferHistory
+        let (sender_score, receiver_score) = (sender.score.unwrap(), receiver.score.unwrap());
+        let _ = sqlx::query!(
+            "INSERT INTO TransferHistory (guild_id, sender_id, receiver_id, amount, sender_score, receiver_score, transfer_time)
+                VALUES (?, ?, ?, ?, ?, ?, ?)",
+            gid, sender.uid, receiver.uid, amount, sender_score, receiver_score, current_time
+        ).execute(&mut transaction).await.map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn adjust_member(
+        &self,
+        actor_id: i64,
+        target: &mut NalgangMember,
+        field: AdjustField,
+        adjustment: Adjustment,
+        reason: &str,
+        current_time: i64,
+    ) -> Result<AuditEntry, NalgangError> {
+        let (gid, uid) = (target.gid, target.uid);
+
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Take the write lock with a no-op write before reading the value to change.
+        sqlx::query!(
+            "UPDATE Member SET score=score WHERE guild_id=? AND user_id=?",
+            gid,
+            uid
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if !self.get_member_info(&mut transaction, target).await? {
+            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        }
+
+        let before = match field {
+            AdjustField::Score => target.score.unwrap(),
+            AdjustField::Combo => target.combo.unwrap(),
+        };
+        let after = adjustment
+            .apply(before)
+            .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidAdjustment))?;
+        match field {
+            AdjustField::Score => target.score = Some(after),
+            AdjustField::Combo if after < 0 => {
+                return Err(nalgang_error!(NalgangErrorInner::InvalidAdjustment))
+            }
+            AdjustField::Combo => target.combo = Some(after),
+        }
+        self.update_member_info(&mut transaction, target).await?;
+
+        let kind = field.kind();
+        let r = sqlx::query!(
+            "INSERT INTO AuditLog (guild_id, actor_id, target_id, field, before, after, reason, event_time)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            gid,
+            actor_id,
+            uid,
+            kind,
+            before,
+            after,
+            reason,
+            current_time
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(AuditEntry {
+            audit_id: r.last_insert_rowid(),
+            actor_id,
+            target_id: uid,
+            field,
+            before,
+            after,
+            reason: reason.to_string(),
+            event_time: current_time,
+        })
+    }
+
+    async fn audit_log(&self, gid: i64, limit: i64) -> Result<Vec<AuditEntry>, NalgangError> {
+        let rows = sqlx::query!(
+            r#"SELECT audit_id AS "audit_id!", actor_id, target_id, field, before, after, reason, event_time
+                FROM AuditLog WHERE guild_id=? ORDER BY audit_id DESC LIMIT ?"#,
+            gid,
+            limit
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        rows.into_iter()
+            .map(|record| {
+                Ok(AuditEntry {
+                    audit_id: record.audit_id,
+                    actor_id: record.actor_id,
+                    target_id: record.target_id,
+                    field: AdjustField::from_kind(&record.field)
+                        .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidAdjustment))?,
+                    before: record.before,
+                    after: record.after,
+                    reason: record.reason,
+                    event_time: record.event_time,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_token(
+        &self,
+        member: &NalgangMember,
+        token: &str,
+    ) -> Result<bool, NalgangError> {
+        let r = sqlx::query!(
+            "insert or ignore into Token (guild_id, user_id, token) VALUES (?, ?, ?)",
+            member.gid,
+            member.uid,
+            token
+        )
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        match r.rows_affected() {
+            1 => Ok(true),
+            0 => Ok(false),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn delete_token(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        let r = sqlx::query!(
+            "delete from Token where guild_id=? and user_id=?",
+            member.gid,
+            member.uid
+        )
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        match r.rows_affected() {
+            1 => Ok(true),
+            0 => Ok(false),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn token_owner(&self, token: &str) -> Result<Option<NalgangMember>, NalgangError> {
+        let row = sqlx::query!(
+            "SELECT guild_id, user_id FROM Token WHERE token=? LIMIT 1",
+            token
+        )
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        Ok(row.map(|record| NalgangMember::new(record.user_id, record.guild_id)))
+    }
+
+    async fn update_profile(
+        &self,
+        member: &NalgangMember,
+        display_name: &str,
+        departed: bool,
+    ) -> Result<(), NalgangError> {
+        sqlx::query!(
+            "UPDATE Member SET display_name=?, departed=? WHERE guild_id=? AND user_id=?",
+            display_name,
+            departed,
+            member.gid,
+            member.uid
+        )
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(())
+    }
+
+    async fn ranking(&self, gid: i64) -> Result<Vec<RankingEntry>, NalgangError> {
+        sqlx::query_as!(
+            RankingEntry,
+            r#"SELECT user_id, score, display_name, departed AS "departed: bool" FROM Member
+                WHERE guild_id=? ORDER BY score DESC, user_id"#,
+            gid,
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn ranking_page(
+        &self,
+        filter: &RankingFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<RankingEntry>, NalgangError> {
+        let sql = format!(
+            "{} SELECT s.user_id, s.score, m.display_name, m.departed FROM Scores s
+                JOIN Member m ON m.guild_id=$1 AND m.user_id=s.user_id
+                ORDER BY s.sort_key DESC, s.user_id LIMIT $8 OFFSET $9",
+            ranking_scores(filter.kind, filter.window.is_some())
+        );
+        bind_filter!(sqlx::query_as::<_, RankingEntry>(&sql), filter)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn ranked_count(&self, filter: &RankingFilter) -> Result<i64, NalgangError> {
+        let sql = format!(
+            "{} SELECT COUNT(*) FROM Scores",
+            ranking_scores(filter.kind, filter.window.is_some())
+        );
+        bind_filter!(sqlx::query_scalar::<_, i64>(&sql), filter)
+            .fetch_one(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn member_rank(
+        &self,
+        filter: &RankingFilter,
+        uid: i64,
+    ) -> Result<Option<i64>, NalgangError> {
+        let sql = format!(
+            "{} SELECT (SELECT COUNT(*) FROM Scores o WHERE o.sort_key > s.sort_key
+                OR (o.sort_key = s.sort_key AND o.user_id < s.user_id))
+                FROM Scores s WHERE s.user_id=$8",
+            ranking_scores(filter.kind, filter.window.is_some())
+        );
+        bind_filter!(sqlx::query_scalar::<_, i64>(&sql), filter)
+            .bind(uid)
+            .fetch_optional(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attendance_history(
+        &self,
+        member: &NalgangMember,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError> {
+        sqlx::query_as!(
+            HistoryEntry,
+            r#"SELECT hit_time AS "hit_time!", hit_rank AS "hit_rank!", hit_point AS "hit_point!",
+                hit_score AS "hit_score!", hit_combo AS "hit_combo!", hit_message FROM AttendanceHistory
+                WHERE guild_id=? AND user_id=? ORDER BY hit_time DESC LIMIT ? OFFSET ?"#,
+            member.gid,
+            member.uid,
+            limit,
+            offset
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attendance_count(&self, member: &NalgangMember) -> Result<i64, NalgangError> {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!: i64" FROM AttendanceHistory WHERE guild_id=? AND user_id=?"#,
+            member.gid,
+            member.uid
+        )
+        .fetch_one(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn attendance_history_since(
+        &self,
+        member: &NalgangMember,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError> {
+        sqlx::query_as!(
+            HistoryEntry,
+            "SELECT hit_time, hit_rank, hit_point, hit_score, hit_combo, hit_message FROM AttendanceHistory
+                WHERE guild_id=? AND user_id=? AND hit_time >= ? ORDER BY hit_time",
+            member.gid,
+            member.uid,
+            since
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn daily_attendance(
+        &self,
+        gid: i64,
+        since: i64,
+    ) -> Result<Vec<AttendanceEntry>, NalgangError> {
+        sqlx::query_as!(
+            AttendanceEntry,
+            r#"SELECT d.user_id, d.hit_message, d.hit_time, m.display_name AS "display_name?",
+                m.departed AS "departed!: bool" FROM DailyAttendance d
+                JOIN Member m ON d.guild_id=m.guild_id AND d.user_id=m.user_id
+                WHERE d.guild_id=? AND d.hit_time >= ?"#,
+            gid,
+            since
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn reset_day(
+        &self,
+        gid: i64,
+        day_start: i64,
+        since: i64,
+    ) -> Result<Option<Vec<AttendanceEntry>>, NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Marking the day first takes the write lock, so attendances and other resets wait.
+        let r = sqlx::query!(
+            "UPDATE AttendanceTimeCount SET reset_time=? WHERE guild_id=? AND reset_time < ?",
+            day_start,
+            gid,
+            day_start
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        if r.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let entries = sqlx::query_as!(
+            AttendanceEntry,
+            r#"SELECT d.user_id, d.hit_message, d.hit_time, m.display_name AS "display_name?",
+                m.departed AS "departed!: bool" FROM DailyAttendance d
+                JOIN Member m ON d.guild_id=m.guild_id AND d.user_id=m.user_id
+                WHERE d.guild_id=? AND d.hit_time >= ? AND d.hit_time < ? ORDER BY d.hit_time"#,
+            gid,
+            since,
+            day_start
+        )
+        .fetch_all(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Attendances of the new day may already be in when the reset runs late.
+        sqlx::query!(
+            "DELETE FROM DailyAttendance WHERE guild_id=? AND hit_time < ?",
+            gid,
+            day_start
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        // Same state as a newly registered guild, so the next attendance takes the first rank.
+        sqlx::query!(
+            "UPDATE AttendanceTimeCount SET hit_count=0, hit_time=0 WHERE guild_id=? AND hit_time < ?",
+            gid,
+            day_start
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(Some(entries))
+    }
+
+    async fn attendance_combos(
+        &self,
+        gid: i64,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError> {
+        let rows = sqlx::query!(
+            "SELECT user_id, hit_combo FROM AttendanceHistory
+                WHERE guild_id=? AND hit_time >= ? AND hit_time < ? ORDER BY hit_time",
+            gid,
+            since,
+            until
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(rows
+            .into_iter()
+            .map(|record| (record.user_id, record.hit_combo))
+            .collect())
+    }
+
+    async fn scores_at(
+        &self,
+        gid: i64,
+        include_departed: bool,
+        time: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError> {
+        sqlx::query_as::<_, (i64, i64)>(SCORES_AT)
+            .bind(gid)
+            .bind(include_departed)
+            .bind(time)
+            .fetch_all(&self.database)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+
+    async fn day_boundary(&self, gid: i64) -> Result<DayBoundary, NalgangError> {
+        self.get_day_boundary(&self.database, gid).await
+    }
+
+    async fn set_day_boundary(&self, gid: i64, boundary: &DayBoundary) -> Result<(), NalgangError> {
+        let (timezone, day_start_hour) = (boundary.timezone.to_string(), boundary.day_start_hour);
+        sqlx::query!(
+            "INSERT INTO GuildSetting (guild_id, timezone, day_start_hour) VALUES (?, ?, ?)
+                ON CONFLICT(guild_id) DO UPDATE SET timezone=excluded.timezone, day_start_hour=excluded.day_start_hour",
+            gid,
+            timezone,
+            day_start_hour
+        )
+        .execute(&self.database)
+        .await
+        .map_err(guild_write_error)?;
+        Ok(())
+    }
+
+    async fn hide_departed(&self, gid: i64) -> Result<bool, NalgangError> {
+        let row = sqlx::query_scalar!(
+            r#"SELECT hide_departed AS "hide_departed: bool" FROM GuildSetting WHERE guild_id=? LIMIT 1"#,
+            gid
+        )
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(row.unwrap_or(false))
+    }
+
+    async fn set_hide_departed(&self, gid: i64, hide_departed: bool) -> Result<(), NalgangError> {
+        sqlx::query!(
+            "INSERT INTO GuildSetting (guild_id, hide_departed) VALUES (?, ?)
+                ON CONFLICT(guild_id) DO UPDATE SET hide_departed=excluded.hide_departed",
+            gid,
+            hide_departed
+        )
+        .execute(&self.database)
+        .await
+        .map_err(guild_write_error)?;
+        Ok(())
+    }
+
+    async fn summary_channel(&self, gid: i64) -> Result<Option<i64>, NalgangError> {
+        let row = sqlx::query_scalar!(
+            "SELECT summary_channel FROM GuildSetting WHERE guild_id=? LIMIT 1",
+            gid
+        )
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(row.flatten())
+    }
+
+    async fn set_summary_channel(
+        &self,
+        gid: i64,
+        channel_id: Option<i64>,
+    ) -> Result<(), NalgangError> {
+        sqlx::query!(
+            "INSERT INTO GuildSetting (guild_id, summary_channel) VALUES (?, ?)
+                ON CONFLICT(guild_id) DO UPDATE SET summary_channel=excluded.summary_channel",
+            gid,
+            channel_id
+        )
+        .execute(&self.database)
+        .await
+        .map_err(guild_write_error)?;
+        Ok(())
+    }
+
+    async fn set_reminder(
+        &self,
+        member: &NalgangMember,
+        remind_time: RemindTime,
+        delivery: ReminderDelivery,
+    ) -> Result<(), NalgangError> {
+        let (minutes, kind) = (remind_time.minutes(), delivery.kind());
+        sqlx::query!(
+            "INSERT INTO Reminder (guild_id, user_id, remind_time, delivery) VALUES (?, ?, ?, ?)
+                ON CONFLICT(guild_id, user_id) DO UPDATE SET remind_time=excluded.remind_time, delivery=excluded.delivery",
+            member.gid,
+            member.uid,
+            minutes,
+            kind
+        )
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(())
+    }
+
+    async fn delete_reminder(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
+        match sqlx::query!(
+            "DELETE FROM Reminder WHERE guild_id=? AND user_id=?",
+            member.gid,
+            member.uid
+        )
+        .execute(&self.database)
+        .await
+        {
+            Ok(r) => Ok(r.rows_affected() > 0),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn reminders(&self, gid: i64) -> Result<Vec<Reminder>, NalgangError> {
+        let rows = sqlx::query!(
+            "SELECT r.user_id, r.remind_time, r.delivery, r.last_reminded, m.combo, m.hit_time,
+                m.freezes
+                FROM Reminder r JOIN Member m ON r.guild_id=m.guild_id AND r.user_id=m.user_id
+                WHERE r.guild_id=? AND m.departed=0",
+            gid
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        rows.into_iter()
+            .map(|record| {
+                Ok(Reminder {
+                    user_id: record.user_id,
+                    remind_time: RemindTime::from_minutes(record.remind_time)
+                        .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidRemindTime))?,
+                    delivery: ReminderDelivery::from_kind(&record.delivery)
+                        .unwrap_or(ReminderDelivery::DirectMessage),
+                    last_reminded: record.last_reminded,
+                    combo: record.combo,
+                    hit_time: record.hit_time,
+                    freezes: record.freezes,
+                })
+            })
+            .collect()
+    }
+
+    async fn mark_reminded(&self, gid: i64, uid: i64, time: i64) -> Result<(), NalgangError> {
+        sqlx::query!(
+            "UPDATE Reminder SET last_reminded=? WHERE guild_id=? AND user_id=?",
+            time,
+            gid,
+            uid
+        )
+        .execute(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        Ok(())
+    }
+
+    async fn reminder_setting(&self, gid: i64) -> Result<ReminderSetting, NalgangError> {
+        let row = sqlx::query!(
+            r#"SELECT reminder_enabled AS "reminder_enabled: bool", reminder_channel,
+                reminder_min_combo FROM GuildSetting WHERE guild_id=? LIMIT 1"#,
+            gid
+        )
+        .fetch_optional(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        Ok(match row {
+            Some(record) => ReminderSetting {
+                enabled: record.reminder_enabled,
+                channel_id: record.reminder_channel,
+                min_combo: record.reminder_min_combo,
+            },
+            None => ReminderSetting::default(),
+        })
+    }
+
+    async fn set_reminder_setting(
+        &self,
+        gid: i64,
+        setting: &ReminderSetting,
+    ) -> Result<(), NalgangError> {
+        sqlx::query!(
+            "INSERT INTO GuildSetting (guild_id, reminder_enabled, reminder_channel, reminder_min_combo)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(guild_id) DO UPDATE SET reminder_enabled=excluded.reminder_enabled,
+                reminder_channel=excluded.reminder_channel, reminder_min_combo=excluded.reminder_min_combo",
+            gid,
+            setting.enabled,
+            setting.channel_id,
+            setting.min_combo
+        )
+        .execute(&self.database)
+        .await
+        .map_err(guild_write_error)?;
+        Ok(())
+    }
+
+    async fn role_rewards(&self, gid: i64) -> Result<Vec<RoleReward>, NalgangError> {
+        let rows = sqlx::query!(
+            "SELECT role_id, kind, threshold, revoke FROM RoleReward WHERE guild_id=?
+                ORDER BY kind DESC, threshold, role_id",
+            gid
+        )
+        .fetch_all(&self.database)
+        .await
+        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        rows.into_iter()
+            .map(|record| {
+                Ok(RoleReward {
+                    role_id: record.role_id,
+                    kind: RewardKind::from_kind(&record.kind)
+                        .ok_or_else(|| nalgang_error!(NalgangErrorInner::InvalidRoleReward))?,
+                    threshold: record.threshold,
+                    revoke: record.revoke,
+                })
+            })
+            .collect()
+    }
+
+    async fn set_role_reward(&self, gid: i64, reward: &RoleReward) -> Result<(), NalgangError> {
+        let kind = reward.kind.kind();
+        sqlx::query!(
+            "INSERT INTO RoleReward (guild_id, role_id, kind, threshold, revoke) VALUES (?, ?, ?, ?, ?)
+                ON CONFLICT(guild_id, role_id) DO UPDATE SET kind=excluded.kind,
+                threshold=excluded.threshold, revoke=excluded.revoke",
+            gid,
+            reward.role_id,
+            kind,
+            reward.threshold,
+            reward.revoke
+        )
+        .execute(&self.database)
+        .await
+        .map(|_| ())
+        .map_err(guild_write_error)
+    }
+
+    async fn delete_role_reward(&self, gid: i64, role_id: i64) -> Result<bool, NalgangError> {
+        match sqlx::query!(
+            "DELETE FROM RoleReward WHERE guild_id=? AND role_id=?",
+            gid,
+            role_id
+        )
+        .execute(&self.database)
+        .await
+        {
+            Ok(r) => Ok(r.rows_affected() > 0),
+            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        }
+    }
+
+    async fn scoring_rule(&self, gid: i64) -> Result<ScoringRule, NalgangError> {
+        self.get_scoring_rule(&self.database, gid).await
+    }
+
+    async fn set_scoring_rule(&self, gid: i64, rule: &ScoringRule) -> Result<(), NalgangError> {
+        let mut transaction = self
+            .database
+            .begin()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        let rank_points = rule.rank_points.to_string();
+        sqlx::query!(
+            "INSERT INTO ScoringRule (guild_id, rank_points, anniversary_bonus, freeze_price, freeze_every, max_freezes)
+                VALUES (?, ?, ?, ?, ?, ?)
+                ON CONFLICT(guild_id) DO UPDATE SET rank_points=excluded.rank_points, anniversary_bonus=excluded.anniversary_bonus,
+                freeze_price=excluded.freeze_price, freeze_every=excluded.freeze_every, max_freezes=excluded.max_freezes",
+            gid,
+            rank_points,
+            rule.anniversary_bonus,
+            rule.freeze.price,
+            rule.freeze.every,
+            rule.freeze.max
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(guild_write_error)?;
+
+        sqlx::query!("DELETE FROM ComboBonus WHERE guild_id=?", gid)
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+
+        for combo_bonus in rule.combo_bonuses.iter() {
+            let (kind, combo) = (combo_bonus.milestone.kind(), combo_bonus.milestone.days());
+            sqlx::query!(
+                "INSERT INTO ComboBonus (guild_id, kind, combo, bonus) VALUES (?, ?, ?, ?)",
+                gid,
+                kind,
+                combo,
+                combo_bonus.bonus
+            )
+            .execute(&mut transaction)
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))
+    }
+}
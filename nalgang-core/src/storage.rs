@@ -0,0 +1,1180 @@
+use async_trait::async_trait;
+
+use crate::achievement::Achievement;
+use crate::archive::{
+    ArchivedAttendance, ArchivedMember, ConflictPolicy, GuildArchive, ImportReport,
+};
+use crate::audit::{AdjustField, Adjustment, AuditEntry};
+use crate::engine::{Attendance, GuildAttendance};
+use crate::reminder::{RemindTime, Reminder, ReminderDelivery, ReminderSetting};
+use crate::role_reward::RoleReward;
+use crate::scoring::{FreezeRule, ScoringRule};
+use crate::shop::{ExpiredRole, InventoryItem, ShopItem};
+use crate::timezone::DayBoundary;
+use crate::{
+    AttendanceEntry, HistoryEntry, NalgangError, NalgangMember, RankingEntry, RankingFilter,
+};
+
+// Persistence used by the engine. Every method is atomic on its own.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn register_guild(&self, gid: i64) -> Result<(), NalgangError>;
+
+    // Guilds registered with `register_guild`
+    async fn guilds(&self) -> Result<Vec<i64>, NalgangError>;
+
+    async fn register_member(&self, gid: i64, uid: i64) -> Result<(), NalgangError>;
+
+    // Fills score, combo and hit_time of `member`. Returns false if the member is not registered.
+    async fn member_info(&self, member: &mut NalgangMember) -> Result<bool, NalgangError>;
+
+    // Every registered member of the guild with score, combo, hit_time and freezes filled.
+    async fn members(&self, gid: i64) -> Result<Vec<NalgangMember>, NalgangError>;
+
+    // Every member of the guild ordered by user id, and their attendances ordered by time, read
+    // at once for an archive.
+    async fn archive_members(
+        &self,
+        gid: i64,
+    ) -> Result<(Vec<ArchivedMember>, Vec<ArchivedAttendance>), NalgangError>;
+
+    // Restores the members of a validated archive and their attendances into the guild,
+    // registering the guild if needed. Settings are left to the caller. The score of every added,
+    // overwritten or merged member is logged as an admin change at `current_time`, so the
+    // history still records it.
+    async fn import_members(
+        &self,
+        gid: i64,
+        archive: &GuildArchive,
+        policy: ConflictPolicy,
+        current_time: i64,
+    ) -> Result<ImportReport, NalgangError>;
+
+    // Records the last attendance of the guild unless a later one is recorded.
+    async fn restore_guild_attendance(
+        &self,
+        gid: i64,
+        attendance: &GuildAttendance,
+    ) -> Result<(), NalgangError>;
+
+    // (user_id, combo, hit_time) of every member of the guild as its attendance history and
+    // admin changes record them, ordered by user id.
+    async fn recorded_combos(&self, gid: i64) -> Result<Vec<(i64, i64, i64)>, NalgangError>;
+
+    // Writes score, combo, hit_time and freezes of the members at once.
+    async fn update_members(&self, members: &[NalgangMember]) -> Result<(), NalgangError>;
+
+    // Resolves the attendance of a member with `engine::resolve_attendance` and records it with the
+    // achievements it newly earned.
+    async fn attend(
+        &self,
+        member: &mut NalgangMember,
+        message: &str,
+        current_time: i64,
+    ) -> Result<Attendance, NalgangError>;
+
+    // Records (achievement, achieved_time) the member did not have yet and returns them.
+    async fn grant_achievements(
+        &self,
+        member: &NalgangMember,
+        achievements: &[(Achievement, i64)],
+    ) -> Result<Vec<Achievement>, NalgangError>;
+
+    // (achievement, achieved_time) of a member in the order they were earned
+    async fn achievements(
+        &self,
+        member: &NalgangMember,
+    ) -> Result<Vec<(Achievement, i64)>, NalgangError>;
+
+    // Trades score for `count` freezes and refreshes the member.
+    async fn buy_freezes(
+        &self,
+        member: &mut NalgangMember,
+        count: i64,
+        rule: &FreezeRule,
+        current_time: i64,
+    ) -> Result<(), NalgangError>;
+
+    // Adds the item, replacing the one of the same name.
+    async fn set_shop_item(&self, gid: i64, item: &ShopItem) -> Result<(), NalgangError>;
+
+    // Returns false if the shop had no such item.
+    async fn delete_shop_item(&self, gid: i64, name: &str) -> Result<bool, NalgangError>;
+
+    // Items of the shop ordered by price
+    async fn shop_items(&self, gid: i64) -> Result<Vec<ShopItem>, NalgangError>;
+
+    // Trades score for one of the item, puts it in the inventory and refreshes the member.
+    async fn buy_item(
+        &self,
+        member: &mut NalgangMember,
+        name: &str,
+        current_time: i64,
+    ) -> Result<InventoryItem, NalgangError>;
+
+    // Gives back the score and the stock of a purchase, removes it from the inventory and refreshes
+    // the member. Returns false if the member had no such purchase.
+    async fn cancel_purchase(
+        &self,
+        member: &mut NalgangMember,
+        purchase_id: i64,
+    ) -> Result<bool, NalgangError>;
+
+    // Purchases of a member which did not expire at `current_time`, latest first.
+    async fn inventory(
+        &self,
+        member: &NalgangMember,
+        current_time: i64,
+    ) -> Result<Vec<InventoryItem>, NalgangError>;
+
+    // Marks the purchases which expired at `current_time`. Returns the roles they granted unless
+    // another purchase of the member still grants the same role.
+    async fn expire_items(
+        &self,
+        gid: i64,
+        current_time: i64,
+    ) -> Result<Vec<ExpiredRole>, NalgangError>;
+
+    // Moves `amount` score and refreshes both members.
+    async fn transfer(
+        &self,
+        sender: &mut NalgangMember,
+        receiver: &mut NalgangMember,
+        amount: i64,
+        current_time: i64,
+    ) -> Result<(), NalgangError>;
+
+    // Changes a value of `target`, refreshes it and records the change made by `actor_id`.
+    // Fails with InvalidAdjustment if the value would overflow or the combo would be negative.
+    async fn adjust_member(
+        &self,
+        actor_id: i64,
+        target: &mut NalgangMember,
+        field: AdjustField,
+        adjustment: Adjustment,
+        reason: &str,
+        current_time: i64,
+    ) -> Result<AuditEntry, NalgangError>;
+
+    // Audit entries of the guild, latest first.
+    async fn audit_log(&self, gid: i64, limit: i64) -> Result<Vec<AuditEntry>, NalgangError>;
+
+    // Returns false if the member already owns a token.
+    async fn insert_token(&self, member: &NalgangMember, token: &str)
+        -> Result<bool, NalgangError>;
+
+    async fn delete_token(&self, member: &NalgangMember) -> Result<bool, NalgangError>;
+
+    async fn token_owner(&self, token: &str) -> Result<Option<NalgangMember>, NalgangError>;
+
+    // Stores the last known display name and whether the member left the guild.
+    async fn update_profile(
+        &self,
+        member: &NalgangMember,
+        display_name: &str,
+        departed: bool,
+    ) -> Result<(), NalgangError>;
+
+    // Members ordered by score, ties broken by user id so that pages never overlap.
+    async fn ranking(&self, gid: i64) -> Result<Vec<RankingEntry>, NalgangError>;
+
+    // Same order as `ranking`. Members without attendance in the window are left out.
+    async fn ranking_page(
+        &self,
+        filter: &RankingFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<RankingEntry>, NalgangError>;
+
+    async fn ranked_count(&self, filter: &RankingFilter) -> Result<i64, NalgangError>;
+
+    // Position of the member in `ranking_page`, starting from 0. None if the member is not ranked.
+    async fn member_rank(
+        &self,
+        filter: &RankingFilter,
+        uid: i64,
+    ) -> Result<Option<i64>, NalgangError>;
+
+    // Attendances of a member, latest first.
+    async fn attendance_history(
+        &self,
+        member: &NalgangMember,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError>;
+
+    async fn attendance_count(&self, member: &NalgangMember) -> Result<i64, NalgangError>;
+
+    // Attendances of a member since `since`, oldest first.
+    async fn attendance_history_since(
+        &self,
+        member: &NalgangMember,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, NalgangError>;
+
+    async fn daily_attendance(
+        &self,
+        gid: i64,
+        since: i64,
+    ) -> Result<Vec<AttendanceEntry>, NalgangError>;
+
+    // Clears the attendances before `day_start` and resets the guild for the day from it.
+    // Returns the cleared attendances since `since` in attendance order, or None if the guild was
+    // already reset for the day.
+    async fn reset_day(
+        &self,
+        gid: i64,
+        day_start: i64,
+        since: i64,
+    ) -> Result<Option<Vec<AttendanceEntry>>, NalgangError>;
+
+    // (user_id, combo) of the attendances in [since, until), in attendance order.
+    async fn attendance_combos(
+        &self,
+        gid: i64,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError>;
+
+    // (user_id, score) of every member just before `time`, in the same order as `ranking`.
+    async fn scores_at(
+        &self,
+        gid: i64,
+        include_departed: bool,
+        time: i64,
+    ) -> Result<Vec<(i64, i64)>, NalgangError>;
+
+    async fn day_boundary(&self, gid: i64) -> Result<DayBoundary, NalgangError>;
+
+    async fn set_day_boundary(&self, gid: i64, boundary: &DayBoundary) -> Result<(), NalgangError>;
+
+    async fn hide_departed(&self, gid: i64) -> Result<bool, NalgangError>;
+
+    async fn set_hide_departed(&self, gid: i64, hide_departed: bool) -> Result<(), NalgangError>;
+
+    async fn summary_channel(&self, gid: i64) -> Result<Option<i64>, NalgangError>;
+
+    async fn set_summary_channel(
+        &self,
+        gid: i64,
+        channel_id: Option<i64>,
+    ) -> Result<(), NalgangError>;
+
+    // Adds the reminder of a member, replacing the existing one.
+    async fn set_reminder(
+        &self,
+        member: &NalgangMember,
+        remind_time: RemindTime,
+        delivery: ReminderDelivery,
+    ) -> Result<(), NalgangError>;
+
+    // Returns false if the member had no reminder.
+    async fn delete_reminder(&self, member: &NalgangMember) -> Result<bool, NalgangError>;
+
+    // Reminders of the members who are still in the guild
+    async fn reminders(&self, gid: i64) -> Result<Vec<Reminder>, NalgangError>;
+
+    async fn mark_reminded(&self, gid: i64, uid: i64, time: i64) -> Result<(), NalgangError>;
+
+    async fn reminder_setting(&self, gid: i64) -> Result<ReminderSetting, NalgangError>;
+
+    async fn set_reminder_setting(
+        &self,
+        gid: i64,
+        setting: &ReminderSetting,
+    ) -> Result<(), NalgangError>;
+
+    // Role rewards of the guild ordered by kind and threshold
+    async fn role_rewards(&self, gid: i64) -> Result<Vec<RoleReward>, NalgangError>;
+
+    // Adds the reward, replacing the one of the same role.
+    async fn set_role_reward(&self, gid: i64, reward: &RoleReward) -> Result<(), NalgangError>;
+
+    // Returns false if the role was not a reward.
+    async fn delete_role_reward(&self, gid: i64, role_id: i64) -> Result<bool, NalgangError>;
+
+    async fn scoring_rule(&self, gid: i64) -> Result<ScoringRule, NalgangError>;
+
+    async fn set_scoring_rule(&self, gid: i64, rule: &ScoringRule) -> Result<(), NalgangError>;
+}
+
+// Every storage runs the same tests. PostgreSQL tests run in a new schema of the database at
+// TEST_POSTGRES_URL, and are skipped when it is not set.
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Storage;
+    use crate::archive::{ArchivedSettings, ConflictPolicy, GuildArchive, ARCHIVE_VERSION};
+    use crate::audit::{AdjustField, Adjustment};
+    use crate::engine::GuildAttendance;
+    use crate::period::RankingWindow;
+    use crate::reminder::{RemindTime, ReminderDelivery, ReminderSetting};
+    use crate::role_reward::{RewardKind, RoleReward};
+    use crate::scoring::{ComboMilestone, ScoringRule};
+    use crate::shop::ShopItem;
+    use crate::timezone::DayBoundary;
+    use crate::{
+        NalgangErrorInner, NalgangMember, PostgresStorage, RankingFilter, RankingKind,
+        SqliteStorage,
+    };
+
+    // Monday 09:00 in Asia/Seoul, 3 hours after the default day start
+    const T0: i64 = 1704067200;
+    const DAY: i64 = 86400;
+    const GID: i64 = 1;
+
+    fn member(uid: i64) -> NalgangMember {
+        NalgangMember::new(uid, GID)
+    }
+
+    fn filter(kind: RankingKind, window: Option<RankingWindow>) -> RankingFilter {
+        RankingFilter {
+            gid: GID,
+            kind,
+            window,
+            include_departed: true,
+            // Today is the day after T0.
+            alive_since: T0 - 3 * 3600,
+            day_end: T0 + 2 * DAY - 3 * 3600,
+        }
+    }
+
+    async fn sqlite_storage() -> SqliteStorage {
+        // A single connection, since every connection opens its own in-memory database.
+        let database = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(sqlx::sqlite::SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        let storage = SqliteStorage::new(database);
+        storage.migrate().await.unwrap();
+        storage
+    }
+
+    async fn postgres_storage() -> Option<(PostgresStorage, String)> {
+        let url = std::env::var("TEST_POSTGRES_URL").ok()?;
+        let schema = format!("nalgang_test_{}", rand::random::<u32>());
+        let database = sqlx::postgres::PgPool::connect(&url).await.unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {}", schema))
+            .execute(&database)
+            .await
+            .unwrap();
+
+        let options = sqlx::postgres::PgConnectOptions::from_str(&url)
+            .unwrap()
+            .options([("search_path", &schema)]);
+        let database = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await
+            .unwrap();
+        let storage = PostgresStorage::new(database);
+        storage.migrate().await.unwrap();
+        Some((storage, schema))
+    }
+
+    macro_rules! storage_tests {
+        ($($name: ident),*) => {
+            mod sqlite {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        super::$name(&super::sqlite_storage().await).await;
+                    }
+                )*
+            }
+
+            mod postgres {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        let (storage, schema) = match super::postgres_storage().await {
+                            Some(storage) => storage,
+                            None => return println!("TEST_POSTGRES_URL is not set, skipped"),
+                        };
+                        super::$name(&storage).await;
+                        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema))
+                            .execute(&storage.database)
+                            .await
+                            .unwrap();
+                    }
+                )*
+            }
+        };
+    }
+
+    storage_tests!(
+        registration,
+        attendance,
+        day_reset,
+        tokens,
+        ranking,
+        transfer_and_scores,
+        shop,
+        adjustment,
+        settings,
+        archive,
+        recorded_combos
+    );
+
+    // Members attending at once, each on its own connection of the pool
+    const CONCURRENT_MEMBERS: i64 = 8;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_attendance_on_sqlite() {
+        let path =
+            std::env::temp_dir().join(format!("nalgang_test_{}.sqlite", rand::random::<u32>()));
+        let options =
+            sqlx::sqlite::SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+                .unwrap()
+                .create_if_missing(true);
+        let database = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(CONCURRENT_MEMBERS as u32)
+            .connect_with(options)
+            .await
+            .unwrap();
+        let storage = SqliteStorage::new(database);
+        storage.migrate().await.unwrap();
+        concurrent_attendance(&storage).await;
+        storage.database.close().await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_attendance_on_postgres() {
+        let (storage, schema) = match postgres_storage().await {
+            Some(storage) => storage,
+            None => return println!("TEST_POSTGRES_URL is not set, skipped"),
+        };
+        concurrent_attendance(&storage).await;
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema))
+            .execute(&storage.database)
+            .await
+            .unwrap();
+    }
+
+    // Every member attends at once, and each rank must be given exactly once.
+    async fn concurrent_attendance<S: Storage + Clone + 'static>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        for uid in 0..CONCURRENT_MEMBERS {
+            storage.register_member(GID, uid).await.ok().unwrap();
+        }
+
+        let tasks: Vec<_> = (0..CONCURRENT_MEMBERS)
+            .map(|uid| {
+                let storage = storage.clone();
+                tokio::spawn(async move {
+                    storage
+                        .attend(&mut member(uid), "", T0 + uid)
+                        .await
+                        .ok()
+                        .unwrap()
+                        .rank
+                })
+            })
+            .collect();
+        let mut ranks = Vec::new();
+        for task in tasks {
+            ranks.push(task.await.unwrap());
+        }
+        ranks.sort();
+        assert_eq!(ranks, (0..CONCURRENT_MEMBERS).collect::<Vec<_>>());
+    }
+
+    async fn registration<S: Storage>(storage: &S) {
+        let e = storage.register_member(GID, 10).await.err().unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::GuildNotExist));
+
+        storage.register_guild(GID).await.ok().unwrap();
+        let e = storage.register_guild(GID).await.err().unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::DuplicateGuildRegister));
+        assert_eq!(storage.guilds().await.ok().unwrap(), vec![GID]);
+
+        storage.register_member(GID, 10).await.ok().unwrap();
+        let e = storage.register_member(GID, 10).await.err().unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::DuplicateMemberRegister));
+
+        let mut registered = member(10);
+        assert!(storage.member_info(&mut registered).await.ok().unwrap());
+        assert_eq!(
+            (registered.score, registered.combo, registered.freezes),
+            (Some(0), Some(0), Some(0))
+        );
+        assert!(!storage.member_info(&mut member(11)).await.ok().unwrap());
+        assert_eq!(storage.members(GID).await.ok().unwrap().len(), 1);
+    }
+
+    async fn attendance<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        for uid in [10, 11] {
+            storage.register_member(GID, uid).await.ok().unwrap();
+        }
+
+        let mut first = member(10);
+        let attendance = storage.attend(&mut first, "first", T0).await.ok().unwrap();
+        assert_eq!((attendance.rank, attendance.combo), (0, 1));
+        assert_eq!(first.score, Some(attendance.earned_point));
+        let e = storage
+            .attend(&mut member(10), "again", T0 + 1)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::DuplicateAttendance));
+        let e = storage
+            .attend(&mut member(12), "", T0 + 1)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::MemberNotExist));
+
+        let attendance = storage
+            .attend(&mut member(11), "second", T0 + 2)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(attendance.rank, 1);
+        let attendance = storage
+            .attend(&mut first, "next day", T0 + DAY)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!((attendance.rank, attendance.combo), (0, 2));
+
+        let today = storage
+            .daily_attendance(GID, T0 + DAY - 3600)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(today.len(), 1);
+        assert_eq!(today[0].hit_message.as_deref(), Some("next day"));
+
+        let history = storage
+            .attendance_history(&first, 0, 10)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| entry.hit_time)
+                .collect::<Vec<_>>(),
+            vec![T0 + DAY, T0]
+        );
+        assert_eq!(history[0].hit_score, first.score.unwrap());
+        assert_eq!(storage.attendance_count(&first).await.ok().unwrap(), 2);
+        let since = storage
+            .attendance_history_since(&first, T0 + 1)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(since.len(), 1);
+        let combos = storage
+            .attendance_combos(GID, T0, T0 + DAY)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(combos, vec![(10, 1), (11, 1)]);
+
+        // The first attendance earns its achievement once.
+        let achievements = storage.achievements(&first).await.ok().unwrap();
+        assert!(!achievements.is_empty());
+        let granted = storage
+            .grant_achievements(&first, &achievements)
+            .await
+            .ok()
+            .unwrap();
+        assert!(granted.is_empty());
+    }
+
+    async fn day_reset<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        storage.register_member(GID, 10).await.ok().unwrap();
+        storage
+            .attend(&mut member(10), "hi", T0)
+            .await
+            .ok()
+            .unwrap();
+
+        let day_start = T0 + DAY - 3 * 3600;
+        let cleared = storage
+            .reset_day(GID, day_start, T0 - DAY)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(cleared.unwrap().len(), 1);
+        assert!(storage
+            .reset_day(GID, day_start, T0 - DAY)
+            .await
+            .ok()
+            .unwrap()
+            .is_none());
+        assert!(storage
+            .daily_attendance(GID, 0)
+            .await
+            .ok()
+            .unwrap()
+            .is_empty());
+    }
+
+    async fn tokens<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        storage.register_member(GID, 10).await.ok().unwrap();
+
+        assert!(storage
+            .insert_token(&member(10), "token")
+            .await
+            .ok()
+            .unwrap());
+        assert!(!storage
+            .insert_token(&member(10), "other")
+            .await
+            .ok()
+            .unwrap());
+        let owner = storage.token_owner("token").await.ok().unwrap().unwrap();
+        assert_eq!((owner.gid, owner.uid), (GID, 10));
+        assert!(storage.token_owner("other").await.ok().unwrap().is_none());
+        assert!(storage.delete_token(&member(10)).await.ok().unwrap());
+        assert!(!storage.delete_token(&member(10)).await.ok().unwrap());
+    }
+
+    async fn ranking<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        for uid in [10, 11, 12] {
+            storage.register_member(GID, uid).await.ok().unwrap();
+        }
+        storage.attend(&mut member(11), "", T0).await.ok().unwrap();
+        storage
+            .attend(&mut member(10), "", T0 + 1)
+            .await
+            .ok()
+            .unwrap();
+        storage
+            .attend(&mut member(10), "", T0 + DAY)
+            .await
+            .ok()
+            .unwrap();
+        storage
+            .update_profile(&member(12), "departed", true)
+            .await
+            .ok()
+            .unwrap();
+
+        let ranking = storage.ranking(GID).await.ok().unwrap();
+        assert_eq!(
+            ranking
+                .iter()
+                .map(|entry| entry.user_id)
+                .collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+        assert!(ranking[2].departed);
+        assert_eq!(ranking[2].display_name.as_deref(), Some("departed"));
+
+        let score = filter(RankingKind::Score, None);
+        let page = storage.ranking_page(&score, 1, 5).await.ok().unwrap();
+        assert_eq!(
+            page.iter().map(|entry| entry.user_id).collect::<Vec<_>>(),
+            vec![11, 12]
+        );
+        assert_eq!(storage.ranked_count(&score).await.ok().unwrap(), 3);
+        assert_eq!(storage.member_rank(&score, 11).await.ok().unwrap(), Some(1));
+        let hidden = RankingFilter {
+            include_departed: false,
+            ..filter(RankingKind::Score, None)
+        };
+        assert_eq!(storage.ranked_count(&hidden).await.ok().unwrap(), 2);
+
+        // Rankings over the attendance history
+        let window = RankingWindow {
+            since: T0 - 3 * 3600,
+            until: T0 + DAY - 3 * 3600,
+            days: 1,
+        };
+        let windowed = filter(RankingKind::Score, Some(window));
+        assert_eq!(storage.ranked_count(&windowed).await.ok().unwrap(), 2);
+        let first_place = filter(RankingKind::FirstPlace, None);
+        let page = storage.ranking_page(&first_place, 0, 5).await.ok().unwrap();
+        assert_eq!(
+            page.iter()
+                .map(|entry| (entry.user_id, entry.score))
+                .collect::<Vec<_>>(),
+            vec![(10, 1), (11, 1)]
+        );
+        let average_rank = filter(RankingKind::AverageRank, None);
+        assert_eq!(
+            storage.member_rank(&average_rank, 10).await.ok().unwrap(),
+            Some(1)
+        );
+        let rate = filter(RankingKind::AttendanceRate, None);
+        let page = storage.ranking_page(&rate, 0, 5).await.ok().unwrap();
+        assert_eq!(page[0].score, 10000);
+        let max_combo = filter(RankingKind::MaxCombo, None);
+        assert_eq!(
+            storage.member_rank(&max_combo, 12).await.ok().unwrap(),
+            None
+        );
+        let combo = filter(RankingKind::Combo, None);
+        assert_eq!(storage.ranked_count(&combo).await.ok().unwrap(), 2);
+    }
+
+    async fn transfer_and_scores<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        for uid in [10, 11] {
+            storage.register_member(GID, uid).await.ok().unwrap();
+        }
+        let mut sender = member(10);
+        storage.attend(&mut sender, "", T0).await.ok().unwrap();
+        let earned = sender.score.unwrap();
+
+        let e = storage
+            .transfer(&mut member(10), &mut member(11), earned + 1, T0 + 10)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::InsufficientScore));
+        let e = storage
+            .transfer(&mut member(10), &mut member(12), 1, T0 + 10)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::ReceiverNotExist));
+
+        let mut receiver = member(11);
+        storage
+            .transfer(&mut sender, &mut receiver, 3, T0 + 10)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!((sender.score, receiver.score), (Some(earned - 3), Some(3)));
+
+        assert_eq!(
+            storage.scores_at(GID, true, T0 + 5).await.ok().unwrap(),
+            vec![(10, earned), (11, 0)]
+        );
+        assert_eq!(
+            storage.scores_at(GID, true, T0 + 11).await.ok().unwrap(),
+            vec![(10, earned - 3), (11, 3)]
+        );
+    }
+
+    async fn shop<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        storage.register_member(GID, 10).await.ok().unwrap();
+        storage.attend(&mut member(10), "", T0).await.ok().unwrap();
+
+        let item = ShopItem {
+            name: "배지".to_string(),
+            price: 2,
+            stock: Some(1),
+            role_id: Some(100),
+            duration: Some(1),
+        };
+        storage.set_shop_item(GID, &item).await.ok().unwrap();
+        let free = ShopItem {
+            name: "free".to_string(),
+            price: 0,
+            stock: None,
+            role_id: None,
+            duration: None,
+        };
+        storage.set_shop_item(GID, &free).await.ok().unwrap();
+        let items = storage.shop_items(GID).await.ok().unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["free", "배지"]
+        );
+
+        let mut buyer = member(10);
+        let before = {
+            storage.member_info(&mut buyer).await.ok().unwrap();
+            buyer.score.unwrap()
+        };
+        let bought = storage.buy_item(&mut buyer, "배지", T0).await.ok().unwrap();
+        assert_eq!(buyer.score, Some(before - 2));
+        assert_eq!(bought.expire_time, Some(T0 + DAY));
+        let e = storage
+            .buy_item(&mut buyer, "배지", T0)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::OutOfStock));
+        let e = storage
+            .buy_item(&mut buyer, "none", T0)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::ShopItemNotExist));
+
+        let free_purchase = storage.buy_item(&mut buyer, "free", T0).await.ok().unwrap();
+        assert!(storage
+            .cancel_purchase(&mut buyer, free_purchase.purchase_id)
+            .await
+            .ok()
+            .unwrap());
+        assert!(!storage
+            .cancel_purchase(&mut buyer, free_purchase.purchase_id)
+            .await
+            .ok()
+            .unwrap());
+
+        assert_eq!(storage.inventory(&buyer, T0).await.ok().unwrap().len(), 1);
+        let expired = storage.expire_items(GID, T0 + DAY).await.ok().unwrap();
+        assert_eq!(
+            expired
+                .iter()
+                .map(|role| (role.user_id, role.role_id))
+                .collect::<Vec<_>>(),
+            vec![(10, 100)]
+        );
+        assert!(storage
+            .expire_items(GID, T0 + DAY)
+            .await
+            .ok()
+            .unwrap()
+            .is_empty());
+        assert!(storage.inventory(&buyer, T0).await.ok().unwrap().is_empty());
+
+        // Cancelling restocks the item the purchase was sold from, but not a redefined one.
+        let mut limited = ShopItem {
+            name: "한정".to_string(),
+            price: 0,
+            stock: Some(1),
+            role_id: None,
+            duration: None,
+        };
+        storage.set_shop_item(GID, &limited).await.ok().unwrap();
+        for restocked in [true, false] {
+            let purchase = storage.buy_item(&mut buyer, "한정", T0).await.ok().unwrap();
+            if !restocked {
+                limited.stock = Some(5);
+                storage.set_shop_item(GID, &limited).await.ok().unwrap();
+            }
+            assert!(storage
+                .cancel_purchase(&mut buyer, purchase.purchase_id)
+                .await
+                .ok()
+                .unwrap());
+            let items = storage.shop_items(GID).await.ok().unwrap();
+            let stock = items.iter().find(|item| item.name == "한정").unwrap().stock;
+            assert_eq!(stock, Some(if restocked { 1 } else { 5 }));
+        }
+
+        assert!(storage.delete_shop_item(GID, "free").await.ok().unwrap());
+        assert!(!storage.delete_shop_item(GID, "free").await.ok().unwrap());
+    }
+
+    async fn adjustment<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        storage.register_member(GID, 10).await.ok().unwrap();
+
+        let mut target = member(10);
+        let entry = storage
+            .adjust_member(
+                1,
+                &mut target,
+                AdjustField::Score,
+                Adjustment::Add(50),
+                "bug",
+                T0,
+            )
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!((entry.before, entry.after, target.score), (0, 50, Some(50)));
+        let e = storage
+            .adjust_member(
+                1,
+                &mut target,
+                AdjustField::Combo,
+                Adjustment::Set(-1),
+                "x",
+                T0,
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::InvalidAdjustment));
+        let e = storage
+            .adjust_member(
+                1,
+                &mut target,
+                AdjustField::Score,
+                Adjustment::Add(i64::MAX),
+                "x",
+                T0,
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::InvalidAdjustment));
+        storage
+            .adjust_member(
+                1,
+                &mut target,
+                AdjustField::Combo,
+                Adjustment::Set(3),
+                "x",
+                T0 + 1,
+            )
+            .await
+            .ok()
+            .unwrap();
+
+        let log = storage.audit_log(GID, 10).await.ok().unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log[0].audit_id > log[1].audit_id);
+        assert_eq!(log[1].reason, "bug");
+        assert_eq!(
+            storage.scores_at(GID, true, T0 + 1).await.ok().unwrap(),
+            vec![(10, 50)]
+        );
+    }
+
+    async fn settings<S: Storage>(storage: &S) {
+        let e = storage.set_hide_departed(GID, true).await.err().unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::GuildNotExist));
+
+        storage.register_guild(GID).await.ok().unwrap();
+        storage.register_member(GID, 10).await.ok().unwrap();
+
+        let boundary = DayBoundary {
+            timezone: "UTC".parse().unwrap(),
+            day_start_hour: 0,
+        };
+        storage.set_day_boundary(GID, &boundary).await.ok().unwrap();
+        assert_eq!(
+            storage.day_boundary(GID).await.ok().unwrap().day_start_hour,
+            0
+        );
+
+        assert!(!storage.hide_departed(GID).await.ok().unwrap());
+        storage.set_hide_departed(GID, true).await.ok().unwrap();
+        assert!(storage.hide_departed(GID).await.ok().unwrap());
+
+        assert_eq!(storage.summary_channel(GID).await.ok().unwrap(), None);
+        storage
+            .set_summary_channel(GID, Some(5))
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(storage.summary_channel(GID).await.ok().unwrap(), Some(5));
+
+        let setting = ReminderSetting {
+            enabled: true,
+            channel_id: Some(6),
+            min_combo: 2,
+        };
+        storage
+            .set_reminder_setting(GID, &setting)
+            .await
+            .ok()
+            .unwrap();
+        let stored = storage.reminder_setting(GID).await.ok().unwrap();
+        assert_eq!(
+            (stored.enabled, stored.channel_id, stored.min_combo),
+            (true, Some(6), 2)
+        );
+
+        let remind_time = RemindTime::from_minutes(21 * 60).unwrap();
+        storage
+            .set_reminder(&member(10), remind_time, ReminderDelivery::DirectMessage)
+            .await
+            .ok()
+            .unwrap();
+        storage.mark_reminded(GID, 10, T0).await.ok().unwrap();
+        let reminders = storage.reminders(GID).await.ok().unwrap();
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].last_reminded, T0);
+        assert!(storage.delete_reminder(&member(10)).await.ok().unwrap());
+
+        let reward = RoleReward {
+            role_id: 7,
+            kind: RewardKind::Combo,
+            threshold: 3,
+            revoke: true,
+        };
+        storage.set_role_reward(GID, &reward).await.ok().unwrap();
+        let rewards = storage.role_rewards(GID).await.ok().unwrap();
+        assert_eq!(rewards.len(), 1);
+        assert!(rewards[0].revoke);
+        assert!(storage.delete_role_reward(GID, 7).await.ok().unwrap());
+
+        let mut rule = ScoringRule {
+            rank_points: "7,3".parse().unwrap(),
+            ..Default::default()
+        };
+        // Bonuses are read back ordered by combo.
+        rule.combo_bonuses.clear();
+        rule.set_combo_bonus(ComboMilestone::Exact(5), 40);
+        rule.set_combo_bonus(ComboMilestone::Every(30), 100);
+        storage.set_scoring_rule(GID, &rule).await.ok().unwrap();
+        assert_eq!(
+            storage.scoring_rule(GID).await.ok().unwrap().to_string(),
+            rule.to_string()
+        );
+    }
+
+    async fn archive<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        for uid in [10, 11] {
+            storage.register_member(GID, uid).await.ok().unwrap();
+        }
+        let mut first = member(10);
+        storage
+            .attend(&mut first, "a, \"b\"", T0)
+            .await
+            .ok()
+            .unwrap();
+        storage
+            .update_profile(&first, "first", true)
+            .await
+            .ok()
+            .unwrap();
+
+        let (members, history) = storage.archive_members(GID).await.ok().unwrap();
+        assert_eq!(
+            members.iter().map(|m| m.user_id).collect::<Vec<_>>(),
+            vec![10, 11]
+        );
+        assert_eq!(members[0].display_name.as_deref(), Some("first"));
+        assert!(members[0].departed);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].hit_message.as_deref(), Some("a, \"b\""));
+        let archive = GuildArchive {
+            version: ARCHIVE_VERSION,
+            guild_id: GID,
+            exported_time: T0,
+            settings: ArchivedSettings::new(
+                &DayBoundary::default(),
+                false,
+                None,
+                &ReminderSetting::default(),
+                &ScoringRule::default(),
+            ),
+            members,
+            history,
+        };
+
+        // Into a new guild, then again with each policy
+        let other = GID + 1;
+        let score = first.score.unwrap();
+        let imported = || NalgangMember::new(10, other);
+        let report = storage
+            .import_members(other, &archive, ConflictPolicy::Skip, T0)
+            .await
+            .ok()
+            .unwrap();
+        assert!(report.guild_created);
+        assert_eq!((report.added, report.history_added), (2, 1));
+        let mut restored = imported();
+        assert!(storage.member_info(&mut restored).await.ok().unwrap());
+        assert_eq!((restored.score, restored.combo), (Some(score), Some(1)));
+        assert!(storage.guilds().await.ok().unwrap().contains(&other));
+
+        let report = storage
+            .import_members(other, &archive, ConflictPolicy::Skip, T0)
+            .await
+            .ok()
+            .unwrap();
+        assert!(!report.guild_created);
+        assert_eq!((report.skipped, report.history_added), (2, 0));
+
+        let report = storage
+            .import_members(other, &archive, ConflictPolicy::MergeScores, T0)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!((report.merged, report.history_added), (2, 0));
+        let mut merged = imported();
+        storage.member_info(&mut merged).await.ok().unwrap();
+        assert_eq!((merged.score, merged.combo), (Some(score * 2), Some(1)));
+
+        let report = storage
+            .import_members(other, &archive, ConflictPolicy::Overwrite, T0)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!((report.overwritten, report.history_added), (2, 1));
+        let mut overwritten = imported();
+        storage.member_info(&mut overwritten).await.ok().unwrap();
+        assert_eq!(overwritten.score, Some(score));
+        assert_eq!(
+            storage.attendance_count(&overwritten).await.ok().unwrap(),
+            1
+        );
+
+        // An earlier attendance does not replace the one of the guild.
+        for hit_time in [T0 + 5, T0] {
+            let attendance = GuildAttendance {
+                hit_count: 1,
+                hit_time,
+            };
+            storage
+                .restore_guild_attendance(other, &attendance)
+                .await
+                .ok()
+                .unwrap();
+        }
+        let mut second = NalgangMember::new(11, other);
+        let attendance = storage.attend(&mut second, "", T0 + 10).await.ok().unwrap();
+        assert_eq!(attendance.rank, 2);
+    }
+
+    async fn recorded_combos<S: Storage>(storage: &S) {
+        storage.register_guild(GID).await.ok().unwrap();
+        for uid in [10, 11, 12] {
+            storage.register_member(GID, uid).await.ok().unwrap();
+        }
+        storage.attend(&mut member(10), "", T0).await.ok().unwrap();
+        storage
+            .attend(&mut member(11), "", T0 + 1)
+            .await
+            .ok()
+            .unwrap();
+        storage
+            .attend(&mut member(10), "", T0 + DAY)
+            .await
+            .ok()
+            .unwrap();
+        storage
+            .adjust_member(
+                1,
+                &mut member(11),
+                AdjustField::Combo,
+                Adjustment::Set(5),
+                "x",
+                T0 + 1,
+            )
+            .await
+            .ok()
+            .unwrap();
+        let recorded = vec![(10, 2, T0 + DAY), (11, 5, T0 + 1), (12, 0, 0)];
+        assert_eq!(storage.recorded_combos(GID).await.ok().unwrap(), recorded);
+
+        let mut members = storage.members(GID).await.ok().unwrap();
+        members.sort_by_key(|member| member.uid);
+        members.remove(1);
+        for member in members.iter_mut() {
+            member.update_data(7, 7, 7);
+        }
+        storage.update_members(&members).await.ok().unwrap();
+        let mut last = member(12);
+        storage.member_info(&mut last).await.ok().unwrap();
+        assert_eq!(
+            (last.score, last.combo, last.hit_time),
+            (Some(7), Some(7), Some(7))
+        );
+        // The combo change now comes before the last attendance.
+        storage
+            .attend(&mut member(11), "", T0 + DAY + 1)
+            .await
+            .ok()
+            .unwrap();
+        let combos = storage.recorded_combos(GID).await.ok().unwrap();
+        assert_eq!(combos[1], (11, 6, T0 + DAY + 1));
+    }
+}
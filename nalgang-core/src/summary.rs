@@ -0,0 +1,195 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use crate::scoring::ScoringRule;
+use crate::{AttendanceEntry, DayRollover};
+
+// Lost combos shorter than this are not worth mentioning.
+pub const BROKEN_STREAK_MIN_COMBO: i64 = 2;
+// Rank changes are reported within this many ranks from the top after the day.
+pub const RANK_CHANGE_TOP: usize = 10;
+
+// Member whose streak the day broke
+pub struct StreakBreak {
+    pub user_id: i64,
+    pub combo: i64,
+}
+
+// Attendance of the day which reached a combo bonus
+pub struct ComboReward {
+    pub user_id: i64,
+    pub combo: i64,
+    pub bonus: i64,
+}
+
+// Score ranks starting from 0. `before` is None for members who were not ranked yet.
+pub struct RankChange {
+    pub user_id: i64,
+    pub before: Option<i64>,
+    pub after: i64,
+}
+
+// Recap of an attendance day, posted when the next day starts
+pub struct DailySummary {
+    pub gid: i64,
+    pub date: NaiveDate,
+    pub attendances: Vec<AttendanceEntry>,
+    pub combo_rewards: Vec<ComboReward>,
+    pub broken_streaks: Vec<StreakBreak>,
+    pub rank_changes: Vec<RankChange>,
+}
+
+impl DailySummary {
+    // `ending_combos` are (user_id, combo) of the members whose streak was alive when the day
+    // started and is not when it ends, and `combos` those of the attendances of the day.
+    // `scores_before` and `scores_after` are (user_id, score) in ranking order.
+    pub fn new(
+        rollover: DayRollover,
+        ending_combos: &[(i64, i64)],
+        combos: &[(i64, i64)],
+        scores_before: &[(i64, i64)],
+        scores_after: &[(i64, i64)],
+        rule: &ScoringRule,
+    ) -> Self {
+        let combo_rewards = combos
+            .iter()
+            .map(|(user_id, combo)| ComboReward {
+                user_id: *user_id,
+                combo: *combo,
+                bonus: rule.combo_bonus(*combo),
+            })
+            .filter(|reward| reward.bonus > 0)
+            .collect();
+
+        let mut broken_streaks: Vec<StreakBreak> = ending_combos
+            .iter()
+            .filter(|(user_id, combo)| {
+                *combo >= BROKEN_STREAK_MIN_COMBO && !combos.iter().any(|(uid, _)| uid == user_id)
+            })
+            .map(|(user_id, combo)| StreakBreak {
+                user_id: *user_id,
+                combo: *combo,
+            })
+            .collect();
+        broken_streaks.sort_by_key(|streak| Reverse(streak.combo));
+
+        let ranks_before: HashMap<i64, i64> = scores_before
+            .iter()
+            .enumerate()
+            .map(|(rank, (user_id, _))| (*user_id, rank as i64))
+            .collect();
+        let rank_changes = scores_after
+            .iter()
+            .take(RANK_CHANGE_TOP)
+            .enumerate()
+            .map(|(rank, (user_id, _))| RankChange {
+                user_id: *user_id,
+                before: ranks_before.get(user_id).copied(),
+                after: rank as i64,
+            })
+            .filter(|change| change.before != Some(change.after))
+            .collect();
+
+        DailySummary {
+            gid: rollover.gid,
+            date: rollover.date,
+            attendances: rollover.entries,
+            combo_rewards,
+            broken_streaks,
+            rank_changes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attendances.is_empty()
+            && self.combo_rewards.is_empty()
+            && self.broken_streaks.is_empty()
+            && self.rank_changes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rollover() -> DayRollover {
+        DayRollover {
+            gid: 1,
+            date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            entries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn combo_rewards() {
+        let summary = DailySummary::new(
+            rollover(),
+            &[],
+            &[(1, 7), (2, 6), (3, 30), (4, 365)],
+            &[],
+            &[],
+            &ScoringRule::default(),
+        );
+        let rewards: Vec<(i64, i64)> = summary
+            .combo_rewards
+            .iter()
+            .map(|reward| (reward.user_id, reward.bonus))
+            .collect();
+        assert_eq!(rewards, vec![(1, 20), (3, 100), (4, 1500)]);
+    }
+
+    #[test]
+    fn broken_streaks() {
+        let summary = DailySummary::new(
+            rollover(),
+            &[(1, 3), (2, 12), (3, 1), (4, 5)],
+            &[(4, 6)],
+            &[],
+            &[],
+            &ScoringRule::default(),
+        );
+        let broken: Vec<(i64, i64)> = summary
+            .broken_streaks
+            .iter()
+            .map(|streak| (streak.user_id, streak.combo))
+            .collect();
+        assert_eq!(broken, vec![(2, 12), (1, 3)]);
+    }
+
+    #[test]
+    fn rank_changes() {
+        let summary = DailySummary::new(
+            rollover(),
+            &[],
+            &[],
+            &[(1, 30), (2, 20), (3, 10)],
+            &[(2, 35), (1, 30), (3, 13), (4, 10)],
+            &ScoringRule::default(),
+        );
+        let changes: Vec<(i64, Option<i64>, i64)> = summary
+            .rank_changes
+            .iter()
+            .map(|change| (change.user_id, change.before, change.after))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![(2, Some(1), 0), (1, Some(0), 1), (4, None, 3)]
+        );
+        assert!(!summary.is_empty());
+    }
+
+    #[test]
+    fn empty_day() {
+        let summary = DailySummary::new(
+            rollover(),
+            &[],
+            &[],
+            &[(1, 30)],
+            &[(1, 30)],
+            &ScoringRule::default(),
+        );
+        assert!(summary.is_empty());
+    }
+}
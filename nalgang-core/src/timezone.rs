@@ -0,0 +1,354 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{
+    FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+pub const DEFAULT_TIMEZONE: &str = "Asia/Seoul";
+pub const DEFAULT_DAY_START_HOUR: u32 = 6;
+// End of the year 9999. Times up to it are safe to convert to dates in any timezone.
+pub const MAX_TIMESTAMP: i64 = 253402300799;
+
+// Timezone of a guild, either an IANA zone name or a fixed UTC offset.
+#[derive(Clone, Copy)]
+pub enum GuildTimezone {
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl FromStr for GuildTimezone {
+    type Err = ();
+
+    // Accepts IANA zone names such as "Asia/Seoul" and offsets such as "+09:00" or "-5".
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim();
+        if let Ok(tz) = s.parse::<Tz>() {
+            return Ok(GuildTimezone::Named(tz));
+        }
+
+        let (sign, rest) = match s.strip_prefix("UTC").unwrap_or(s) {
+            r if r.starts_with('+') => (1, &r[1..]),
+            r if r.starts_with('-') => (-1, &r[1..]),
+            _ => return Err(()),
+        };
+        let (hours, minutes) = match rest.split_once(':') {
+            Some((h, m)) => (h, m),
+            None => (rest, "0"),
+        };
+        let hours: i32 = hours.parse().map_err(|_| ())?;
+        let minutes: i32 = minutes.parse().map_err(|_| ())?;
+        if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+            return Err(());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(GuildTimezone::Fixed)
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for GuildTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuildTimezone::Named(tz) => write!(f, "{}", tz.name()),
+            GuildTimezone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+impl GuildTimezone {
+    fn local_time(&self, utc_time: i64) -> NaiveDateTime {
+        let utc = Utc.timestamp_opt(utc_time, 0).unwrap().naive_utc();
+        match self {
+            GuildTimezone::Named(tz) => utc + tz.offset_from_utc_datetime(&utc).fix(),
+            GuildTimezone::Fixed(offset) => utc + *offset,
+        }
+    }
+
+    fn timestamp_of_local(&self, local: &NaiveDateTime) -> LocalResult<i64> {
+        match self {
+            GuildTimezone::Named(tz) => tz.from_local_datetime(local).map(|t| t.timestamp()),
+            GuildTimezone::Fixed(offset) => {
+                offset.from_local_datetime(local).map(|t| t.timestamp())
+            }
+        }
+    }
+}
+
+// Where a guild's attendance day starts.
+#[derive(Clone, Copy)]
+pub struct DayBoundary {
+    pub timezone: GuildTimezone,
+    pub day_start_hour: u32,
+}
+
+impl Default for DayBoundary {
+    fn default() -> Self {
+        DayBoundary {
+            timezone: DEFAULT_TIMEZONE.parse().unwrap(),
+            day_start_hour: DEFAULT_DAY_START_HOUR,
+        }
+    }
+}
+
+impl DayBoundary {
+    // Local date of the attendance day which contains `utc_time`.
+    pub fn attendance_date(&self, utc_time: i64) -> NaiveDate {
+        let local = self.timezone.local_time(utc_time);
+        if local.time() < self.start_time() {
+            local.date().pred_opt().unwrap()
+        } else {
+            local.date()
+        }
+    }
+
+    // Start of the attendance day of `date` as a unix timestamp.
+    // When the start time is skipped by a DST transition, the day starts at the first existing
+    // local time after it. When it is repeated, the day starts at its first occurrence.
+    pub fn day_start(&self, date: NaiveDate) -> i64 {
+        self.local_timestamp(date.and_time(self.start_time()))
+    }
+
+    // Moment of the local `time` within the attendance day of `date`. Times before the start of
+    // the day belong to the next local date.
+    pub fn time_of_day(&self, date: NaiveDate, time: NaiveTime) -> i64 {
+        if time < self.start_time() {
+            self.local_timestamp(date.succ_opt().unwrap().and_time(time))
+        } else {
+            self.local_timestamp(date.and_time(time))
+        }
+    }
+
+    // Skipped local times move forward to the first existing one, repeated ones take the first.
+    fn local_timestamp(&self, mut local: NaiveDateTime) -> i64 {
+        loop {
+            match self.timezone.timestamp_of_local(&local) {
+                LocalResult::Single(t) => return t,
+                LocalResult::Ambiguous(earliest, _) => return earliest,
+                LocalResult::None => local += chrono::Duration::minutes(15),
+            }
+        }
+    }
+
+    // Start of the attendance day which contains `utc_time`.
+    pub fn round_down(&self, utc_time: i64) -> i64 {
+        self.day_start(self.attendance_date(utc_time))
+    }
+
+    // Start of the attendance day `days` days after the one which contains `utc_time`.
+    pub fn following(&self, utc_time: i64, days: u64) -> i64 {
+        let date = self.attendance_date(utc_time) + chrono::Days::new(days);
+        self.day_start(date)
+    }
+
+    fn start_time(&self) -> NaiveTime {
+        NaiveTime::from_hms_opt(self.day_start_hour, 0, 0).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    // America/New_York skips 02:00-03:00 on 2024-03-10 and repeats 01:00-02:00 on 2024-11-03.
+    fn boundary(timezone: &str, day_start_hour: u32) -> DayBoundary {
+        DayBoundary {
+            timezone: timezone.parse().unwrap(),
+            day_start_hour,
+        }
+    }
+
+    fn utc(s: &str) -> i64 {
+        DateTime::parse_from_rfc3339(s).unwrap().timestamp()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parse_timezones() {
+        assert_eq!(boundary("+09:00", 0).timezone.to_string(), "+09:00");
+        assert_eq!(boundary("UTC-5", 0).timezone.to_string(), "-05:00");
+        assert_eq!(boundary("Asia/Seoul", 0).timezone.to_string(), "Asia/Seoul");
+        for invalid in ["", "Mars/Base", "+15", "+09:60", "9"] {
+            assert!(invalid.parse::<GuildTimezone>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn attendance_date() {
+        let cases = [
+            ("+09:00", 6, "2024-01-01T20:59:59Z", "2024-01-01"),
+            ("+09:00", 6, "2024-01-01T21:00:00Z", "2024-01-02"),
+            ("Asia/Seoul", 0, "2024-01-01T15:00:00Z", "2024-01-02"),
+            ("America/New_York", 2, "2024-03-10T06:59:59Z", "2024-03-09"),
+            ("America/New_York", 2, "2024-03-10T07:00:00Z", "2024-03-10"),
+            ("America/New_York", 1, "2024-11-03T04:59:59Z", "2024-11-02"),
+            ("America/New_York", 1, "2024-11-03T05:30:00Z", "2024-11-03"),
+            ("America/New_York", 1, "2024-11-03T06:30:00Z", "2024-11-03"),
+        ];
+        for (timezone, hour, time, expected) in cases {
+            assert_eq!(
+                boundary(timezone, hour).attendance_date(utc(time)),
+                date(expected),
+                "{} {} {}",
+                timezone,
+                hour,
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn day_start() {
+        let cases = [
+            ("+09:00", 6, "2024-01-02", "2024-01-01T21:00:00Z"),
+            ("-05:30", 0, "2024-01-02", "2024-01-02T05:30:00Z"),
+            // Skipped start time
+            ("America/New_York", 2, "2024-03-10", "2024-03-10T07:00:00Z"),
+            ("America/New_York", 2, "2024-03-11", "2024-03-11T06:00:00Z"),
+            // Repeated start time
+            ("America/New_York", 1, "2024-11-03", "2024-11-03T05:00:00Z"),
+            ("America/New_York", 2, "2024-11-03", "2024-11-03T07:00:00Z"),
+        ];
+        for (timezone, hour, day, expected) in cases {
+            assert_eq!(
+                boundary(timezone, hour).day_start(date(day)),
+                utc(expected),
+                "{} {} {}",
+                timezone,
+                hour,
+                day
+            );
+        }
+    }
+
+    #[test]
+    fn round_down() {
+        let cases = [
+            ("+09:00", 6, "2024-01-01T20:59:59Z", "2023-12-31T21:00:00Z"),
+            ("+09:00", 6, "2024-01-01T21:00:00Z", "2024-01-01T21:00:00Z"),
+            (
+                "America/New_York",
+                2,
+                "2024-03-10T12:00:00Z",
+                "2024-03-10T07:00:00Z",
+            ),
+            (
+                "America/New_York",
+                1,
+                "2024-11-03T06:30:00Z",
+                "2024-11-03T05:00:00Z",
+            ),
+        ];
+        for (timezone, hour, time, expected) in cases {
+            assert_eq!(
+                boundary(timezone, hour).round_down(utc(time)),
+                utc(expected),
+                "{} {} {}",
+                timezone,
+                hour,
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn following() {
+        let cases = [
+            (
+                "+09:00",
+                6,
+                "2024-01-01T21:00:00Z",
+                0,
+                "2024-01-01T21:00:00Z",
+            ),
+            (
+                "+09:00",
+                6,
+                "2024-01-01T20:59:59Z",
+                1,
+                "2024-01-01T21:00:00Z",
+            ),
+            (
+                "America/New_York",
+                2,
+                "2024-03-09T12:00:00Z",
+                1,
+                "2024-03-10T07:00:00Z",
+            ),
+            (
+                "America/New_York",
+                2,
+                "2024-03-09T12:00:00Z",
+                2,
+                "2024-03-11T06:00:00Z",
+            ),
+            (
+                "America/New_York",
+                1,
+                "2024-11-02T12:00:00Z",
+                1,
+                "2024-11-03T05:00:00Z",
+            ),
+            (
+                "America/New_York",
+                1,
+                "2024-11-02T12:00:00Z",
+                2,
+                "2024-11-04T06:00:00Z",
+            ),
+        ];
+        for (timezone, hour, time, days, expected) in cases {
+            assert_eq!(
+                boundary(timezone, hour).following(utc(time), days),
+                utc(expected),
+                "{} {} {} {}",
+                timezone,
+                hour,
+                time,
+                days
+            );
+        }
+    }
+
+    #[test]
+    fn time_of_day() {
+        let cases = [
+            // Before the day start, so on the next local date
+            ("+09:00", 6, "2024-01-01", 5, 0, "2024-01-01T20:00:00Z"),
+            ("+09:00", 6, "2024-01-01", 23, 0, "2024-01-01T14:00:00Z"),
+            (
+                "America/New_York",
+                0,
+                "2024-03-10",
+                2,
+                30,
+                "2024-03-10T07:00:00Z",
+            ),
+            (
+                "America/New_York",
+                0,
+                "2024-11-03",
+                1,
+                30,
+                "2024-11-03T05:30:00Z",
+            ),
+        ];
+        for (timezone, hour, day, time_hour, time_minute, expected) in cases {
+            let time = NaiveTime::from_hms_opt(time_hour, time_minute, 0).unwrap();
+            assert_eq!(
+                boundary(timezone, hour).time_of_day(date(day), time),
+                utc(expected),
+                "{} {} {} {}",
+                timezone,
+                hour,
+                day,
+                time
+            );
+        }
+    }
+}
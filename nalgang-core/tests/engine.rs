@@ -0,0 +1,336 @@
+// Drives the engine through its public API on an in-memory SQLite database.
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use nalgang_core::archive::ConflictPolicy;
+use nalgang_core::reminder::ReminderDelivery;
+use nalgang_core::scoring::ScoringRuleChange;
+use nalgang_core::shop::{ShopItem, MAX_ITEM_DURATION};
+use nalgang_core::{Clock, DayRollover, Nalgang, NalgangErrorInner, NalgangMember, SqliteStorage};
+
+// Monday 09:00 in Asia/Seoul, 3 hours after the default day start
+const T0: i64 = 1704067200;
+const DAY: i64 = 86400;
+const GID: i64 = 1;
+
+#[derive(Clone)]
+struct TestClock(Arc<AtomicI64>);
+
+impl TestClock {
+    fn set(&self, time: i64) {
+        self.0.store(time, Ordering::SeqCst);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> i64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// Engine on a new database with members 10, 11 and 12 registered at T0
+async fn nalgang() -> (Nalgang<SqliteStorage, TestClock>, TestClock) {
+    // A single connection, since every connection opens its own in-memory database.
+    let database = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(sqlx::sqlite::SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+        .await
+        .unwrap();
+    let storage = SqliteStorage::new(database);
+    storage.migrate().await.unwrap();
+
+    let clock = TestClock(Arc::new(AtomicI64::new(T0)));
+    let nalgang = Nalgang::new(storage, clock.clone());
+    nalgang.register_guild(GID).await.ok().unwrap();
+    for uid in [10, 11, 12] {
+        nalgang.register(&mut member(uid)).await.ok().unwrap();
+    }
+    (nalgang, clock)
+}
+
+fn member(uid: i64) -> NalgangMember {
+    NalgangMember::new(uid, GID)
+}
+
+#[tokio::test]
+async fn registration() {
+    let (nalgang, _) = nalgang().await;
+    let e = nalgang.register(&mut member(10)).await.err().unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::DuplicateMemberRegister));
+    let e = nalgang.point(&mut member(13)).await.err().unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::MemberNotExist));
+
+    let mut registered = member(10);
+    nalgang.point(&mut registered).await.ok().unwrap();
+    assert_eq!(
+        (registered.score, registered.combo, registered.hit_time),
+        (Some(0), Some(0), Some(0))
+    );
+}
+
+#[tokio::test]
+async fn attendance_ranks_and_combos() {
+    let (nalgang, clock) = nalgang().await;
+
+    let first = nalgang.attend(&mut member(10), "first").await.ok().unwrap();
+    assert_eq!((first.rank, first.combo), (0, 1));
+    clock.set(T0 + 60);
+    let second = nalgang
+        .attend(&mut member(11), "second")
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!((second.rank, second.combo), (1, 1));
+    let e = nalgang
+        .attend(&mut member(10), "again")
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::DuplicateAttendance));
+
+    // A new day starts at 06:00 in Asia/Seoul, which is 21:00 UTC.
+    clock.set(T0 + 21 * 3600 - 1);
+    let e = nalgang.attend(&mut member(10), "").await.err().unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::DuplicateAttendance));
+    clock.set(T0 + 21 * 3600);
+    let next = nalgang.attend(&mut member(11), "").await.ok().unwrap();
+    assert_eq!((next.rank, next.combo), (0, 2));
+
+    // Member 10 skipped a day, so the combo starts again.
+    clock.set(T0 + 2 * DAY);
+    let broken = nalgang.attend(&mut member(10), "").await.ok().unwrap();
+    assert_eq!((broken.rank, broken.combo), (0, 1));
+
+    let mut attended = member(10);
+    nalgang.point(&mut attended).await.ok().unwrap();
+    assert_eq!(
+        attended.score,
+        Some(first.earned_point + broken.earned_point)
+    );
+    assert_eq!(attended.hit_time, Some(T0 + 2 * DAY));
+}
+
+#[tokio::test]
+async fn transfer() {
+    let (nalgang, _) = nalgang().await;
+    nalgang.attend(&mut member(10), "").await.ok().unwrap();
+
+    let (mut sender, mut receiver) = (member(10), member(11));
+    nalgang
+        .transfer(&mut sender, &mut receiver, 4)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!((sender.score, receiver.score), (Some(6), Some(4)));
+
+    let e = nalgang
+        .transfer(&mut member(10), &mut member(11), 0)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::InvalidTransferAmount));
+    let e = nalgang
+        .transfer(&mut member(10), &mut member(10), 1)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::SelfTransfer));
+    let e = nalgang
+        .transfer(&mut member(10), &mut member(11), 100)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::InsufficientScore));
+    let e = nalgang
+        .transfer(&mut member(10), &mut member(13), 1)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::ReceiverNotExist));
+}
+
+#[tokio::test]
+async fn ranking() {
+    let (nalgang, clock) = nalgang().await;
+    for (offset, uid) in [(0, 12), (1, 10), (2, 11)] {
+        clock.set(T0 + offset);
+        nalgang.attend(&mut member(uid), "").await.ok().unwrap();
+    }
+    let ranking: Vec<(i64, i64)> = nalgang
+        .ranking(GID)
+        .await
+        .ok()
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.user_id, entry.score))
+        .collect();
+    assert_eq!(ranking, vec![(12, 10), (10, 5), (11, 3)]);
+}
+
+#[tokio::test]
+async fn tokens() {
+    let (nalgang, _) = nalgang().await;
+    let token = nalgang.issue_token(&member(10)).await.ok().unwrap();
+    let e = nalgang.issue_token(&member(10)).await.err().unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::DuplidateTokenIssue));
+
+    let owner = nalgang.token_owner(&token).await.ok().unwrap().unwrap();
+    assert_eq!((owner.uid, owner.gid), (10, GID));
+    assert!(nalgang.delete_token(&member(10)).await.ok().unwrap());
+    assert!(nalgang.token_owner(&token).await.ok().unwrap().is_none());
+}
+
+async fn set_freeze_rule(nalgang: &Nalgang<SqliteStorage, TestClock>, price: i64, max: i64) {
+    let change = ScoringRuleChange::Freeze {
+        price: Some(price),
+        every: None,
+        max: Some(max),
+    };
+    nalgang.scoring_rule(GID, change).await.ok().unwrap();
+}
+
+#[tokio::test]
+async fn freezes_keep_streaks_alive() {
+    let (nalgang, clock) = nalgang().await;
+    set_freeze_rule(&nalgang, 0, 2).await;
+    nalgang
+        .reminder_setting(GID, Some(true), None, Some(1))
+        .await
+        .ok()
+        .unwrap();
+    for uid in [10, 11] {
+        for day in 0..3 {
+            clock.set(T0 + day * DAY);
+            nalgang.attend(&mut member(uid), "").await.ok().unwrap();
+        }
+        nalgang
+            .set_reminder(&mut member(uid), "08:00", ReminderDelivery::DirectMessage)
+            .await
+            .ok()
+            .unwrap();
+    }
+    nalgang.buy_freezes(&mut member(10), 1).await.ok().unwrap();
+
+    // Both missed 2024-01-04, which only a freeze covers for member 10.
+    clock.set(T0 + 4 * DAY);
+    let rollover = DayRollover {
+        gid: GID,
+        date: NaiveDate::from_ymd_opt(2024, 1, 4).unwrap(),
+        entries: Vec::new(),
+    };
+    let summary = nalgang.daily_summary(rollover).await.ok().unwrap();
+    let broken: Vec<(i64, i64)> = summary
+        .broken_streaks
+        .iter()
+        .map(|broken| (broken.user_id, broken.combo))
+        .collect();
+    assert_eq!(broken, vec![(11, 3)]);
+
+    let reminded: Vec<i64> = nalgang
+        .due_reminders(GID)
+        .await
+        .ok()
+        .unwrap()
+        .iter()
+        .map(|reminder| reminder.user_id)
+        .collect();
+    assert_eq!(reminded, vec![10]);
+}
+
+#[tokio::test]
+async fn freeze_purchase_limits() {
+    let (nalgang, _) = nalgang().await;
+    let e = nalgang
+        .buy_freezes(&mut member(10), i64::MAX)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::FreezeLimitExceeded));
+
+    set_freeze_rule(&nalgang, 2, i64::MAX).await;
+    let e = nalgang
+        .buy_freezes(&mut member(10), i64::MAX / 2 + 1)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(e.kind, NalgangErrorInner::InsufficientScore));
+}
+
+#[tokio::test]
+async fn shop_item_durations() {
+    let (nalgang, _) = nalgang().await;
+    let item = |duration| ShopItem {
+        name: "badge".to_string(),
+        price: 0,
+        stock: None,
+        role_id: None,
+        duration,
+    };
+    for duration in [None, Some(1), Some(MAX_ITEM_DURATION)] {
+        nalgang
+            .set_shop_item(GID, &item(duration))
+            .await
+            .ok()
+            .unwrap();
+    }
+    for duration in [Some(0), Some(MAX_ITEM_DURATION + 1), Some(i64::MAX)] {
+        let e = nalgang
+            .set_shop_item(GID, &item(duration))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(e.kind, NalgangErrorInner::InvalidShopItem));
+    }
+
+    nalgang
+        .set_shop_item(GID, &item(Some(MAX_ITEM_DURATION)))
+        .await
+        .ok()
+        .unwrap();
+    let bought = nalgang
+        .buy_item(&mut member(10), "badge")
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(bought.expire_time, Some(T0 + MAX_ITEM_DURATION * DAY));
+}
+
+#[tokio::test]
+async fn recompute_keeps_imported_scores() {
+    let (nalgang, clock) = nalgang().await;
+    nalgang.attend(&mut member(10), "").await.ok().unwrap();
+    let mut archive = nalgang.export_guild(GID).await.ok().unwrap();
+    // A score the history does not add up to, as in dumps of the previous bot
+    archive.members[1].score = 500;
+
+    clock.set(T0 + 60);
+    let other = GID + 1;
+    for policy in [ConflictPolicy::Skip, ConflictPolicy::MergeScores] {
+        nalgang
+            .import_guild(other, &archive, policy)
+            .await
+            .ok()
+            .unwrap();
+    }
+    nalgang
+        .import_guild(GID, &archive, ConflictPolicy::Overwrite)
+        .await
+        .ok()
+        .unwrap();
+
+    for (gid, scores) in [(GID, [10, 500, 0]), (other, [20, 1000, 0])] {
+        assert!(nalgang
+            .recompute_members(gid, false)
+            .await
+            .ok()
+            .unwrap()
+            .is_empty());
+        for (uid, score) in [10, 11, 12].into_iter().zip(scores) {
+            let mut imported = NalgangMember::new(uid, gid);
+            nalgang.point(&mut imported).await.ok().unwrap();
+            assert_eq!(imported.score, Some(score), "{} {}", gid, uid);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use nalgang_core::{NalgangError, NalgangErrorInner, NalgangMember};
+use serde::Serialize;
+
+use crate::Handler;
+
+// Member who owns the bearer token of the request
+struct Authorized(NalgangMember);
+
+enum ApiError {
+    Unauthorized,
+    MemberNotExist,
+    Internal(NalgangError),
+}
+
+impl From<NalgangError> for ApiError {
+    fn from(e: NalgangError) -> Self {
+        match e.kind {
+            NalgangErrorInner::MemberNotExist => ApiError::MemberNotExist,
+            _ => ApiError::Internal(e),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "invalid token"),
+            ApiError::MemberNotExist => (StatusCode::NOT_FOUND, "member not exist"),
+            ApiError::Internal(e) => {
+                println!("{}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
+        };
+        (status, Json(ErrorBody { error: message })).into_response()
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Handler> for Authorized {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Handler) -> Result<Self, ApiError> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(ApiError::Unauthorized)?;
+
+        match state.nalgang.token_owner(token.trim()).await? {
+            Some(member) => Ok(Authorized(member)),
+            None => Err(ApiError::Unauthorized),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+}
+
+// Snowflakes are serialized as strings since they do not fit in a JSON number.
+#[derive(Serialize)]
+struct MemberBody {
+    user_id: String,
+    score: i64,
+    combo: i64,
+    hit_time: i64,
+}
+
+#[derive(Serialize)]
+struct RankingBody {
+    rank: usize,
+    user_id: String,
+    score: i64,
+}
+
+#[derive(Serialize)]
+struct AttendanceBody {
+    rank: usize,
+    user_id: String,
+    hit_message: Option<String>,
+    hit_time: i64,
+}
+
+async fn member_body(handler: &Handler, mut member: NalgangMember) -> Result<MemberBody, ApiError> {
+    handler.nalgang.point(&mut member).await?;
+    Ok(MemberBody {
+        user_id: member.uid.to_string(),
+        score: member.score.unwrap(),
+        combo: member.combo.unwrap(),
+        hit_time: member.hit_time.unwrap(),
+    })
+}
+
+async fn get_me(
+    State(handler): State<Handler>,
+    Authorized(member): Authorized,
+) -> Result<Json<MemberBody>, ApiError> {
+    Ok(Json(member_body(&handler, member).await?))
+}
+
+async fn get_member(
+    State(handler): State<Handler>,
+    Authorized(member): Authorized,
+    Path(user_id): Path<i64>,
+) -> Result<Json<MemberBody>, ApiError> {
+    let target = NalgangMember::new(user_id, member.gid);
+    Ok(Json(member_body(&handler, target).await?))
+}
+
+async fn get_ranking(
+    State(handler): State<Handler>,
+    Authorized(member): Authorized,
+) -> Result<Json<Vec<RankingBody>>, ApiError> {
+    let ranking = handler.nalgang.ranking(member.gid).await?;
+    Ok(Json(
+        ranking
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| RankingBody {
+                rank: index + 1,
+                user_id: row.user_id.to_string(),
+                score: row.score,
+            })
+            .collect(),
+    ))
+}
+
+async fn get_today_attendance(
+    State(handler): State<Handler>,
+    Authorized(member): Authorized,
+) -> Result<Json<Vec<AttendanceBody>>, ApiError> {
+    let (_, attendance) = handler.nalgang.today_attendance(member.gid).await?;
+    Ok(Json(
+        attendance
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| AttendanceBody {
+                rank: index + 1,
+                user_id: row.user_id.to_string(),
+                hit_message: row.hit_message,
+                hit_time: row.hit_time,
+            })
+            .collect(),
+    ))
+}
+
+pub fn router(handler: Handler) -> Router {
+    Router::new()
+        .route("/members/me", get(get_me))
+        .route("/members/:user_id", get(get_member))
+        .route("/ranking", get(get_ranking))
+        .route("/attendance/today", get(get_today_attendance))
+        .with_state(handler)
+}
+
+pub async fn serve(handler: Handler, address: SocketAddr) {
+    println!("API server is listening on {}", address);
+    if let Err(why) = axum::Server::bind(&address)
+        .serve(router(handler).into_make_service())
+        .await
+    {
+        println!("API server error: {:?}", why);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use nalgang_core::{DatabaseStorage, Nalgang, SystemClock};
+    use tower::ServiceExt;
+
+    const GID: i64 = 1;
+
+    // Handler on a new SQLite file with member 10, the token of the member and the file
+    async fn handler(name: &str) -> (Handler, String, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "nalgang-api-{}-{}.sqlite",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let storage = DatabaseStorage::connect(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        storage.migrate().await.unwrap();
+        let handler = Handler {
+            nalgang: Nalgang::new(storage, SystemClock),
+        };
+        handler.nalgang.register_guild(GID).await.ok().unwrap();
+        let mut member = NalgangMember::new(10, GID);
+        handler.nalgang.register(&mut member).await.ok().unwrap();
+        let token = handler.nalgang.issue_token(&member).await.ok().unwrap();
+        (handler, token, path)
+    }
+
+    async fn get(handler: &Handler, uri: &str, token: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = router(handler.clone())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_invalid_tokens() {
+        let (handler, _, path) = handler("unauthorized").await;
+        for token in [None, Some("invalid")] {
+            let (status, body) = get(&handler, "/members/me", token).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body, r#"{"error":"invalid token"}"#);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_the_member_of_the_token() {
+        let (handler, token, path) = handler("authorized").await;
+        let (status, body) = get(&handler, "/members/me", Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"user_id":"10","score":0,"combo":0,"hit_time":0}"#);
+        let (status, _) = get(&handler, "/members/11", Some(&token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+// Maintenance of the database at DATABASE_URL without connecting to Discord, for when the bot is
+// down. Only `migrate` changes the schema, so run it first on a new or older database.
+use std::{env, fs, process};
+
+use chrono::{LocalResult, TimeZone, Utc};
+use nalgang_core::archive::{ArchiveFormat, ConflictPolicy, GuildArchive};
+use nalgang_core::{DatabaseStorage, Nalgang, NalgangMember, SystemClock};
+
+const USAGE: &str = "usage:
+    nalgang-admin migrate
+    nalgang-admin guilds
+    nalgang-admin member <guild_id> <user_id>
+    nalgang-admin recompute <guild_id> [--apply]
+    nalgang-admin revoke-token <guild_id> <user_id>
+    nalgang-admin export <guild_id> <json|csv> <path>
+    nalgang-admin import <guild_id> <path> [skip|overwrite|merge]
+    nalgang-admin vacuum
+    nalgang-admin backup <path>";
+
+// Attendances `member` prints, latest first
+const RECENT_ATTENDANCES: i64 = 5;
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    if let Err(message) = run().await {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let database_url =
+        env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:database.sqlite".to_string());
+    let storage = DatabaseStorage::connect(&database_url)
+        .await
+        .map_err(|e| format!("Couldn't connect to database: {}", e))?;
+    let nalgang = Nalgang::new(storage, SystemClock);
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["migrate"] => {
+            nalgang
+                .storage
+                .migrate()
+                .await
+                .map_err(|e| format!("Couldn't run database migrations: {}", e))?;
+            println!("Database is up to date");
+        }
+        ["guilds"] => {
+            for gid in nalgang.guilds().await.map_err(|e| e.to_string())? {
+                let members = nalgang.members(gid).await.map_err(|e| e.to_string())?;
+                println!("{}\t{} members", gid, members.len());
+            }
+        }
+        ["member", gid, uid] => {
+            let mut member = NalgangMember::new(parse_id(uid)?, parse_id(gid)?);
+            let history = nalgang
+                .history_page(&mut member, 0, RECENT_ATTENDANCES)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("score\t{}", member.score.unwrap());
+            println!("combo\t{}", member.combo.unwrap());
+            println!("last attendance\t{}", format_time(member.hit_time.unwrap()));
+            println!("freezes\t{}", member.freezes.unwrap());
+            for entry in history.entries.iter() {
+                println!(
+                    "{}\trank {}, {} points, score {}, combo {}",
+                    history.date(entry),
+                    entry.hit_rank + 1,
+                    entry.hit_point,
+                    entry.hit_score,
+                    entry.hit_combo
+                );
+            }
+        }
+        ["recompute", gid, apply @ ..] if apply.is_empty() || apply == ["--apply"] => {
+            let gid = parse_id(gid)?;
+            let apply = !apply.is_empty();
+            let changed = nalgang
+                .recompute_members(gid, apply)
+                .await
+                .map_err(|e| e.to_string())?;
+            for (stored, recorded) in changed.iter() {
+                println!(
+                    "{}\tscore {} -> {}, combo {} -> {}, last attendance {} -> {}",
+                    stored.uid,
+                    stored.score.unwrap(),
+                    recorded.score.unwrap(),
+                    stored.combo.unwrap(),
+                    recorded.combo.unwrap(),
+                    format_time(stored.hit_time.unwrap()),
+                    format_time(recorded.hit_time.unwrap())
+                );
+            }
+            match apply {
+                true => println!("Recomputed {} members", changed.len()),
+                false => println!(
+                    "{} members differ from their history, run with --apply to recompute them",
+                    changed.len()
+                ),
+            }
+        }
+        ["revoke-token", gid, uid] => {
+            let member = NalgangMember::new(parse_id(uid)?, parse_id(gid)?);
+            match nalgang
+                .delete_token(&member)
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                true => println!("Revoked the token of {}", member.uid),
+                false => println!("{} has no token", member.uid),
+            }
+        }
+        ["export", gid, format, path] => {
+            let gid = parse_id(gid)?;
+            let format = ArchiveFormat::from_kind(format).ok_or_else(|| USAGE.to_string())?;
+            let archive = nalgang.export_guild(gid).await.map_err(|e| e.to_string())?;
+            fs::write(path, archive.encode(format))
+                .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+            println!(
+                "Exported {} members and {} attendances of guild {} to {}",
+                archive.members.len(),
+                archive.history.len(),
+                gid,
+                path
+            );
+        }
+        ["import", gid, path, policy @ ..] if policy.len() <= 1 => {
+            let gid = parse_id(gid)?;
+            let policy = match policy.first() {
+                Some(kind) => ConflictPolicy::from_kind(kind).ok_or_else(|| USAGE.to_string())?,
+                None => ConflictPolicy::Skip,
+            };
+            let data =
+                fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+            let archive = GuildArchive::decode(&data).map_err(|e| e.to_string())?;
+            let report = nalgang
+                .import_guild(gid, &archive, policy)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Imported guild {} into {}: {} added, {} skipped, {} overwritten, {} merged, {} attendances added{}",
+                archive.guild_id,
+                gid,
+                report.added,
+                report.skipped,
+                report.overwritten,
+                report.merged,
+                report.history_added,
+                match report.settings_restored {
+                    true => ", settings restored",
+                    false => "",
+                }
+            );
+        }
+        ["vacuum"] => {
+            nalgang
+                .storage
+                .vacuum()
+                .await
+                .map_err(|e| format!("Couldn't vacuum database: {}", e))?;
+            println!("Vacuumed database");
+        }
+        ["backup", path] => {
+            nalgang
+                .storage
+                .backup(path)
+                .await
+                .map_err(|e| format!("Couldn't back up database: {}", e))?;
+            println!("Backed up database to {}", path);
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+fn parse_id(id: &str) -> Result<i64, String> {
+    id.parse().map_err(|_| format!("{} is not a valid id", id))
+}
+
+fn format_time(time: i64) -> String {
+    match Utc.timestamp_opt(time, 0) {
+        LocalResult::Single(time) if time.timestamp() > 0 => {
+            time.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+        }
+        _ => "never".to_string(),
+    }
+}
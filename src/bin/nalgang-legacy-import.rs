@@ -0,0 +1,96 @@
+// Imports a dump of the previous Python bot into the database at DATABASE_URL. The format of the
+// dump is described in `nalgang_core::legacy`.
+use std::{env, fs, process};
+
+use nalgang_core::archive::ConflictPolicy;
+use nalgang_core::legacy::LegacyDump;
+use nalgang_core::{DatabaseStorage, Nalgang, SystemClock};
+
+const USAGE: &str =
+    "usage: nalgang-legacy-import [--dry-run] <dump.json> [guild_id] [skip|overwrite|merge]";
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    if let Err(message) = run().await {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let dry_run = match args.iter().position(|arg| arg == "--dry-run") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let (path, gid, policy) = match args.as_slice() {
+        [path] => (path, None, None),
+        [path, gid] => (path, Some(gid), None),
+        [path, gid, policy] => (path, Some(gid), Some(policy)),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let data = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let dump: LegacyDump =
+        serde_json::from_str(&data).map_err(|e| format!("{} is not a legacy dump: {}", path, e))?;
+    // Imports into the guild of the dump unless another is given.
+    let gid = match gid {
+        Some(gid) => gid
+            .parse()
+            .map_err(|_| format!("{} is not a valid guild id", gid))?,
+        None => dump.guild_id,
+    };
+    let policy = match policy {
+        Some(kind) => ConflictPolicy::from_kind(kind).ok_or_else(|| USAGE.to_string())?,
+        None => ConflictPolicy::Skip,
+    };
+
+    let database_url =
+        env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:database.sqlite".to_string());
+    let storage = DatabaseStorage::connect(&database_url)
+        .await
+        .map_err(|e| format!("Couldn't connect to database: {}", e))?;
+    storage
+        .migrate()
+        .await
+        .map_err(|e| format!("Couldn't run database migrations: {}", e))?;
+    let nalgang = Nalgang::new(storage, SystemClock);
+
+    let conversion = nalgang
+        .convert_legacy(gid, &dump)
+        .await
+        .map_err(|e| e.to_string())?;
+    for issue in conversion.issues.iter() {
+        println!("{}", issue);
+    }
+    println!(
+        "{} of {} members and {} of {} attendances to import, {} issues",
+        conversion.archive.members.len(),
+        dump.members.len(),
+        conversion.archive.history.len(),
+        dump.history.len(),
+        conversion.issues.len()
+    );
+    if dry_run {
+        return Ok(());
+    }
+
+    let report = nalgang
+        .import_legacy(gid, &conversion, policy)
+        .await
+        .map_err(|e| e.to_string())?;
+    println!(
+        "Imported into guild {}: {} added, {} skipped, {} overwritten, {} merged, {} attendances added",
+        gid,
+        report.added,
+        report.skipped,
+        report.overwritten,
+        report.merged,
+        report.history_added
+    );
+    Ok(())
+}
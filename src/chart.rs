@@ -0,0 +1,319 @@
+use nalgang_core::ScoreHistory;
+
+pub type Color = [u8; 3];
+
+const BACKGROUND: Color = [255, 255, 255];
+const AXIS: Color = [200, 203, 208];
+const TEXT: Color = [60, 64, 70];
+const BAR: Color = [88, 101, 242];
+const LINE: Color = [64, 196, 99];
+
+const MARGIN: usize = 12;
+
+// Glyphs of the built-in 3x5 font, one row per byte from the top with the leftmost pixel in bit 2.
+// Only digits and the signs used by the charts are drawn, names are left to the embed.
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => return None,
+    })
+}
+
+// Pixels per font pixel
+const FONT_SCALE: usize = 2;
+const GLYPH_WIDTH: usize = 3 * FONT_SCALE;
+const GLYPH_HEIGHT: usize = 5 * FONT_SCALE;
+const GLYPH_ADVANCE: usize = GLYPH_WIDTH + FONT_SCALE;
+
+// RGB image drawn by the charts and the heatmap
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: BACKGROUND.repeat(width * height),
+        }
+    }
+
+    // Parts outside of the canvas are clipped.
+    pub fn fill_rect(
+        &mut self,
+        left: usize,
+        top: usize,
+        width: usize,
+        height: usize,
+        color: Color,
+    ) {
+        for y in top..(top + height).min(self.height) {
+            for x in left..(left + width).min(self.width) {
+                let index = (y * self.width + x) * 3;
+                self.pixels[index..index + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    // Two pixels thick line from (x0, y0) to (x1, y1)
+    fn line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), color: Color) {
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1);
+        for step in 0..=steps {
+            let x = x0 + (x1 - x0) * step / steps;
+            let y = y0 + (y1 - y0) * step / steps;
+            self.fill_rect(x.max(0) as usize, y.max(0) as usize, 2, 2, color);
+        }
+    }
+
+    fn text_width(text: &str) -> usize {
+        (text.chars().count() * GLYPH_ADVANCE).saturating_sub(FONT_SCALE)
+    }
+
+    // Unknown characters are skipped.
+    fn text(&mut self, left: usize, top: usize, text: &str, color: Color) {
+        for (i, c) in text.chars().enumerate() {
+            let rows = match glyph(c) {
+                Some(rows) => rows,
+                None => continue,
+            };
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        self.fill_rect(
+                            left + i * GLYPH_ADVANCE + column * FONT_SCALE,
+                            top + row * FONT_SCALE,
+                            FONT_SCALE,
+                            FONT_SCALE,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut image = Vec::new();
+        let mut encoder = png::Encoder::new(&mut image, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(image)
+    }
+}
+
+const BAR_CHART_WIDTH: usize = 480;
+const BAR_HEIGHT: usize = 18;
+const BAR_PITCH: usize = 26;
+
+// Horizontal bars of (rank, score) from the top, labeled with the rank starting from 1 and the score.
+pub fn render_bar_chart(bars: &[(i64, i64)]) -> Result<Vec<u8>, png::EncodingError> {
+    let height = MARGIN * 2 + (bars.len() * BAR_PITCH).saturating_sub(BAR_PITCH - BAR_HEIGHT);
+    let mut canvas = Canvas::new(BAR_CHART_WIDTH, height.max(MARGIN * 2));
+
+    let label_width = bars
+        .iter()
+        .map(|(rank, _)| Canvas::text_width(&(rank + 1).to_string()))
+        .max()
+        .unwrap_or(0);
+    let value_width = bars
+        .iter()
+        .map(|(_, score)| Canvas::text_width(&score.to_string()))
+        .max()
+        .unwrap_or(0);
+    let bar_left = MARGIN + label_width + MARGIN;
+    let bar_space = BAR_CHART_WIDTH - bar_left - MARGIN - value_width - MARGIN;
+    let max_score = bars
+        .iter()
+        .map(|(_, score)| *score)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    canvas.fill_rect(bar_left - 1, MARGIN, 1, height - MARGIN * 2, AXIS);
+    for (i, (rank, score)) in bars.iter().enumerate() {
+        let top = MARGIN + i * BAR_PITCH;
+        let text_top = top + (BAR_HEIGHT - GLYPH_HEIGHT) / 2;
+        let label = (rank + 1).to_string();
+        canvas.text(
+            bar_left - MARGIN - Canvas::text_width(&label),
+            text_top,
+            &label,
+            TEXT,
+        );
+
+        // Negative scores are drawn as empty bars.
+        let bar_width = (*score).max(0) as usize * bar_space / max_score as usize;
+        canvas.fill_rect(bar_left, top, bar_width, BAR_HEIGHT, BAR);
+        canvas.text(
+            bar_left + bar_width + MARGIN / 2,
+            text_top,
+            &score.to_string(),
+            TEXT,
+        );
+    }
+
+    canvas.encode()
+}
+
+const LINE_CHART_WIDTH: usize = 480;
+const LINE_CHART_HEIGHT: usize = 240;
+
+// Score over the days of `history`, with the score range on the left and the dates below.
+pub fn render_line_chart(history: &ScoreHistory) -> Result<Vec<u8>, png::EncodingError> {
+    let mut canvas = Canvas::new(LINE_CHART_WIDTH, LINE_CHART_HEIGHT);
+
+    let scores = history.scores.iter().map(|(_, score)| *score);
+    let (mut min_score, mut max_score) =
+        (scores.clone().min().unwrap_or(0), scores.max().unwrap_or(0));
+    if min_score == max_score {
+        min_score -= 1;
+        max_score += 1;
+    }
+    let (min_label, max_label) = (min_score.to_string(), max_score.to_string());
+    let label_width = Canvas::text_width(&min_label).max(Canvas::text_width(&max_label));
+
+    let plot_left = MARGIN + label_width + MARGIN;
+    let plot_top = MARGIN + GLYPH_HEIGHT / 2;
+    let plot_width = LINE_CHART_WIDTH - plot_left - MARGIN;
+    let plot_height = LINE_CHART_HEIGHT - plot_top - MARGIN - GLYPH_HEIGHT - MARGIN;
+    let plot_bottom = plot_top + plot_height;
+
+    canvas.fill_rect(plot_left - 1, plot_top, 1, plot_height + 1, AXIS);
+    canvas.fill_rect(plot_left - 1, plot_bottom, plot_width + 1, 1, AXIS);
+    canvas.text(
+        plot_left - MARGIN - Canvas::text_width(&max_label),
+        plot_top - GLYPH_HEIGHT / 2,
+        &max_label,
+        TEXT,
+    );
+    canvas.text(
+        plot_left - MARGIN - Canvas::text_width(&min_label),
+        plot_bottom - GLYPH_HEIGHT / 2,
+        &min_label,
+        TEXT,
+    );
+
+    let (since, today) = (
+        history.since.format("%Y-%m-%d").to_string(),
+        history.today.format("%Y-%m-%d").to_string(),
+    );
+    let date_top = plot_bottom + MARGIN;
+    canvas.text(plot_left, date_top, &since, TEXT);
+    canvas.text(
+        LINE_CHART_WIDTH - MARGIN - Canvas::text_width(&today),
+        date_top,
+        &today,
+        TEXT,
+    );
+
+    let days = (history.today - history.since).num_days().max(1);
+    let point = |(date, score): &(chrono::NaiveDate, i64)| {
+        (
+            plot_left as i64 + (*date - history.since).num_days() * plot_width as i64 / days,
+            plot_bottom as i64 - (score - min_score) * plot_height as i64 / (max_score - min_score),
+        )
+    };
+    let points: Vec<(i64, i64)> = history.scores.iter().map(point).collect();
+    for pair in points.windows(2) {
+        canvas.line(pair[0], pair[1], LINE);
+    }
+    for (x, y) in points {
+        canvas.fill_rect((x - 2).max(0) as usize, (y - 2).max(0) as usize, 5, 5, LINE);
+    }
+
+    canvas.encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::path::PathBuf;
+
+    // Compares `image` with tests/golden/`name` pixel by pixel.
+    // Run with UPDATE_GOLDEN=1 to rewrite the golden image after an intended change.
+    fn assert_golden(name: &str, image: &[u8]) {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+            .iter()
+            .collect();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, image).unwrap();
+            return;
+        }
+
+        let golden = std::fs::read(&path).unwrap();
+        assert_eq!(decode(image), decode(&golden), "{} differs", name);
+    }
+
+    fn decode(image: &[u8]) -> (u32, u32, Vec<u8>) {
+        let mut reader = png::Decoder::new(image).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(info.buffer_size());
+        (info.width, info.height, pixels)
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn bar_chart() {
+        let bars = [
+            (0, 1520),
+            (1, 1210),
+            (2, 980),
+            (3, 455),
+            (4, 120),
+            (5, 0),
+            (6, -30),
+        ];
+        assert_golden("bar_chart.png", &render_bar_chart(&bars).unwrap());
+    }
+
+    #[test]
+    fn empty_bar_chart() {
+        assert_golden("empty_bar_chart.png", &render_bar_chart(&[]).unwrap());
+    }
+
+    #[test]
+    fn line_chart() {
+        let history = ScoreHistory {
+            since: date("2024-01-01"),
+            today: date("2024-01-31"),
+            scores: vec![
+                (date("2024-01-01"), 10),
+                (date("2024-01-02"), 23),
+                (date("2024-01-05"), 30),
+                (date("2024-01-06"), 18),
+                (date("2024-01-20"), 64),
+                (date("2024-01-31"), 75),
+            ],
+        };
+        assert_golden("line_chart.png", &render_line_chart(&history).unwrap());
+    }
+
+    #[test]
+    fn flat_line_chart() {
+        let history = ScoreHistory {
+            since: date("2024-01-31"),
+            today: date("2024-01-31"),
+            scores: vec![(date("2024-01-31"), 10)],
+        };
+        assert_golden("flat_line_chart.png", &render_line_chart(&history).unwrap());
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs;
+
+use nalgang_core::archive::{ArchiveFormat, ConflictPolicy, GuildArchive};
+use nalgang_core::{DatabaseStorage, Nalgang, SystemClock};
+
+// Subcommands which work on the database without connecting to Discord
+pub const USAGE: &str = "usage:
+    nalgang-rust export <guild_id> <json|csv> <path>
+    nalgang-rust import <guild_id> <path> [skip|overwrite|merge]";
+
+// Runs the subcommand of `args`, which leave out the program name.
+pub async fn run(
+    nalgang: &Nalgang<DatabaseStorage, SystemClock>,
+    args: &[String],
+) -> Result<String, String> {
+    match args {
+        [command, gid, format, path] if command == "export" => {
+            let gid = parse_guild_id(gid)?;
+            let format = ArchiveFormat::from_kind(format).ok_or_else(|| USAGE.to_string())?;
+            let archive = nalgang.export_guild(gid).await.map_err(|e| e.to_string())?;
+            fs::write(path, archive.encode(format))
+                .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+            Ok(format!(
+                "Exported {} members and {} attendances of guild {} to {}",
+                archive.members.len(),
+                archive.history.len(),
+                gid,
+                path
+            ))
+        }
+        [command, gid, path, policy @ ..] if command == "import" && policy.len() <= 1 => {
+            let gid = parse_guild_id(gid)?;
+            let policy = match policy.first() {
+                Some(kind) => ConflictPolicy::from_kind(kind).ok_or_else(|| USAGE.to_string())?,
+                None => ConflictPolicy::Skip,
+            };
+            let data =
+                fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+            let archive = GuildArchive::decode(&data).map_err(|e| e.to_string())?;
+            let report = nalgang
+                .import_guild(gid, &archive, policy)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(format!(
+                "Imported guild {} into {}: {} added, {} skipped, {} overwritten, {} merged, {} attendances added{}",
+                archive.guild_id,
+                gid,
+                report.added,
+                report.skipped,
+                report.overwritten,
+                report.merged,
+                report.history_added,
+                match report.settings_restored {
+                    true => ", settings restored",
+                    false => "",
+                }
+            ))
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn parse_guild_id(gid: &str) -> Result<i64, String> {
+    gid.parse()
+        .map_err(|_| format!("{} is not a valid guild id", gid))
+}
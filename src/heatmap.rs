@@ -0,0 +1,59 @@
+use chrono::{Datelike, Duration};
+use nalgang_core::AttendanceCalendar;
+
+use crate::chart::{Canvas, Color};
+
+// GitHub style yearly heatmap: one column per week, one row per weekday from Monday.
+const WEEKS: i64 = 53;
+const CELL: usize = 12;
+const PITCH: usize = 15;
+const MARGIN: usize = 12;
+
+// Days the heatmap covers, to be requested from `Nalgang::attendance_calendar`.
+pub const HEATMAP_DAYS: i64 = WEEKS * 7;
+
+const EMPTY: Color = [235, 237, 240];
+
+// Darker as the combo of the day grows
+fn combo_color(combo: i64) -> Color {
+    match combo {
+        i64::MIN..=6 => [155, 233, 168],
+        7..=29 => [64, 196, 99],
+        30..=99 => [48, 161, 78],
+        _ => [33, 110, 57],
+    }
+}
+
+pub fn render_heatmap(calendar: &AttendanceCalendar) -> Result<Vec<u8>, png::EncodingError> {
+    let width = MARGIN * 2 + WEEKS as usize * PITCH - (PITCH - CELL);
+    let height = MARGIN * 2 + 7 * PITCH - (PITCH - CELL);
+    let mut canvas = Canvas::new(width, height);
+
+    let today = calendar.today;
+    let first_day =
+        today - Duration::days((WEEKS - 1) * 7 + today.weekday().num_days_from_monday() as i64);
+    for week in 0..WEEKS {
+        for weekday in 0..7 {
+            let date = first_day + Duration::days(week * 7 + weekday);
+            if date > today {
+                break;
+            }
+            let color = calendar
+                .days
+                .iter()
+                .find(|(day, _)| *day == date)
+                .map(|(_, combo)| combo_color(*combo))
+                .unwrap_or(EMPTY);
+
+            canvas.fill_rect(
+                MARGIN + week as usize * PITCH,
+                MARGIN + weekday as usize * PITCH,
+                CELL,
+                CELL,
+                color,
+            );
+        }
+    }
+
+    canvas.encode()
+}
@@ -1,448 +1,661 @@
+use std::env;
 use std::fmt::Write as FmtWrite;
-use std::{borrow::Cow, env, fmt};
 
-use chrono::{DateTime, FixedOffset, NaiveDateTime};
-use serenity::builder::CreateApplicationCommands;
+use chrono::NaiveDate;
+use serenity::builder::{CreateApplicationCommands, CreateInteractionResponseData};
 use serenity::model::prelude::command::Command;
 use serenity::{
     async_trait,
     client::{Context, EventHandler},
     model::{
+        channel::{AttachmentType, ChannelType},
         gateway::GatewayIntents,
         gateway::Ready,
-        guild::{Guild, Member},
+        guild::{Guild, Member, PartialMember},
         id::GuildId,
         id::UserId,
         permissions::Permissions,
         prelude::command::CommandOptionType,
+        prelude::component::ButtonStyle,
         prelude::interaction::{
             application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+            message_component::MessageComponentInteraction,
             Interaction, InteractionResponseType,
         },
-        timestamp::Timestamp,
+        user::User,
     },
     Client,
 };
 
-mod utils;
+mod api;
+mod chart;
+mod cli;
+mod heatmap;
+mod scheduler;
 
-// Struct for database interaction
+use chart::{render_bar_chart, render_line_chart};
+use heatmap::{render_heatmap, HEATMAP_DAYS};
+use nalgang_core::achievement::Achievement;
+use nalgang_core::archive::{ArchiveFormat, ConflictPolicy, GuildArchive};
+use nalgang_core::audit::{AdjustField, Adjustment};
+use nalgang_core::period::Period;
+use nalgang_core::reminder::ReminderDelivery;
+use nalgang_core::role_reward::{RewardKind, RoleChanges, RoleReward};
+use nalgang_core::scoring::{ComboMilestone, RankPoints, ScoringRuleChange};
+use nalgang_core::shop::{ShopItem, MAX_ITEM_DURATION};
+use nalgang_core::{
+    nalgang_error, DatabaseStorage, HistoryPage, Nalgang, NalgangError, NalgangErrorInner,
+    NalgangMember, RankingKind, RankingPage, SystemClock,
+};
+
+// Discord frontend of the attendance engine
+#[derive(Clone)]
 struct Handler {
-    database: sqlx::SqlitePool,
+    nalgang: Nalgang<DatabaseStorage, SystemClock>,
 }
 
-struct NalgangMember {
-    pub uid: i64,
-    pub gid: i64,
-    pub score: Option<i64>,
-    pub combo: Option<i64>,
-    pub hit_time: Option<i64>,
+fn resolved_display_name<'a>(user: &'a User, member: &'a Option<PartialMember>) -> &'a str {
+    match member.as_ref().and_then(|inner| inner.nick.as_ref()) {
+        Some(s) => s,
+        None => &user.name,
+    }
 }
 
-// Wrapper for Serenity guild member
-impl NalgangMember {
-    pub fn new(member: &Member) -> Self {
-        NalgangMember {
-            uid: member.user.id.0 as i64,
-            gid: member.guild_id.0 as i64,
-            score: None,
-            combo: None,
-            hit_time: None,
-        }
-    }
+const RANKING_PAGE_SIZE: i64 = 10;
 
-    pub fn new_explict(user_id: UserId, guild_id: GuildId) -> Self {
-        NalgangMember {
-            uid: user_id.0 as i64,
-            gid: guild_id.0 as i64,
-            score: None,
-            combo: None,
-            hit_time: None,
-        }
-    }
+// Custom ids of the ranking buttons, followed by the page they were pressed on, the kind and the
+// period such as "ranking_next:0:점수:이번주".
+const RANKING_PREVIOUS: &str = "ranking_previous";
+const RANKING_NEXT: &str = "ranking_next";
+const RANKING_ME: &str = "ranking_me";
 
-    pub fn update_data(&mut self, score: i64, combo: i64, hit_time: i64) {
-        self.score = Some(score);
-        self.combo = Some(combo);
-        self.hit_time = Some(hit_time);
-    }
+fn ranking_custom_id(action: &str, ranking: &RankingPage) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        action, ranking.page, ranking.kind, ranking.period
+    )
 }
 
-macro_rules! nalgang_error {
-    ($error: expr) => {
-        NalgangError {
-            kind: $error,
-            file: file!(),
-            line: line!(),
-        }
-    };
-}
+// Image of the top scores, attached to the first page of the score ranking
+const RANKING_CHART_FILENAME: &str = "ranking.png";
 
-struct NalgangError {
-    kind: NalgangErrorInner,
-    file: &'static str,
-    line: u32,
+fn ranking_message<'a, 'b>(
+    message: &'b mut CreateInteractionResponseData<'a>,
+    ranking: &RankingPage,
+    content: String,
+    chart: bool,
+) -> &'b mut CreateInteractionResponseData<'a> {
+    message
+        .embed(|create_embed| {
+            create_embed
+                .title(format!("{} 랭킹 ({})", ranking.kind, ranking.period))
+                .description(content)
+                .footer(|footer| {
+                    footer.text(format!(
+                        "{}/{} 페이지",
+                        ranking.page + 1,
+                        ranking.page_count
+                    ))
+                });
+            if chart {
+                create_embed.attachment(RANKING_CHART_FILENAME);
+            }
+            create_embed
+        })
+        .components(|components| {
+            components.create_action_row(|row| {
+                row.create_button(|button| {
+                    button
+                        .custom_id(ranking_custom_id(RANKING_PREVIOUS, ranking))
+                        .label("이전")
+                        .style(ButtonStyle::Secondary)
+                        .disabled(ranking.page == 0)
+                })
+                .create_button(|button| {
+                    button
+                        .custom_id(ranking_custom_id(RANKING_ME, ranking))
+                        .label("내 순위")
+                        .style(ButtonStyle::Primary)
+                })
+                .create_button(|button| {
+                    button
+                        .custom_id(ranking_custom_id(RANKING_NEXT, ranking))
+                        .label("다음")
+                        .style(ButtonStyle::Secondary)
+                        .disabled(ranking.page + 1 >= ranking.page_count)
+                })
+            })
+        })
 }
 
-enum NalgangErrorInner {
-    DuplicateAttendance,
-    DuplicateMemberRegister,
-    DuplicateGuildRegister,
-    DuplidateTokenIssue,
-    MemberNotExist,
-    GuildNotExist,
-    BufferError(std::fmt::Error),
-    UnhandledDatabaseError(sqlx::Error),
-}
+const HISTORY_PAGE_SIZE: i64 = 10;
+const HEATMAP_FILENAME: &str = "heatmap.png";
+const SCORE_CHART_FILENAME: &str = "score.png";
+const SCORE_CHART_DAYS: i64 = 90;
 
-impl fmt::Display for NalgangError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = match &self.kind {
-            NalgangErrorInner::DuplicateAttendance => "duplicate attendance".to_string(),
-            NalgangErrorInner::DuplicateMemberRegister => "duplciate member register".to_string(),
-            NalgangErrorInner::DuplicateGuildRegister => "duplicate guild register".to_string(),
-            NalgangErrorInner::DuplidateTokenIssue => "duplciate token issue".to_string(),
-            NalgangErrorInner::MemberNotExist => "member not exist".to_string(),
-            NalgangErrorInner::GuildNotExist => "guild_not_exist".to_string(),
-            NalgangErrorInner::BufferError(_) => "buffer error".to_string(),
-            NalgangErrorInner::UnhandledDatabaseError(e) => e.to_string(),
-        };
-        writeln!(f, "{} error raised at {}:{}", s, self.file, self.line)
+// Custom ids of the history buttons, followed by the page they were pressed on and the user id
+// such as "history_next:0:1234".
+const HISTORY_PREVIOUS: &str = "history_previous";
+const HISTORY_NEXT: &str = "history_next";
+
+const AUDIT_LOG_SIZE: i64 = 10;
+const AUDIT_LOG_MAX_SIZE: i64 = 50;
+// Largest value an admin can add, subtract or set at once
+const ADJUSTMENT_MAX_VALUE: i64 = 1_000_000_000;
+// Discord limit of a message content, in characters
+const MESSAGE_LIMIT: usize = 2000;
+
+// Attaches a rendered image, dropping it with a log if rendering failed.
+fn add_image(
+    message: &mut CreateInteractionResponseData<'_>,
+    image: Option<Result<Vec<u8>, png::EncodingError>>,
+    filename: &str,
+) -> bool {
+    match image {
+        Some(Ok(data)) => {
+            message.add_file(AttachmentType::Bytes {
+                data: data.into(),
+                filename: filename.to_string(),
+            });
+            true
+        }
+        Some(Err(e)) => {
+            println!("Cannot render {}: {}", filename, e);
+            false
+        }
+        None => false,
     }
 }
 
-fn timestamp_round_down(utc_time: i64) -> i64 {
-    let hour = 3600;
-    let day = hour * 24;
-    let utc_kst_offset = 9 * hour;
-    let local_offset = 6 * hour;
-    ((utc_time + utc_kst_offset - local_offset) / day) * day + local_offset - utc_kst_offset
+fn has_attachment(component: &MessageComponentInteraction, filename: &str) -> bool {
+    component
+        .message
+        .attachments
+        .iter()
+        .any(|attachment| attachment.filename == filename)
 }
 
-fn earned_attendance_point(rank: i64, combo: i64) -> i64 {
-    let mut earned_point = match rank {
-        0 => 10,
-        1 => 5,
-        2 => 3,
-        _ => 1,
-    };
+fn history_message<'a, 'b>(
+    message: &'b mut CreateInteractionResponseData<'a>,
+    history: &HistoryPage,
+    uid: i64,
+    name: &str,
+    content: String,
+    heatmap: bool,
+    score_chart: bool,
+) -> &'b mut CreateInteractionResponseData<'a> {
+    message.embed(|create_embed| {
+        create_embed
+            .title(format!("{}님의 날갱 기록", name))
+            .description(content)
+            .footer(|footer| {
+                footer.text(format!(
+                    "{}/{} 페이지",
+                    history.page + 1,
+                    history.page_count
+                ))
+            });
+        if heatmap {
+            create_embed.attachment(HEATMAP_FILENAME);
+        }
+        create_embed
+    });
+    if score_chart {
+        message.embed(|create_embed| {
+            create_embed
+                .title("최근 점수 변화")
+                .attachment(SCORE_CHART_FILENAME)
+        });
+    }
+    message.components(|components| {
+        components.create_action_row(|row| {
+            row.create_button(|button| {
+                button
+                    .custom_id(format!("{}:{}:{}", HISTORY_PREVIOUS, history.page, uid))
+                    .label("이전")
+                    .style(ButtonStyle::Secondary)
+                    .disabled(history.page == 0)
+            })
+            .create_button(|button| {
+                button
+                    .custom_id(format!("{}:{}:{}", HISTORY_NEXT, history.page, uid))
+                    .label("다음")
+                    .style(ButtonStyle::Secondary)
+                    .disabled(history.page + 1 >= history.page_count)
+            })
+        })
+    })
+}
 
-    if combo % 7 == 0 {
-        earned_point += 20;
+// Resolves the name of a ranked or attending member. Falls back to the cached user, then to the
+// last known name, since the member may have left the guild.
+async fn resolve_name(
+    context: &Context,
+    guild_id: GuildId,
+    user_id: i64,
+    display_name: &Option<String>,
+    departed: bool,
+) -> String {
+    let user_id = UserId(user_id as u64);
+    if !departed {
+        if let Ok(member) = guild_id.member(context, user_id).await {
+            return member.display_name().into_owned();
+        }
     }
-    if combo % 30 == 0 {
-        earned_point += 100;
+    if let Some(user) = context.cache.user(user_id) {
+        return user.name;
     }
-    if combo % 365 == 1500 {
-        earned_point += 1500
+    match display_name {
+        Some(name) => name.clone(),
+        None => "(탈퇴한 사용자)".to_string(),
     }
-    earned_point
 }
 
-impl Handler {
-    async fn get_member_info(&self, member: &mut NalgangMember) -> Result<bool, NalgangError> {
-        let row = sqlx::query!(
-            "SELECT score, combo, hit_time FROM Member WHERE user_id=? AND guild_id=? LIMIT 1",
-            member.uid,
-            member.gid
-        )
-        .fetch_one(&self.database)
-        .await;
+// Wrapper for Serenity guild member
+fn nalgang_member(member: &Member) -> NalgangMember {
+    NalgangMember::new(member.user.id.0 as i64, member.guild_id.0 as i64)
+}
 
-        match row {
-            Ok(record) => {
-                member.update_data(record.score, record.combo, record.hit_time);
-                Ok(true)
-            }
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => Ok(false),
-                _ => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+impl Handler {
+    async fn simple_response(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        message: Result<String, NalgangError>,
+        ephemeral: bool,
+    ) {
+        let content = match message {
+            Ok(s) => s,
+            Err(e) => match e.kind {
+                NalgangErrorInner::GuildNotExist => {
+                    "등록되지 않은 서버입니다. 먼저 /서버등록 을 해주세요.".to_string()
+                }
+                _ => {
+                    println!("{}", e);
+                    "오류가 발생했습니다.".to_string()
+                }
             },
-        }
-    }
+        };
 
-    async fn update_member_info(&self, member: &NalgangMember) -> Result<(), NalgangError> {
-        let (score, combo, hit_time) = (
-            member.score.unwrap(),
-            member.combo.unwrap(),
-            member.hit_time.unwrap(),
-        );
-        match sqlx::query!(
-            "UPDATE Member SET score=?, combo=?, hit_time=? WHERE guild_id=? AND user_id=?",
-            score,
-            combo,
-            hit_time,
-            member.gid,
-            member.uid
-        )
-        .execute(&self.database)
-        .await
+        if let Err(why) = command
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message.content(content).ephemeral(ephemeral)
+                    })
+            })
+            .await
         {
-            Ok(_) => Ok(()),
-            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+            println!("Cannot respond to slash command: {}", why);
         }
     }
 
-    async fn daily_attendance_clear(&self, gid: i64) -> Result<(), NalgangError> {
-        match sqlx::query!("DELETE FROM DailyAttendance WHERE guild_id=?", gid)
-            .execute(&self.database)
-            .await
-        {
-            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
-            Ok(_) => Ok(()),
+    // Gives and takes the reward roles of a member. Returns the number of roles changed.
+    async fn apply_role_changes(
+        &self,
+        ctx: &Context,
+        gid: i64,
+        uid: i64,
+        changes: &RoleChanges,
+    ) -> usize {
+        let mut changed = 0;
+        for role_id in changes.grant.iter() {
+            match ctx
+                .http
+                .add_member_role(
+                    gid as u64,
+                    uid as u64,
+                    *role_id as u64,
+                    Some("날갱 역할 보상"),
+                )
+                .await
+            {
+                Ok(()) => changed += 1,
+                Err(why) => println!("Cannot grant role: {}", why),
+            }
+        }
+        for role_id in changes.revoke.iter() {
+            match ctx
+                .http
+                .remove_member_role(
+                    gid as u64,
+                    uid as u64,
+                    *role_id as u64,
+                    Some("날갱 역할 보상 조건 해제"),
+                )
+                .await
+            {
+                Ok(()) => changed += 1,
+                Err(why) => println!("Cannot remove role: {}", why),
+            }
         }
+        changed
     }
 
-    async fn register_guild(&self, gid: i64) -> Result<(), NalgangError> {
-        match sqlx::query_scalar!(
-            "SELECT EXISTS (SELECT (1) FROM AttendanceTimeCount WHERE guild_id=? LIMIT 1)",
-            gid
-        )
-        .fetch_one(&self.database)
-        .await
-        {
-            Ok(1) => return Err(nalgang_error!(NalgangErrorInner::DuplicateGuildRegister)),
-            Ok(0) => (),
-            Err(e) => return Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
-            _ => unreachable!(),
-        };
-
-        match sqlx::query!("INSERT INTO AttendanceTimeCount (guild_id) VALUES (?)", gid)
-            .execute(&self.database)
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+    // Applies the reward roles to every member of the guild.
+    async fn reconcile_roles(&self, ctx: &Context, gid: i64) -> Result<usize, NalgangError> {
+        let mut members = Vec::new();
+        let mut after = None;
+        loop {
+            let page = match GuildId(gid as u64)
+                .members(&ctx.http, Some(1000), after)
+                .await
+            {
+                Ok(page) => page,
+                Err(why) => {
+                    println!("Cannot get guild members: {}", why);
+                    break;
+                }
+            };
+            after = page.last().map(|member| member.user.id);
+            let last_page = page.len() < 1000;
+            members.extend(page.into_iter().map(|member| {
+                (
+                    member.user.id.0 as i64,
+                    member.roles.iter().map(|role| role.0 as i64).collect(),
+                )
+            }));
+            if last_page {
+                break;
+            }
         }
-    }
 
-    async fn command_point(&self, member: &mut NalgangMember) -> Result<(), NalgangError> {
-        match self.get_member_info(member).await? {
-            true => Ok(()),
-            false => Err(nalgang_error!(NalgangErrorInner::MemberNotExist)),
+        let mut changed = 0;
+        for (uid, changes) in self.nalgang.guild_role_changes(gid, &members).await? {
+            changed += self.apply_role_changes(ctx, gid, uid, &changes).await;
         }
+        Ok(changed)
     }
 
-    async fn command_register(&self, member: &mut NalgangMember) -> Result<(), NalgangError> {
-        if self.get_member_info(member).await? {
-            return Err(nalgang_error!(NalgangErrorInner::DuplicateMemberRegister));
+    // Imports an archive file into the guild and describes the result.
+    async fn import_archive(&self, gid: i64, data: &str, policy: ConflictPolicy) -> String {
+        let result = match GuildArchive::decode(data) {
+            Ok(archive) => self.nalgang.import_guild(gid, &archive, policy).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(report) => {
+                let mut content = format!(
+                    "새 멤버 {}명, 건너뛴 멤버 {}명, 덮어쓴 멤버 {}명, 점수를 합친 멤버 {}명, 날갱 기록 {}개를 가져왔습니다.",
+                    report.added, report.skipped, report.overwritten, report.merged, report.history_added
+                );
+                if report.settings_restored {
+                    content.push_str("\n서버 설정도 백업으로 바꿨습니다.");
+                }
+                content
+            }
+            Err(e) => match e.kind {
+                NalgangErrorInner::InvalidArchive(reason) => {
+                    format!("백업 파일이 올바르지 않습니다. {}", reason)
+                }
+                _ => {
+                    println!("{}", e);
+                    "오류가 발생했습니다.".to_string()
+                }
+            },
         }
+    }
 
-        let (uid, gid) = (member.uid, member.gid);
-        match sqlx::query!(
-            "INSERT INTO Member (user_id, guild_id) VALUES (?, ?)",
-            uid,
-            gid
-        )
-        .execute(&self.database)
-        .await
+    // Keeps the stored name of a registered member up to date.
+    async fn update_profile(&self, member: &NalgangMember, display_name: &str, departed: bool) {
+        if let Err(e) = self
+            .nalgang
+            .update_profile(member, display_name, departed)
+            .await
         {
-            Ok(_) => Ok(()),
-            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+            println!("{}", e);
         }
     }
 
-    async fn command_nalgang(
+    async fn today_attendance_collect(
         &self,
-        member: &mut NalgangMember,
-        time: Timestamp,
-        message: String,
-    ) -> Result<i64, NalgangError> {
-        if !self.get_member_info(member).await? {
-            return Err(nalgang_error!(NalgangErrorInner::MemberNotExist));
+        context: &Context,
+        guild_id: i64,
+    ) -> Result<(NaiveDate, String), NalgangError> {
+        let (date, rec) = self.nalgang.today_attendance(guild_id).await?;
+
+        let mut content = String::new();
+        let guild = GuildId(guild_id as u64);
+        for (index, row) in rec.iter().enumerate() {
+            let user_name =
+                resolve_name(context, guild, row.user_id, &row.display_name, row.departed).await;
+
+            let message = row.hit_message.clone().unwrap_or_default();
+            writeln!(&mut content, "{}. {}: {}", index + 1, user_name, message)
+                .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
         }
+        Ok((date, content))
+    }
 
-        let (gid, uid, member_hit_time) = (member.gid, member.uid, member.hit_time.unwrap());
-        let current_time = time.unix_timestamp();
+    async fn ranking_collect(
+        &self,
+        context: &Context,
+        member: &NalgangMember,
+        kind: RankingKind,
+        period: Period,
+        page: i64,
+    ) -> Result<(RankingPage, String), NalgangError> {
+        let ranking = self
+            .nalgang
+            .ranking_page(member.gid, kind, period, page, RANKING_PAGE_SIZE)
+            .await?;
 
-        // Get last hit_count, hit_timestamp from AttendanceTimeCount by guild_id
-        let guild_entry = sqlx::query!(
-            "SELECT hit_count, hit_time FROM 
-                AttendanceTimeCount WHERE guild_id=? LIMIT 1",
-            gid
-        )
-        .fetch_one(&self.database)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => nalgang_error!(NalgangErrorInner::GuildNotExist),
-            _ => nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)),
-        })?;
-        let guild_hit_count = guild_entry.hit_count;
-        let guild_hit_time = guild_entry.hit_time;
-
-        let day = 3600 * 24;
-        let rank_boundary_time = timestamp_round_down(guild_hit_time) + day;
-        let combo_boundary_time = timestamp_round_down(member_hit_time) + 2 * day;
-
-        let rank = if current_time >= rank_boundary_time {
-            self.daily_attendance_clear(gid).await?; // TODO: Schedule the delete query.
-            0
-        } else {
-            // Raise error if user tries to do duplicate hit.
-            // boundary_time - 86400 <= t < current_time < boundary_time, then duplicate hit!
-            if (rank_boundary_time - 86400) <= member_hit_time {
-                return Err(nalgang_error!(NalgangErrorInner::DuplicateAttendance));
+        let mut content = String::new();
+        let guild_id = GuildId(member.gid as u64);
+        for (index, row) in ranking.entries.iter().enumerate() {
+            let user_name = resolve_name(
+                context,
+                guild_id,
+                row.user_id,
+                &row.display_name,
+                row.departed,
+            )
+            .await;
+            let rank = ranking.offset + index as i64 + 1;
+            let score = ranking.kind.format(row.score);
+            if row.user_id == member.uid {
+                writeln!(&mut content, "**{}. {} {}**", rank, score, user_name)
+            } else {
+                writeln!(&mut content, "{}. {} {}", rank, score, user_name)
             }
-            guild_hit_count + 1
-        };
-
-        let _ = sqlx::query!(
-            "UPDATE AttendanceTimeCount SET hit_count=?, hit_time=? WHERE guild_id=?",
-            rank,
-            current_time,
-            gid
-        )
-        .execute(&self.database)
-        .await
-        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+            .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+        }
+        if content.is_empty() {
+            content.push_str(match (ranking.kind, ranking.period) {
+                (RankingKind::Score, Period::AllTime) => "등록된 계정이 없습니다.",
+                _ => "해당하는 기록이 없습니다.",
+            });
+        }
 
-        let combo = if current_time >= combo_boundary_time {
-            1
-        } else {
-            member.combo.unwrap() + 1
-        };
-        let earned_point = earned_attendance_point(rank, combo);
-        let new_score = member.score.unwrap() + earned_point;
-        // Update Member
-        member.update_data(new_score, combo, current_time);
-        self.update_member_info(member).await?;
-
-        // Update DailyAttendance
-        let _ = sqlx::query!(
-            "INSERT INTO DailyAttendance (guild_id, user_id, hit_message, hit_time) VALUES (?, ?, ?, ?)",
-            gid, uid, message, current_time
-        ).execute(&self.database).await.map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
-
-        // Insert AttendanceHistory
-        let _ = sqlx::query!(
-            "INSERT INTO AttendanceHistory (guild_id, user_id, hit_message, hit_time, hit_score, hit_combo, hit_rank)
-                VALUES (?, ?, ?, ?, ?, ?, ?)",
-            gid, uid, message, current_time, new_score, combo, rank
-        ).execute(&self.database).await.map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
-        Ok(earned_point)
+        Ok((ranking, content))
     }
 
-    async fn command_token_issue(&self, member: &NalgangMember) -> Result<String, NalgangError> {
-        let token = utils::generate_random_bytes();
-        let r = sqlx::query!(
-            "insert or ignore into Token (guild_id, user_id, token) VALUES (?, ?, ?)",
-            member.gid,
-            member.uid,
-            token
-        )
-        .execute(&self.database)
-        .await
-        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
+    async fn history_collect(
+        &self,
+        member: &mut NalgangMember,
+        page: i64,
+    ) -> Result<(HistoryPage, String), NalgangError> {
+        let history = self
+            .nalgang
+            .history_page(member, page, HISTORY_PAGE_SIZE)
+            .await?;
 
-        match r.rows_affected() {
-            1 => Ok(token),
-            0 => Err(nalgang_error!(NalgangErrorInner::DuplidateTokenIssue)),
-            _ => unreachable!(),
+        let mut content = String::new();
+        for entry in history.entries.iter() {
+            write!(
+                &mut content,
+                "{} {}등 {}점 {}일 연속",
+                history.date(entry).format("%Y/%m/%d"),
+                entry.hit_rank + 1,
+                entry.hit_point,
+                entry.hit_combo
+            )
+            .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+            match entry.hit_message.as_deref() {
+                Some(message) if !message.is_empty() => writeln!(&mut content, ": {}", message),
+                _ => writeln!(&mut content),
+            }
+            .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+        }
+        if content.is_empty() {
+            content.push_str("날갱한 기록이 없습니다.");
         }
+
+        Ok((history, content))
     }
 
-    async fn command_token_delete(&self, member: &NalgangMember) -> Result<bool, NalgangError> {
-        let r = sqlx::query!(
-            "delete from Token where guild_id=? and user_id=?",
-            member.gid,
-            member.uid
-        )
-        .execute(&self.database)
-        .await
-        .map_err(|e| nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e)))?;
-        match r.rows_affected() {
-            1 => Ok(true),
-            0 => Ok(false),
-            _ => unreachable!(),
+    // Renders the heatmap of `member`. Failures only drop the image from the response.
+    async fn heatmap(&self, member: &NalgangMember) -> Option<Result<Vec<u8>, png::EncodingError>> {
+        match self.nalgang.attendance_calendar(member, HEATMAP_DAYS).await {
+            Ok(calendar) => Some(render_heatmap(&calendar)),
+            Err(e) => {
+                println!("{}", e);
+                None
+            }
         }
     }
 
-    async fn simple_response(
+    // Renders the recent scores of `member`, if there are any.
+    async fn score_chart(
         &self,
-        ctx: &Context,
-        command: &ApplicationCommandInteraction,
-        message: Result<String, NalgangError>,
-        ephemeral: bool,
-    ) {
-        let content = match message {
-            Ok(s) => s,
+        member: &NalgangMember,
+    ) -> Option<Result<Vec<u8>, png::EncodingError>> {
+        match self.nalgang.score_history(member, SCORE_CHART_DAYS).await {
+            Ok(history) if history.scores.is_empty() => None,
+            Ok(history) => Some(render_line_chart(&history)),
             Err(e) => {
                 println!("{}", e);
-                "오류가 발생했습니다.".to_string()
+                None
             }
+        }
+    }
+
+    // Moves the history message to the page requested by one of its buttons.
+    async fn history_component(&self, ctx: &Context, component: &MessageComponentInteraction) {
+        let mut parts = component.data.custom_id.splitn(3, ':');
+        let (action, page, uid) = (parts.next(), parts.next(), parts.next());
+        let page = page.and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+        let uid = match uid.and_then(|s| s.parse::<i64>().ok()) {
+            Some(uid) => uid,
+            None => return,
+        };
+        let page = match action {
+            Some(HISTORY_PREVIOUS) => page - 1,
+            Some(HISTORY_NEXT) => page + 1,
+            _ => return,
         };
 
-        if let Err(why) = command
-            .create_interaction_response(&ctx.http, |response| {
-                response
-                    .kind(InteractionResponseType::ChannelMessageWithSource)
-                    .interaction_response_data(|message| {
-                        message.content(content).ephemeral(ephemeral)
+        let guild_id = component.guild_id.expect("Expected guild");
+        let mut target = NalgangMember::new(uid, guild_id.0 as i64);
+        let response = match self.history_collect(&mut target, page).await {
+            Ok((history, content)) => {
+                let name = resolve_name(ctx, guild_id, uid, &None, false).await;
+                let heatmap = has_attachment(component, HEATMAP_FILENAME);
+                let score_chart = has_attachment(component, SCORE_CHART_FILENAME);
+                component
+                    .create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|message| {
+                                history_message(
+                                    message,
+                                    &history,
+                                    uid,
+                                    &name,
+                                    content,
+                                    heatmap,
+                                    score_chart,
+                                )
+                            })
                     })
-            })
-            .await
-        {
-            println!("Cannot respond to slash command: {}", why);
+                    .await
+            }
+            Err(e) => {
+                println!("{}", e);
+                component
+                    .create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::ChannelMessageWithSource)
+                            .interaction_response_data(|message| {
+                                message.content("오류가 발생했습니다.").ephemeral(true)
+                            })
+                    })
+                    .await
+            }
+        };
+        if let Err(why) = response {
+            println!("Cannot respond to message component: {}", why);
         }
     }
 
-    async fn today_attendance_collect(
-        &self,
-        context: &Context,
-        guild_id: i64,
-        current_time: Timestamp,
-    ) -> Result<String, NalgangError> {
-        let boundary_time = timestamp_round_down(current_time.unix_timestamp());
-
-        let record = sqlx::query!(
-            "SELECT user_id, hit_message FROM DailyAttendance WHERE guild_id=? AND hit_time >= ?",
-            guild_id,
-            boundary_time
-        )
-        .fetch_all(&self.database)
-        .await;
+    // Moves the ranking message to the page requested by one of its buttons.
+    async fn ranking_component(&self, ctx: &Context, component: &MessageComponentInteraction) {
+        let member = component.member.as_ref().expect("Expected guild member");
+        let mut nalgang_member = nalgang_member(member);
 
-        match record {
-            Ok(rec) => {
-                let mut content = String::new();
-                let guild = GuildId(guild_id as u64);
-                for (index, row) in rec.iter().enumerate() {
-                    let user = UserId(row.user_id as u64);
-                    let member = guild.member(context, user).await.unwrap();
-                    let user_name = member.display_name();
+        let mut parts = component.data.custom_id.splitn(4, ':');
+        let (action, page, kind, period) = (parts.next(), parts.next(), parts.next(), parts.next());
+        let page = page.and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+        let kind = kind
+            .and_then(|s| s.parse::<RankingKind>().ok())
+            .unwrap_or(RankingKind::Score);
+        let period = period
+            .and_then(|s| s.parse::<Period>().ok())
+            .unwrap_or(Period::AllTime);
+        let page = match action {
+            Some(RANKING_PREVIOUS) => Ok(page - 1),
+            Some(RANKING_NEXT) => Ok(page + 1),
+            Some(RANKING_ME) => self
+                .nalgang
+                .member_rank(&mut nalgang_member, kind, period)
+                .await
+                .map(|rank| rank / RANKING_PAGE_SIZE),
+            _ => return,
+        };
 
-                    let message = row.hit_message.clone().unwrap_or_default();
-                    writeln!(&mut content, "{}. {}: {}", index + 1, user_name, message)
-                        .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
-                }
-                Ok(content)
+        let result = match page {
+            Ok(page) => {
+                self.ranking_collect(ctx, &nalgang_member, kind, period, page)
+                    .await
             }
-            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
-        }
-    }
-
-    async fn ranking_collect(&self, context: &Context, gid: i64) -> Result<String, NalgangError> {
-        let record = sqlx::query!(
-            "SELECT user_id, score FROM Member WHERE guild_id=? ORDER BY score DESC",
-            gid,
-        )
-        .fetch_all(&self.database)
-        .await;
-        match record {
-            Ok(rec) => {
-                let mut content = String::new();
-                let guild_id = GuildId(gid as u64);
-                for (index, row) in rec.iter().enumerate() {
-                    let user_id = UserId(row.user_id as u64);
-                    let member = guild_id.member(context, user_id).await.unwrap();
-                    let user_name = member.display_name();
-                    writeln!(&mut content, "{}. {}점 {}", index + 1, row.score, user_name)
-                        .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
-                }
-
-                Ok(content)
+            Err(e) => Err(e),
+        };
+        let chart = has_attachment(component, RANKING_CHART_FILENAME);
+        let response = match result {
+            Ok((ranking, content)) => {
+                component
+                    .create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|message| {
+                                ranking_message(message, &ranking, content, chart)
+                            })
+                    })
+                    .await
+            }
+            Err(e) => {
+                let content = match e.kind {
+                    NalgangErrorInner::MemberNotExist => "등록되지 않은 계정입니다.",
+                    NalgangErrorInner::NotRanked => "해당 기간에 날갱한 기록이 없습니다.",
+                    _ => {
+                        println!("{}", e);
+                        "오류가 발생했습니다."
+                    }
+                };
+                component
+                    .create_interaction_response(&ctx.http, |response| {
+                        response
+                            .kind(InteractionResponseType::ChannelMessageWithSource)
+                            .interaction_response_data(|message| {
+                                message.content(content).ephemeral(true)
+                            })
+                    })
+                    .await
             }
-            Err(e) => Err(nalgang_error!(NalgangErrorInner::UnhandledDatabaseError(e))),
+        };
+        if let Err(why) = response {
+            println!("Cannot respond to message component: {}", why);
         }
     }
 }
@@ -451,7 +664,7 @@ impl Handler {
 impl EventHandler for Handler {
     async fn guild_create(&self, _ctx: Context, guild: Guild, is_new: bool) {
         if is_new {
-            match self.register_guild(guild.id.0 as i64).await {
+            match self.nalgang.register_guild(guild.id.0 as i64).await {
                 Ok(()) => (),
                 Err(e) => {
                     println!("{}", e)
@@ -460,13 +673,43 @@ impl EventHandler for Handler {
         }
     }
 
+    async fn guild_member_addition(&self, _ctx: Context, new_member: Member) {
+        self.update_profile(
+            &nalgang_member(&new_member),
+            &new_member.display_name(),
+            false,
+        )
+        .await;
+    }
+
+    async fn guild_member_update(&self, _ctx: Context, _old: Option<Member>, new: Member) {
+        self.update_profile(&nalgang_member(&new), &new.display_name(), false)
+            .await;
+    }
+
+    async fn guild_member_removal(
+        &self,
+        _ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        member: Option<Member>,
+    ) {
+        let display_name = match member {
+            Some(member) => member.display_name().into_owned(),
+            None => user.name.clone(),
+        };
+        let departed_member = NalgangMember::new(user.id.0 as i64, guild_id.0 as i64);
+        self.update_profile(&departed_member, &display_name, true)
+            .await;
+    }
+
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         if let Interaction::ApplicationCommand(command) = interaction {
             let member = command.member.as_ref().expect("Expected guild member");
-            let mut nalgang_member = NalgangMember::new(member);
+            let mut nalgang_member = nalgang_member(member);
             match command.data.name.as_str() {
                 "서버등록" => {
-                    let res = self.register_guild(nalgang_member.gid).await;
+                    let res = self.nalgang.register_guild(nalgang_member.gid).await;
                     let content = match res {
                         Ok(()) => Ok("서버를 등록했습니다.".to_string()),
                         Err(e) => match e.kind {
@@ -479,14 +722,22 @@ impl EventHandler for Handler {
                     self.simple_response(&ctx, &command, content, false).await;
                 }
                 "등록" | "register" => {
-                    let res = self.command_register(&mut nalgang_member).await;
+                    let res = self.nalgang.register(&mut nalgang_member).await;
                     let content = match res {
-                        Ok(()) => Ok("계정을 등록했습니다.".to_string()),
+                        Ok(()) => {
+                            self.update_profile(&nalgang_member, &member.display_name(), false)
+                                .await;
+                            Ok("계정을 등록했습니다.".to_string())
+                        }
                         Err(e) => match e.kind {
                             NalgangErrorInner::DuplicateMemberRegister => Ok(format!(
                                 "{}님은 이미 등록되었습니다.",
                                 member.display_name()
                             )),
+                            NalgangErrorInner::GuildNotExist => {
+                                Ok("등록되지 않은 서버입니다. 먼저 /서버등록 을 해주세요."
+                                    .to_string())
+                            }
                             _ => Err(e),
                         },
                     };
@@ -494,9 +745,7 @@ impl EventHandler for Handler {
                 }
 
                 "날갱" | "nalgang" => {
-                    let interaction_time = command.id.created_at();
-
-                    let message = match command.data.options.get(0) {
+                    let message = match command.data.options.first() {
                         None => String::new(),
                         Some(v) => match v.resolved.as_ref().unwrap() {
                             CommandDataOptionValue::String(s) => s.clone(),
@@ -504,36 +753,41 @@ impl EventHandler for Handler {
                         },
                     };
 
-                    let result = self
-                        .command_nalgang(&mut nalgang_member, interaction_time, message)
-                        .await;
+                    let result = self.nalgang.attend(&mut nalgang_member, &message).await;
                     match result {
-                        Ok(earned_point) => {
-                            let main_message = format!(
+                        Ok(attendance) => {
+                            self.update_profile(&nalgang_member, &member.display_name(), false)
+                                .await;
+                            let mut main_message = format!(
                                 "{}님이 날갱해서 {}점을 얻었습니다!",
                                 member.display_name(),
-                                earned_point
+                                attendance.earned_point
                             );
+                            if !attendance.frozen_days.is_empty() {
+                                main_message.push_str(&format!(
+                                    "\n휴가 {}개를 사용해 {}일 연속 출석을 지켰습니다.",
+                                    attendance.frozen_days.len(),
+                                    attendance.combo
+                                ));
+                            }
+                            if attendance.freeze_earned {
+                                main_message.push_str("\n연속 출석으로 휴가 1개를 얻었습니다.");
+                            }
+                            if !attendance.achievements.is_empty() {
+                                let names: Vec<String> = attendance
+                                    .achievements
+                                    .iter()
+                                    .map(|achievement| format!("🏅 {}", achievement))
+                                    .collect();
+                                main_message
+                                    .push_str(&format!("\n업적 달성: {}", names.join(", ")));
+                            }
 
                             let embed_result = self
-                                .today_attendance_collect(
-                                    &ctx,
-                                    nalgang_member.gid,
-                                    interaction_time,
-                                )
+                                .today_attendance_collect(&ctx, nalgang_member.gid)
                                 .await;
                             match embed_result {
-                                Ok(attendance_embed) => {
-                                    let tz = FixedOffset::east(9 * 3600);
-                                    let date = DateTime::<FixedOffset>::from_utc(
-                                        NaiveDateTime::from_timestamp(
-                                            interaction_time.unix_timestamp(),
-                                            0,
-                                        ),
-                                        tz,
-                                    )
-                                    .date();
-
+                                Ok((date, attendance_embed)) => {
                                     if let Err(why) = command
                                     .create_interaction_response(&ctx.http, |response| {
                                         response
@@ -555,6 +809,23 @@ impl EventHandler for Handler {
                                     self.simple_response(&ctx, &command, Err(e), false).await;
                                 }
                             };
+
+                            // Roles are given after responding, since each role is a request to
+                            // Discord and the interaction must be answered within 3 seconds.
+                            let roles: Vec<i64> =
+                                member.roles.iter().map(|role| role.0 as i64).collect();
+                            match self.nalgang.role_changes(&mut nalgang_member, &roles).await {
+                                Ok(changes) => {
+                                    self.apply_role_changes(
+                                        &ctx,
+                                        nalgang_member.gid,
+                                        nalgang_member.uid,
+                                        &changes,
+                                    )
+                                    .await;
+                                }
+                                Err(e) => println!("{}", e),
+                            }
                         }
                         Err(e) => {
                             let content = match e.kind {
@@ -571,28 +842,18 @@ impl EventHandler for Handler {
                     }
                 }
                 "점수" => {
-                    let (mut target_member, name) = match command.data.options.get(0) {
-                        None => (nalgang_member, member.display_name()),
+                    let (mut target_member, name) = match command.data.options.first() {
+                        None => (nalgang_member, member.display_name().into_owned()),
                         Some(value) => match value.resolved.as_ref().unwrap() {
-                            CommandDataOptionValue::User(user, pm) => {
-                                let display_name = match pm {
-                                    Some(inner) => match inner.nick.as_ref() {
-                                        Some(s) => Cow::Borrowed(s),
-                                        None => Cow::Owned(user.name.clone()),
-                                    },
-                                    None => Cow::Owned(user.name.clone()),
-                                };
-
-                                (
-                                    NalgangMember::new_explict(user.id, member.guild_id),
-                                    display_name,
-                                )
-                            }
+                            CommandDataOptionValue::User(user, pm) => (
+                                NalgangMember::new(user.id.0 as i64, nalgang_member.gid),
+                                resolved_display_name(user, pm).to_string(),
+                            ),
                             _ => unreachable!(),
                         },
                     };
 
-                    let content = match self.command_point(&mut target_member).await {
+                    let content = match self.nalgang.point(&mut target_member).await {
                         Ok(()) => Ok(format!(
                             "{}님의 점수는 {}점입니다. {}연속 출석중입니다.",
                             name,
@@ -608,20 +869,43 @@ impl EventHandler for Handler {
                     };
                     self.simple_response(&ctx, &command, content, false).await;
                 }
-                "랭킹" => {
-                    let ranking_result = self.ranking_collect(&ctx, nalgang_member.gid).await;
-                    match ranking_result {
-                        Ok(ranking_result) => {
+                "기록" => {
+                    let (mut target_member, name) = match command.data.options.first() {
+                        None => (nalgang_member, member.display_name().into_owned()),
+                        Some(value) => match value.resolved.as_ref().unwrap() {
+                            CommandDataOptionValue::User(user, pm) => (
+                                NalgangMember::new(user.id.0 as i64, nalgang_member.gid),
+                                resolved_display_name(user, pm).to_string(),
+                            ),
+                            _ => unreachable!(),
+                        },
+                    };
+
+                    match self.history_collect(&mut target_member, 0).await {
+                        Ok((history, content)) => {
+                            let heatmap = self.heatmap(&target_member).await;
+                            let score_chart = self.score_chart(&target_member).await;
                             if let Err(why) = command
                                 .create_interaction_response(&ctx.http, |response| {
                                     response
                                         .kind(InteractionResponseType::ChannelMessageWithSource)
                                         .interaction_response_data(|message| {
-                                            message.embed(|create_embed| {
-                                                create_embed
-                                                    .title("랭킹")
-                                                    .description(ranking_result)
-                                            })
+                                            let heatmap =
+                                                add_image(message, heatmap, HEATMAP_FILENAME);
+                                            let score_chart = add_image(
+                                                message,
+                                                score_chart,
+                                                SCORE_CHART_FILENAME,
+                                            );
+                                            history_message(
+                                                message,
+                                                &history,
+                                                target_member.uid,
+                                                &name,
+                                                content,
+                                                heatmap,
+                                                score_chart,
+                                            )
                                         })
                                 })
                                 .await
@@ -630,53 +914,1086 @@ impl EventHandler for Handler {
                             }
                         }
                         Err(e) => {
-                            println!("{}", e);
-                            self.simple_response(&ctx, &command, Err(e), false).await;
+                            let content = match e.kind {
+                                NalgangErrorInner::MemberNotExist => {
+                                    Ok("등록되지 않은 계정입니다.".to_string())
+                                }
+                                _ => Err(e),
+                            };
+                            self.simple_response(&ctx, &command, content, false).await;
                         }
                     }
                 }
-                "토큰발급" => {
-                    let content = match self.command_token_issue(&nalgang_member).await {
-                        Ok(token) => Ok(format!("토큰이 발급되었습니다: {}", token)),
-                        Err(e) => match e.kind {
-                            NalgangErrorInner::DuplidateTokenIssue => {
-                                Ok("이미 발급된 토큰이 존재합니다.".to_string())
-                            }
-                            _ => Err(e),
-                        },
+                "랭킹" => {
+                    let option_str = |name: &str| {
+                        command
+                            .data
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| match option.resolved.as_ref() {
+                                Some(CommandDataOptionValue::String(s)) => Some(s.as_str()),
+                                _ => None,
+                            })
                     };
-                    self.simple_response(&ctx, &command, content, true).await;
-                }
-                "토큰삭제" => {
-                    let content = match self.command_token_delete(&nalgang_member).await {
-                        Ok(b) => {
-                            if b {
-                                Ok("토큰이 삭제되었습니다.".to_string())
-                            } else {
-                                Ok("발급된 토큰이 없습니다.".to_string())
+                    // A date range takes precedence over the named periods.
+                    let period = match (option_str("시작"), option_str("끝")) {
+                        (None, None) => match option_str("기간") {
+                            Some(s) => s.parse::<Period>().ok(),
+                            None => Some(Period::AllTime),
+                        },
+                        (Some(start), Some(end)) => {
+                            match (Period::parse_date(start), Period::parse_date(end)) {
+                                (Some(start), Some(end)) => Period::custom(start, end),
+                                _ => None,
                             }
                         }
-                        Err(e) => Err(e),
+                        _ => None,
                     };
-                    self.simple_response(&ctx, &command, content, true).await;
-                }
-                _ => {
-                    self.simple_response(
-                        &ctx,
-                        &command,
-                        Ok("개발 중인 기능입니다.".to_string()),
-                        false,
-                    )
-                    .await;
-                }
-            };
-        }
-    }
 
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+                    let kind = option_str("종류")
+                        .and_then(|s| s.parse::<RankingKind>().ok())
+                        .unwrap_or(RankingKind::Score);
 
-        let _commands = Command::set_global_application_commands(
+                    let ranking_result = match period {
+                        Some(period) => {
+                            self.ranking_collect(&ctx, &nalgang_member, kind, period, 0)
+                                .await
+                        }
+                        None => Err(nalgang_error!(NalgangErrorInner::InvalidPeriod)),
+                    };
+                    match ranking_result {
+                        Ok((ranking, content)) => {
+                            // Ranks of the first page are the top scores.
+                            let chart = match ranking.kind {
+                                RankingKind::Score
+                                    if ranking.page == 0 && !ranking.entries.is_empty() =>
+                                {
+                                    let bars: Vec<(i64, i64)> = ranking
+                                        .entries
+                                        .iter()
+                                        .enumerate()
+                                        .map(|(i, entry)| (i as i64, entry.score))
+                                        .collect();
+                                    Some(render_bar_chart(&bars))
+                                }
+                                _ => None,
+                            };
+                            if let Err(why) = command
+                                .create_interaction_response(&ctx.http, |response| {
+                                    response
+                                        .kind(InteractionResponseType::ChannelMessageWithSource)
+                                        .interaction_response_data(|message| {
+                                            let chart =
+                                                add_image(message, chart, RANKING_CHART_FILENAME);
+                                            ranking_message(message, &ranking, content, chart)
+                                        })
+                                })
+                                .await
+                            {
+                                println!("Cannot respond to slash command: {}", why)
+                            }
+                        }
+                        Err(e) => {
+                            let content = match e.kind {
+                                NalgangErrorInner::InvalidPeriod => {
+                                    Ok("시작과 끝을 모두 2024-01-01 형식으로 입력해주세요."
+                                        .to_string())
+                                }
+                                _ => Err(e),
+                            };
+                            self.simple_response(&ctx, &command, content, true).await;
+                        }
+                    }
+                }
+                "보내기" => {
+                    let (mut receiver, name) = match command
+                        .data
+                        .options
+                        .iter()
+                        .find(|option| option.name == "이름")
+                        .and_then(|option| option.resolved.as_ref())
+                    {
+                        Some(CommandDataOptionValue::User(user, pm)) => (
+                            NalgangMember::new(user.id.0 as i64, nalgang_member.gid),
+                            resolved_display_name(user, pm),
+                        ),
+                        _ => unreachable!(),
+                    };
+                    let amount = match command
+                        .data
+                        .options
+                        .iter()
+                        .find(|option| option.name == "점수")
+                        .and_then(|option| option.resolved.as_ref())
+                    {
+                        Some(CommandDataOptionValue::Integer(i)) => *i,
+                        _ => unreachable!(),
+                    };
+
+                    let result = self
+                        .nalgang
+                        .transfer(&mut nalgang_member, &mut receiver, amount)
+                        .await;
+                    let content = match result {
+                        Ok(()) => Ok(format!(
+                            "{}님이 {}님에게 {}점을 보냈습니다. 남은 점수는 {}점입니다.",
+                            member.display_name(),
+                            name,
+                            amount,
+                            nalgang_member.score.unwrap()
+                        )),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::SelfTransfer => {
+                                Ok("자신에게는 점수를 보낼 수 없습니다.".to_string())
+                            }
+                            NalgangErrorInner::MemberNotExist => {
+                                Ok("등록되지 않은 계정입니다.".to_string())
+                            }
+                            NalgangErrorInner::ReceiverNotExist => {
+                                Ok(format!("{}님은 등록되지 않은 계정입니다.", name))
+                            }
+                            NalgangErrorInner::InsufficientScore => {
+                                Ok("점수가 부족합니다.".to_string())
+                            }
+                            NalgangErrorInner::InvalidTransferAmount => {
+                                Ok("보낼 점수는 1점 이상이어야 합니다.".to_string())
+                            }
+                            _ => Err(e),
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, false).await;
+                }
+                "휴가" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let content = match subcommand.name.as_str() {
+                        "구매" => {
+                            let count = match subcommand
+                                .options
+                                .first()
+                                .and_then(|option| option.resolved.as_ref())
+                            {
+                                Some(CommandDataOptionValue::Integer(i)) => *i,
+                                _ => 1,
+                            };
+                            match self.nalgang.buy_freezes(&mut nalgang_member, count).await {
+                                Ok(rule) => Ok(format!(
+                                    "휴가 {}개를 {}점에 샀습니다. 휴가 {}개, 남은 점수는 {}점입니다.",
+                                    count,
+                                    rule.price * count,
+                                    nalgang_member.freezes.unwrap(),
+                                    nalgang_member.score.unwrap()
+                                )),
+                                Err(e) => match e.kind {
+                                    NalgangErrorInner::MemberNotExist => {
+                                        Ok("등록되지 않은 계정입니다.".to_string())
+                                    }
+                                    NalgangErrorInner::InsufficientScore => {
+                                        Ok("점수가 부족합니다.".to_string())
+                                    }
+                                    NalgangErrorInner::FreezeLimitExceeded => {
+                                        Ok("더 이상 휴가를 가질 수 없습니다.".to_string())
+                                    }
+                                    NalgangErrorInner::InvalidFreezeCount => {
+                                        Ok("휴가는 1개 이상 사야 합니다.".to_string())
+                                    }
+                                    _ => Err(e),
+                                },
+                            }
+                        }
+                        _ => match self.nalgang.point(&mut nalgang_member).await {
+                            Ok(()) => self
+                                .nalgang
+                                .scoring_rule(nalgang_member.gid, ScoringRuleChange::Show)
+                                .await
+                                .map(|rule| {
+                                    format!(
+                                        "휴가 {}개를 가지고 있습니다. 날갱하지 못한 날마다 1개씩 사용해 연속 출석을 지킵니다.\n{}",
+                                        nalgang_member.freezes.unwrap(),
+                                        rule
+                                    )
+                                }),
+                            Err(e) => match e.kind {
+                                NalgangErrorInner::MemberNotExist => {
+                                    Ok("등록되지 않은 계정입니다.".to_string())
+                                }
+                                _ => Err(e),
+                            },
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "상점" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let content = match subcommand.name.as_str() {
+                        "구매" => {
+                            let name = match subcommand
+                                .options
+                                .first()
+                                .and_then(|option| option.resolved.as_ref())
+                            {
+                                Some(CommandDataOptionValue::String(s)) => s.clone(),
+                                _ => unreachable!(),
+                            };
+                            match self.nalgang.buy_item(&mut nalgang_member, &name).await {
+                                Ok(item) => {
+                                    let granted = match item.role_id {
+                                        Some(role_id) => {
+                                            ctx.http
+                                                .add_member_role(
+                                                    nalgang_member.gid as u64,
+                                                    nalgang_member.uid as u64,
+                                                    role_id as u64,
+                                                    Some("날갱 상점 구매"),
+                                                )
+                                                .await
+                                        }
+                                        None => Ok(()),
+                                    };
+                                    match granted {
+                                        Ok(()) => {
+                                            let mut content = format!(
+                                                "{}님이 {}을(를) {}점에 샀습니다. 남은 점수는 {}점입니다.",
+                                                member.display_name(),
+                                                item.name,
+                                                item.price,
+                                                nalgang_member.score.unwrap()
+                                            );
+                                            if let Some(expire_time) = item.expire_time {
+                                                content.push_str(&format!(
+                                                    "\n<t:{}:f>까지 사용할 수 있습니다.",
+                                                    expire_time
+                                                ));
+                                            }
+                                            Ok(content)
+                                        }
+                                        Err(why) => {
+                                            println!("Cannot grant role: {}", why);
+                                            self.nalgang
+                                                .cancel_purchase(
+                                                    &mut nalgang_member,
+                                                    item.purchase_id,
+                                                )
+                                                .await
+                                                .map(|_| {
+                                                    "역할을 줄 수 없어 구매를 취소했습니다. 봇의 역할 관리 권한을 확인해주세요."
+                                                        .to_string()
+                                                })
+                                        }
+                                    }
+                                }
+                                Err(e) => match e.kind {
+                                    NalgangErrorInner::MemberNotExist => {
+                                        Ok("등록되지 않은 계정입니다.".to_string())
+                                    }
+                                    NalgangErrorInner::ShopItemNotExist => {
+                                        Ok(format!("{}은(는) 상점에 없는 물건입니다.", name))
+                                    }
+                                    NalgangErrorInner::OutOfStock => {
+                                        Ok(format!("{}은(는) 모두 팔렸습니다.", name))
+                                    }
+                                    NalgangErrorInner::InsufficientScore => {
+                                        Ok("점수가 부족합니다.".to_string())
+                                    }
+                                    _ => Err(e),
+                                },
+                            }
+                        }
+                        _ => self
+                            .nalgang
+                            .shop_items(nalgang_member.gid)
+                            .await
+                            .map(|items| match items.is_empty() {
+                                true => "상점에 물건이 없습니다.".to_string(),
+                                false => items
+                                    .iter()
+                                    .map(|item| item.to_string())
+                                    .collect::<Vec<String>>()
+                                    .join("\n"),
+                            }),
+                    };
+                    self.simple_response(&ctx, &command, content, false).await;
+                }
+                "상점관리" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let option_value = |name: &str| {
+                        subcommand
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| option.resolved.as_ref())
+                    };
+                    let integer = |name: &str| match option_value(name) {
+                        Some(CommandDataOptionValue::Integer(i)) => Some(*i),
+                        _ => None,
+                    };
+                    let name = match option_value("이름") {
+                        Some(CommandDataOptionValue::String(s)) => s.trim().to_string(),
+                        _ => unreachable!(),
+                    };
+
+                    let content = match subcommand.name.as_str() {
+                        "추가" => {
+                            let item = ShopItem {
+                                name,
+                                price: integer("가격").unwrap_or_default(),
+                                stock: integer("재고"),
+                                role_id: match option_value("역할") {
+                                    Some(CommandDataOptionValue::Role(role)) => {
+                                        Some(role.id.0 as i64)
+                                    }
+                                    _ => None,
+                                },
+                                duration: integer("기간"),
+                            };
+                            match self.nalgang.set_shop_item(nalgang_member.gid, &item).await {
+                                Ok(()) => Ok(format!("상점에 올렸습니다. {}", item)),
+                                Err(e) => match e.kind {
+                                    NalgangErrorInner::InvalidShopItem => {
+                                        Ok("올바르지 않은 물건입니다.".to_string())
+                                    }
+                                    _ => Err(e),
+                                },
+                            }
+                        }
+                        "삭제" => self
+                            .nalgang
+                            .delete_shop_item(nalgang_member.gid, &name)
+                            .await
+                            .map(|deleted| match deleted {
+                                true => format!("{}을(를) 상점에서 내렸습니다.", name),
+                                false => format!("{}은(는) 상점에 없는 물건입니다.", name),
+                            }),
+                        _ => unreachable!(),
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "인벤토리" => {
+                    let (target_member, name) = match command.data.options.first() {
+                        None => (nalgang_member, member.display_name().into_owned()),
+                        Some(value) => match value.resolved.as_ref().unwrap() {
+                            CommandDataOptionValue::User(user, pm) => (
+                                NalgangMember::new(user.id.0 as i64, nalgang_member.gid),
+                                resolved_display_name(user, pm).to_string(),
+                            ),
+                            _ => unreachable!(),
+                        },
+                    };
+
+                    let content = self.nalgang.inventory(&target_member).await.map(|items| {
+                        if items.is_empty() {
+                            return format!("{}님은 가진 물건이 없습니다.", name);
+                        }
+                        let mut content = format!("{}님의 인벤토리", name);
+                        for item in items {
+                            content.push_str(&format!(
+                                "\n{}: <t:{}:d>에 {}점",
+                                item.name, item.purchase_time, item.price
+                            ));
+                            if let Some(role_id) = item.role_id {
+                                content.push_str(&format!(", <@&{}> 역할", role_id));
+                            }
+                            if let Some(expire_time) = item.expire_time {
+                                content.push_str(&format!(", <t:{}:R> 만료", expire_time));
+                            }
+                        }
+                        content
+                    });
+                    self.simple_response(&ctx, &command, content, false).await;
+                }
+                "역할보상" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let option_value = |name: &str| {
+                        subcommand
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| option.resolved.as_ref())
+                    };
+                    let role_id = match option_value("역할") {
+                        Some(CommandDataOptionValue::Role(role)) => Some(role.id.0 as i64),
+                        _ => None,
+                    };
+
+                    let content = match subcommand.name.as_str() {
+                        "추가" => {
+                            let reward = RoleReward {
+                                role_id: role_id.expect("Expected role"),
+                                kind: match option_value("종류") {
+                                    Some(CommandDataOptionValue::String(kind)) => {
+                                        RewardKind::from_kind(kind).unwrap_or(RewardKind::Score)
+                                    }
+                                    _ => RewardKind::Score,
+                                },
+                                threshold: match option_value("기준") {
+                                    Some(CommandDataOptionValue::Integer(i)) => *i,
+                                    _ => 0,
+                                },
+                                revoke: matches!(
+                                    option_value("회수"),
+                                    Some(CommandDataOptionValue::Boolean(true))
+                                ),
+                            };
+                            match self.nalgang.set_role_reward(nalgang_member.gid, &reward).await {
+                                Ok(()) => Ok(format!(
+                                    "역할 보상을 설정했습니다. {}\n기존 멤버에게 주려면 동기화해주세요.",
+                                    reward
+                                )),
+                                Err(e) => match e.kind {
+                                    NalgangErrorInner::InvalidRoleReward => {
+                                        Ok("기준은 1 이상이어야 합니다.".to_string())
+                                    }
+                                    _ => Err(e),
+                                },
+                            }
+                        }
+                        "삭제" => self
+                            .nalgang
+                            .delete_role_reward(nalgang_member.gid, role_id.expect("Expected role"))
+                            .await
+                            .map(|deleted| match deleted {
+                                true => {
+                                    "역할 보상을 삭제했습니다. 이미 받은 역할은 그대로 남습니다."
+                                        .to_string()
+                                }
+                                false => "해당 역할 보상이 없습니다.".to_string(),
+                            }),
+                        "동기화" => {
+                            // Reading every member can take longer than an interaction response allows.
+                            if let Err(why) = command
+                                .create_interaction_response(&ctx.http, |response| {
+                                    response
+                                        .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                                        .interaction_response_data(|message| message.ephemeral(true))
+                                })
+                                .await
+                            {
+                                println!("Cannot respond to slash command: {}", why);
+                                return;
+                            }
+                            let content = match self.reconcile_roles(&ctx, nalgang_member.gid).await
+                            {
+                                Ok(changed) => format!("역할 {}개를 주거나 회수했습니다.", changed),
+                                Err(e) => {
+                                    println!("{}", e);
+                                    "오류가 발생했습니다.".to_string()
+                                }
+                            };
+                            if let Err(why) = command
+                                .edit_original_interaction_response(&ctx.http, |response| {
+                                    response.content(content)
+                                })
+                                .await
+                            {
+                                println!("Cannot respond to slash command: {}", why);
+                            }
+                            return;
+                        }
+                        _ => self
+                            .nalgang
+                            .role_rewards(nalgang_member.gid)
+                            .await
+                            .map(|rewards| match rewards.is_empty() {
+                                true => "역할 보상이 없습니다.".to_string(),
+                                false => rewards
+                                    .iter()
+                                    .map(|reward| reward.to_string())
+                                    .collect::<Vec<String>>()
+                                    .join("\n"),
+                            }),
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "업적" => {
+                    let (target_member, name) = match command.data.options.first() {
+                        None => (nalgang_member, member.display_name().into_owned()),
+                        Some(value) => match value.resolved.as_ref().unwrap() {
+                            CommandDataOptionValue::User(user, pm) => (
+                                NalgangMember::new(user.id.0 as i64, nalgang_member.gid),
+                                resolved_display_name(user, pm).to_string(),
+                            ),
+                            _ => unreachable!(),
+                        },
+                    };
+
+                    let content = self
+                        .nalgang
+                        .achievements(&target_member)
+                        .await
+                        .map(|achieved| {
+                            let mut content = format!(
+                                "{}님의 업적 {}/{}",
+                                name,
+                                achieved.len(),
+                                Achievement::ALL.len()
+                            );
+                            for (achievement, achieved_time) in achieved.iter() {
+                                content.push_str(&format!(
+                                    "\n🏅 **{}** {} <t:{}:d>",
+                                    achievement,
+                                    achievement.description(),
+                                    achieved_time
+                                ));
+                            }
+                            for achievement in Achievement::ALL.iter().filter(|achievement| {
+                                !achieved.iter().any(|(a, _)| a == *achievement)
+                            }) {
+                                content.push_str(&format!(
+                                    "\n🔒 {} {}",
+                                    achievement,
+                                    achievement.description()
+                                ));
+                            }
+                            content
+                        });
+                    self.simple_response(&ctx, &command, content, false).await;
+                }
+                "업적재계산" => {
+                    let content = self
+                        .nalgang
+                        .backfill_achievements(nalgang_member.gid)
+                        .await
+                        .map(|granted| {
+                            format!(
+                                "날갱 기록으로 업적을 다시 계산해 {}개를 새로 주었습니다.",
+                                granted
+                            )
+                        });
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "점수조정" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let option_value = |name: &str| {
+                        subcommand
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| option.resolved.as_ref())
+                    };
+                    let (mut target, name) = match option_value("이름") {
+                        Some(CommandDataOptionValue::User(user, pm)) => (
+                            NalgangMember::new(user.id.0 as i64, nalgang_member.gid),
+                            resolved_display_name(user, pm).to_string(),
+                        ),
+                        _ => unreachable!(),
+                    };
+                    let field = match option_value("항목") {
+                        Some(CommandDataOptionValue::String(kind)) => {
+                            AdjustField::from_kind(kind).unwrap_or(AdjustField::Score)
+                        }
+                        _ => AdjustField::Score,
+                    };
+                    let value = match option_value("값") {
+                        Some(CommandDataOptionValue::Integer(i)) => *i,
+                        _ => unreachable!(),
+                    };
+                    let reason = match option_value("사유") {
+                        Some(CommandDataOptionValue::String(s)) => s.as_str(),
+                        _ => "",
+                    };
+                    let adjustment = match subcommand.name.as_str() {
+                        "더하기" => Adjustment::Add(value),
+                        "빼기" => Adjustment::Add(-value),
+                        _ => Adjustment::Set(value),
+                    };
+
+                    let result = self
+                        .nalgang
+                        .adjust_member(&nalgang_member, &mut target, field, adjustment, reason)
+                        .await;
+                    let content = match result {
+                        Ok(entry) => Ok(format!(
+                            "{}님의 {}을(를) {}에서 {}(으)로 바꿨습니다. 사유: {}",
+                            name, entry.field, entry.before, entry.after, entry.reason
+                        )),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::MemberNotExist => {
+                                Ok(format!("{}님은 등록되지 않은 계정입니다.", name))
+                            }
+                            NalgangErrorInner::InvalidAdjustment => {
+                                Ok("연속 출석은 0일보다 작을 수 없습니다.".to_string())
+                            }
+                            NalgangErrorInner::MissingAuditReason => {
+                                Ok("사유를 입력해주세요.".to_string())
+                            }
+                            _ => Err(e),
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "감사로그" => {
+                    let limit = match command
+                        .data
+                        .options
+                        .first()
+                        .and_then(|option| option.resolved.as_ref())
+                    {
+                        Some(CommandDataOptionValue::Integer(i)) => *i,
+                        _ => AUDIT_LOG_SIZE,
+                    };
+                    let content =
+                        self.nalgang
+                            .audit_log(nalgang_member.gid, limit)
+                            .await
+                            .map(|entries| {
+                                if entries.is_empty() {
+                                    return "기록된 변경이 없습니다.".to_string();
+                                }
+                                // Older entries which do not fit in a message are left out.
+                                let mut content = String::new();
+                                for entry in entries.iter() {
+                                    let line = format!(
+                                        "<t:{}:f> <@{}> → <@{}> {} {} → {}: {}\n",
+                                        entry.event_time,
+                                        entry.actor_id,
+                                        entry.target_id,
+                                        entry.field,
+                                        entry.before,
+                                        entry.after,
+                                        entry.reason
+                                    );
+                                    if content.chars().count() + line.chars().count()
+                                        > MESSAGE_LIMIT
+                                    {
+                                        break;
+                                    }
+                                    content.push_str(&line);
+                                }
+                                content
+                            });
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "내보내기" => {
+                    let format = match command
+                        .data
+                        .options
+                        .first()
+                        .and_then(|option| option.resolved.as_ref())
+                    {
+                        Some(CommandDataOptionValue::String(kind)) => {
+                            ArchiveFormat::from_kind(kind).unwrap_or(ArchiveFormat::Json)
+                        }
+                        _ => ArchiveFormat::Json,
+                    };
+                    // Reading the whole history can take longer than an interaction response allows.
+                    if let Err(why) = command
+                        .create_interaction_response(&ctx.http, |response| {
+                            response
+                                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                                .interaction_response_data(|message| message.ephemeral(true))
+                        })
+                        .await
+                    {
+                        println!("Cannot respond to slash command: {}", why);
+                        return;
+                    }
+                    let (content, file) = match self.nalgang.export_guild(nalgang_member.gid).await
+                    {
+                        Ok(archive) => (
+                            format!(
+                                "멤버 {}명과 날갱 기록 {}개를 내보냈습니다.",
+                                archive.members.len(),
+                                archive.history.len()
+                            ),
+                            Some(AttachmentType::Bytes {
+                                data: archive.encode(format).into_bytes().into(),
+                                filename: format!(
+                                    "nalgang-{}.{}",
+                                    nalgang_member.gid,
+                                    format.kind()
+                                ),
+                            }),
+                        ),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::GuildNotExist => {
+                                ("등록되지 않은 서버입니다.".to_string(), None)
+                            }
+                            _ => {
+                                println!("{}", e);
+                                ("오류가 발생했습니다.".to_string(), None)
+                            }
+                        },
+                    };
+                    if let Err(why) = command
+                        .create_followup_message(&ctx.http, |message| {
+                            if let Some(file) = file {
+                                message.add_file(file);
+                            }
+                            message.content(content).ephemeral(true)
+                        })
+                        .await
+                    {
+                        println!("Cannot respond to slash command: {}", why);
+                    }
+                }
+                "가져오기" => {
+                    let option_value = |name: &str| {
+                        command
+                            .data
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| option.resolved.as_ref())
+                    };
+                    let attachment = match option_value("파일") {
+                        Some(CommandDataOptionValue::Attachment(attachment)) => attachment,
+                        _ => unreachable!(),
+                    };
+                    let policy = match option_value("정책") {
+                        Some(CommandDataOptionValue::String(kind)) => {
+                            ConflictPolicy::from_kind(kind).unwrap_or(ConflictPolicy::Skip)
+                        }
+                        _ => ConflictPolicy::Skip,
+                    };
+                    if let Err(why) = command
+                        .create_interaction_response(&ctx.http, |response| {
+                            response
+                                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                                .interaction_response_data(|message| message.ephemeral(true))
+                        })
+                        .await
+                    {
+                        println!("Cannot respond to slash command: {}", why);
+                        return;
+                    }
+
+                    let content = match attachment.download().await {
+                        Ok(data) => match String::from_utf8(data) {
+                            Ok(data) => {
+                                self.import_archive(nalgang_member.gid, &data, policy).await
+                            }
+                            Err(_) => "UTF-8 텍스트 파일이 아닙니다.".to_string(),
+                        },
+                        Err(why) => {
+                            println!("Cannot download attachment: {}", why);
+                            "파일을 받지 못했습니다.".to_string()
+                        }
+                    };
+                    if let Err(why) = command
+                        .edit_original_interaction_response(&ctx.http, |response| {
+                            response.content(content)
+                        })
+                        .await
+                    {
+                        println!("Cannot respond to slash command: {}", why);
+                    }
+                }
+                "설정" => {
+                    let timezone = command
+                        .data
+                        .options
+                        .iter()
+                        .find(|option| option.name == "시간대")
+                        .and_then(|option| match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::String(s)) => Some(s.as_str()),
+                            _ => None,
+                        });
+                    let day_start_hour = command
+                        .data
+                        .options
+                        .iter()
+                        .find(|option| option.name == "시작시각")
+                        .and_then(|option| match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::Integer(i)) => Some(*i),
+                            _ => None,
+                        });
+                    let hide_departed = command
+                        .data
+                        .options
+                        .iter()
+                        .find(|option| option.name == "탈퇴자숨김")
+                        .and_then(|option| match option.resolved.as_ref() {
+                            Some(CommandDataOptionValue::Boolean(b)) => Some(*b),
+                            _ => None,
+                        });
+                    // Turning the summary off takes precedence over a new channel.
+                    let summary_channel = command
+                        .data
+                        .options
+                        .iter()
+                        .filter_map(|option| {
+                            match (option.name.as_str(), option.resolved.as_ref()) {
+                                ("요약끄기", Some(CommandDataOptionValue::Boolean(true))) => {
+                                    Some(None)
+                                }
+                                ("요약채널", Some(CommandDataOptionValue::Channel(channel))) => {
+                                    Some(Some(channel.id.0 as i64))
+                                }
+                                _ => None,
+                            }
+                        })
+                        .min();
+
+                    let result = match self
+                        .nalgang
+                        .setting(nalgang_member.gid, timezone, day_start_hour)
+                        .await
+                    {
+                        Ok(boundary) => self
+                            .nalgang
+                            .hide_departed(nalgang_member.gid, hide_departed)
+                            .await
+                            .map(|hide_departed| (boundary, hide_departed)),
+                        Err(e) => Err(e),
+                    };
+                    let result = match result {
+                        Ok((boundary, hide_departed)) => self
+                            .nalgang
+                            .summary_channel(nalgang_member.gid, summary_channel)
+                            .await
+                            .map(|summary_channel| (boundary, hide_departed, summary_channel)),
+                        Err(e) => Err(e),
+                    };
+                    let content = match result {
+                        Ok((boundary, hide_departed, summary_channel)) => Ok(format!(
+                            "시간대는 {}, 하루의 시작은 {}시입니다. 탈퇴한 사용자를 랭킹에서 {}. {}",
+                            boundary.timezone,
+                            boundary.day_start_hour,
+                            if hide_departed { "숨깁니다" } else { "보여줍니다" },
+                            match summary_channel {
+                                Some(channel_id) => format!("전날의 요약을 <#{}>에 올립니다.", channel_id),
+                                None => "전날의 요약은 올리지 않습니다.".to_string(),
+                            }
+                        )),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::InvalidTimezone => Ok(
+                                "올바르지 않은 시간대입니다. Asia/Seoul 또는 +09:00 형식으로 입력해주세요."
+                                    .to_string(),
+                            ),
+                            NalgangErrorInner::InvalidDayStartHour => {
+                                Ok("시작시각은 0에서 23 사이여야 합니다.".to_string())
+                            }
+                            _ => Err(e),
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "알림" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let content = match subcommand.name.as_str() {
+                        "설정" => {
+                            let option_str = |name: &str| {
+                                subcommand
+                                    .options
+                                    .iter()
+                                    .find(|option| option.name == name)
+                                    .and_then(|option| match option.resolved.as_ref() {
+                                        Some(CommandDataOptionValue::String(s)) => Some(s.as_str()),
+                                        _ => None,
+                                    })
+                            };
+                            let delivery = option_str("방식")
+                                .and_then(ReminderDelivery::from_kind)
+                                .unwrap_or(ReminderDelivery::DirectMessage);
+                            match self
+                                .nalgang
+                                .set_reminder(
+                                    &mut nalgang_member,
+                                    option_str("시각").unwrap_or_default(),
+                                    delivery,
+                                )
+                                .await
+                            {
+                                Ok(remind_time) => Ok(format!(
+                                    "오늘 날갱하지 않아 연속 출석이 끊길 것 같으면 {}에 {}(으)로 알려드립니다.",
+                                    remind_time, delivery
+                                )),
+                                Err(e) => match e.kind {
+                                    NalgangErrorInner::InvalidRemindTime => {
+                                        Ok("시각은 21:30 형식으로 입력해주세요.".to_string())
+                                    }
+                                    NalgangErrorInner::MemberNotExist => {
+                                        Ok("등록되지 않은 계정입니다.".to_string())
+                                    }
+                                    _ => Err(e),
+                                },
+                            }
+                        }
+                        "끄기" => {
+                            self.nalgang
+                                .delete_reminder(&nalgang_member)
+                                .await
+                                .map(|deleted| match deleted {
+                                    true => "알림을 껐습니다.".to_string(),
+                                    false => "설정된 알림이 없습니다.".to_string(),
+                                })
+                        }
+                        _ => unreachable!(),
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "알림설정" => {
+                    let option_value = |name: &str| {
+                        command
+                            .data
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| option.resolved.as_ref())
+                    };
+                    let enabled = match option_value("사용") {
+                        Some(CommandDataOptionValue::Boolean(b)) => Some(*b),
+                        _ => None,
+                    };
+                    // Removing the channel takes precedence over a new channel.
+                    let channel_id = match (option_value("채널해제"), option_value("채널")) {
+                        (Some(CommandDataOptionValue::Boolean(true)), _) => Some(None),
+                        (_, Some(CommandDataOptionValue::Channel(channel))) => {
+                            Some(Some(channel.id.0 as i64))
+                        }
+                        _ => None,
+                    };
+                    let min_combo = match option_value("기준") {
+                        Some(CommandDataOptionValue::Integer(i)) => Some(*i),
+                        _ => None,
+                    };
+
+                    let content = match self
+                        .nalgang
+                        .reminder_setting(nalgang_member.gid, enabled, channel_id, min_combo)
+                        .await
+                    {
+                        Ok(setting) if setting.enabled => Ok(format!(
+                            "{}일 이상 연속 출석한 사용자에게 알림을 보냅니다. {}",
+                            setting.min_combo,
+                            match setting.channel_id {
+                                Some(channel_id) => {
+                                    format!("채널 멘션은 <#{}>에 올립니다.", channel_id)
+                                }
+                                None => "채널이 없어 모든 알림을 DM으로 보냅니다.".to_string(),
+                            }
+                        )),
+                        Ok(_) => Ok("연속 출석 알림을 사용하지 않습니다.".to_string()),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::InvalidReminderMinCombo => {
+                                Ok("기준은 1일 이상이어야 합니다.".to_string())
+                            }
+                            _ => Err(e),
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "점수규칙" => {
+                    let subcommand = command.data.options.first().expect("Expected subcommand");
+                    let option_value = |name: &str| {
+                        subcommand
+                            .options
+                            .iter()
+                            .find(|option| option.name == name)
+                            .and_then(|option| option.resolved.as_ref())
+                    };
+                    let milestone = || match (option_value("종류"), option_value("일수")) {
+                        (
+                            Some(CommandDataOptionValue::String(kind)),
+                            Some(CommandDataOptionValue::Integer(days)),
+                        ) => ComboMilestone::from_kind(kind, *days),
+                        _ => None,
+                    };
+                    let point = match option_value("점수") {
+                        Some(CommandDataOptionValue::Integer(i)) => Some(*i),
+                        _ => None,
+                    };
+
+                    let change = match subcommand.name.as_str() {
+                        "순위점수" => match option_value("점수") {
+                            Some(CommandDataOptionValue::String(s)) => s
+                                .parse::<RankPoints>()
+                                .ok()
+                                .map(ScoringRuleChange::RankPoints),
+                            _ => None,
+                        },
+                        "연속보너스" => milestone()
+                            .zip(point.filter(|point| *point >= 0))
+                            .map(|(milestone, point)| {
+                                ScoringRuleChange::SetComboBonus(milestone, point)
+                            }),
+                        "연속보너스삭제" => {
+                            milestone().map(ScoringRuleChange::RemoveComboBonus)
+                        }
+                        "1년보너스" => point
+                            .filter(|point| *point >= 0)
+                            .map(ScoringRuleChange::AnniversaryBonus),
+                        "휴가" => {
+                            let value = |name: &str| match option_value(name) {
+                                Some(CommandDataOptionValue::Integer(i)) => Some(*i),
+                                _ => None,
+                            };
+                            Some(ScoringRuleChange::Freeze {
+                                price: value("가격"),
+                                every: value("주기"),
+                                max: value("최대"),
+                            })
+                        }
+                        "초기화" => Some(ScoringRuleChange::Reset),
+                        _ => Some(ScoringRuleChange::Show),
+                    };
+
+                    let result = match change {
+                        Some(change) => self.nalgang.scoring_rule(nalgang_member.gid, change).await,
+                        None => Err(nalgang_error!(NalgangErrorInner::InvalidScoringRule)),
+                    };
+                    let content = match result {
+                        Ok(rule) => Ok(rule.to_string()),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::InvalidScoringRule => {
+                                Ok("올바르지 않은 점수 규칙입니다.".to_string())
+                            }
+                            NalgangErrorInner::ComboBonusNotExist => {
+                                Ok("해당 연속 출석 보너스가 없습니다.".to_string())
+                            }
+                            _ => Err(e),
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "토큰발급" => {
+                    let content = match self.nalgang.issue_token(&nalgang_member).await {
+                        Ok(token) => Ok(format!("토큰이 발급되었습니다: {}", token)),
+                        Err(e) => match e.kind {
+                            NalgangErrorInner::DuplidateTokenIssue => {
+                                Ok("이미 발급된 토큰이 존재합니다.".to_string())
+                            }
+                            _ => Err(e),
+                        },
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                "토큰삭제" => {
+                    let content = match self.nalgang.delete_token(&nalgang_member).await {
+                        Ok(b) => {
+                            if b {
+                                Ok("토큰이 삭제되었습니다.".to_string())
+                            } else {
+                                Ok("발급된 토큰이 없습니다.".to_string())
+                            }
+                        }
+                        Err(e) => Err(e),
+                    };
+                    self.simple_response(&ctx, &command, content, true).await;
+                }
+                _ => {
+                    self.simple_response(
+                        &ctx,
+                        &command,
+                        Ok("개발 중인 기능입니다.".to_string()),
+                        false,
+                    )
+                    .await;
+                }
+            };
+        } else if let Interaction::MessageComponent(component) = interaction {
+            if component.data.custom_id.starts_with("history_") {
+                self.history_component(&ctx, &component).await;
+            } else {
+                self.ranking_component(&ctx, &component).await;
+            }
+        }
+    }
+
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        println!("{} is connected!", ready.user.name);
+
+        let _commands = Command::set_global_application_commands(
             &ctx.http,
             |commands: &mut CreateApplicationCommands| {
                 commands
@@ -709,13 +2026,63 @@ impl EventHandler for Handler {
                                     .required(false)
                             })
                     })
+                    .create_application_command(|command| {
+                        command
+                            .name("기록")
+                            .description("날갱 기록과 출석 달력을 확인합니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("이름")
+                                    .description("기록을 확인하고 싶은 계정을 입력해주세요.")
+                                    .kind(CommandOptionType::User)
+                                    .required(false)
+                            })
+                    })
                     .create_application_command(|command| {
                         command
                             .name("서버등록")
                             .description("서버를 날갱 시스템에 등록합니다.")
                     })
                     .create_application_command(|command| {
-                        command.name("랭킹").description("순위를 확인합니다.")
+                        command
+                            .name("랭킹")
+                            .description("순위를 확인합니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("종류")
+                                    .description("무엇으로 순위를 매길지 선택해주세요.")
+                                    .kind(CommandOptionType::String)
+                                    .required(false);
+                                for kind in RankingKind::ALL {
+                                    option.add_string_choice(kind, kind);
+                                }
+                                option
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("기간")
+                                    .description("이 기간의 기록으로 순위를 매깁니다.")
+                                    .kind(CommandOptionType::String)
+                                    .add_string_choice("이번주", "이번주")
+                                    .add_string_choice("이번달", "이번달")
+                                    .add_string_choice("올해", "올해")
+                                    .add_string_choice("전체", "전체")
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("시작")
+                                    .description("직접 지정할 기간의 첫 날을 2024-01-01 형식으로 입력해주세요.")
+                                    .kind(CommandOptionType::String)
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("끝")
+                                    .description("직접 지정할 기간의 마지막 날을 2024-01-01 형식으로 입력해주세요.")
+                                    .kind(CommandOptionType::String)
+                                    .required(false)
+                            })
                     })
                     .create_application_command(|command| {
                         command
@@ -728,6 +2095,640 @@ impl EventHandler for Handler {
                                     .kind(CommandOptionType::User)
                                     .required(true)
                             })
+                            .create_option(|option| {
+                                option
+                                    .name("점수")
+                                    .description("보낼 점수를 입력해주세요.")
+                                    .kind(CommandOptionType::Integer)
+                                    .min_int_value(1)
+                                    .required(true)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("휴가")
+                            .description("날갱하지 못한 날에 연속 출석을 지켜주는 휴가를 사거나 확인합니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("구매")
+                                    .description("점수로 휴가를 삽니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("개수")
+                                            .description("살 휴가 수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .required(false)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("확인")
+                                    .description("가지고 있는 휴가와 휴가 규칙을 확인합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("상점")
+                            .description("날갱점수로 서버 상점의 물건을 삽니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("목록")
+                                    .description("상점에서 파는 물건을 확인합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("구매")
+                                    .description("상점의 물건을 삽니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("이름")
+                                            .description("살 물건의 이름을 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("상점관리")
+                            .description("서버 상점의 물건을 올리거나 내립니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("추가")
+                                    .description("물건을 상점에 올리거나 같은 이름의 물건을 바꿉니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("이름")
+                                            .description("물건의 이름을 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("가격")
+                                            .description("물건의 점수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("재고")
+                                            .description("팔 개수를 입력해주세요. 없으면 무제한입니다.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(false)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("역할")
+                                            .description("산 사람에게 줄 역할을 선택해주세요.")
+                                            .kind(CommandOptionType::Role)
+                                            .required(false)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("기간")
+                                            .description("산 물건을 사용할 수 있는 일수를 입력해주세요. 없으면 영구적입니다.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .max_int_value(MAX_ITEM_DURATION)
+                                            .required(false)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("삭제")
+                                    .description("물건을 상점에서 내립니다. 이미 산 물건은 그대로 남습니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("이름")
+                                            .description("내릴 물건의 이름을 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("인벤토리")
+                            .description("상점에서 산 물건을 확인합니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("이름")
+                                    .description("인벤토리를 확인할 계정을 입력해주세요.")
+                                    .kind(CommandOptionType::User)
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("역할보상")
+                            .description("점수나 연속 출석 일수에 따라 주는 역할을 관리합니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("목록")
+                                    .description("역할 보상을 확인합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("추가")
+                                    .description("역할 보상을 추가하거나 같은 역할의 보상을 바꿉니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("역할")
+                                            .description("줄 역할을 선택해주세요.")
+                                            .kind(CommandOptionType::Role)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("종류")
+                                            .description("역할을 주는 조건을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("점수", "score")
+                                            .add_string_choice("연속 출석", "combo")
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("기준")
+                                            .description("역할을 받는 최소 점수나 연속 출석 일수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("회수")
+                                            .description("조건을 더 이상 만족하지 않으면 역할을 회수할지 선택해주세요.")
+                                            .kind(CommandOptionType::Boolean)
+                                            .required(false)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("삭제")
+                                    .description("역할 보상을 삭제합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("역할")
+                                            .description("삭제할 역할을 선택해주세요.")
+                                            .kind(CommandOptionType::Role)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("동기화")
+                                    .description("서버의 모든 멤버에게 역할 보상을 다시 적용합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("업적")
+                            .description("날갱으로 얻은 업적을 확인합니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("이름")
+                                    .description("업적을 확인할 계정을 입력해주세요.")
+                                    .kind(CommandOptionType::User)
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("업적재계산")
+                            .description("지금까지의 날갱 기록으로 받지 못한 업적을 줍니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("점수조정")
+                            .description("멤버의 점수나 연속 출석 일수를 바꾸고 감사 로그에 남깁니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("더하기")
+                                    .description("점수나 연속 출석 일수를 더합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("이름")
+                                            .description("바꿀 계정을 입력해주세요.")
+                                            .kind(CommandOptionType::User)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("항목")
+                                            .description("바꿀 항목을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("점수", "score")
+                                            .add_string_choice("연속 출석", "combo")
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("값")
+                                            .description("더할 값을 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .max_int_value(ADJUSTMENT_MAX_VALUE)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("사유")
+                                            .description("감사 로그에 남길 사유를 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("빼기")
+                                    .description("점수나 연속 출석 일수를 뺍니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("이름")
+                                            .description("바꿀 계정을 입력해주세요.")
+                                            .kind(CommandOptionType::User)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("항목")
+                                            .description("바꿀 항목을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("점수", "score")
+                                            .add_string_choice("연속 출석", "combo")
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("값")
+                                            .description("뺄 값을 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .max_int_value(ADJUSTMENT_MAX_VALUE)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("사유")
+                                            .description("감사 로그에 남길 사유를 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("설정")
+                                    .description("점수나 연속 출석 일수를 정합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("이름")
+                                            .description("바꿀 계정을 입력해주세요.")
+                                            .kind(CommandOptionType::User)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("항목")
+                                            .description("바꿀 항목을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("점수", "score")
+                                            .add_string_choice("연속 출석", "combo")
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("값")
+                                            .description("새 값을 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(-ADJUSTMENT_MAX_VALUE)
+                                            .max_int_value(ADJUSTMENT_MAX_VALUE)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("사유")
+                                            .description("감사 로그에 남길 사유를 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("감사로그")
+                            .description("관리자가 바꾼 점수와 연속 출석 기록을 최근 순으로 확인합니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("개수")
+                                    .description("확인할 기록 수를 입력해주세요.")
+                                    .kind(CommandOptionType::Integer)
+                                    .min_int_value(1)
+                                    .max_int_value(AUDIT_LOG_MAX_SIZE)
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("내보내기")
+                            .description("서버의 멤버, 날갱 기록과 설정을 백업 파일로 내보냅니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("형식")
+                                    .description("파일 형식을 선택해주세요. 기본값은 JSON입니다.")
+                                    .kind(CommandOptionType::String)
+                                    .add_string_choice("JSON", "json")
+                                    .add_string_choice("CSV", "csv")
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("가져오기")
+                            .description("백업 파일의 멤버, 날갱 기록과 설정을 서버로 가져옵니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("파일")
+                                    .description("/내보내기 로 받은 JSON이나 CSV 파일을 올려주세요.")
+                                    .kind(CommandOptionType::Attachment)
+                                    .required(true)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("정책")
+                                    .description("이미 등록된 멤버를 어떻게 할지 선택해주세요. 기본값은 건너뛰기입니다.")
+                                    .kind(CommandOptionType::String)
+                                    .add_string_choice("건너뛰기", "skip")
+                                    .add_string_choice("덮어쓰기", "overwrite")
+                                    .add_string_choice("점수 합치기", "merge")
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("설정")
+                            .description("서버의 시간대, 하루의 시작 시각, 랭킹 표시 방식과 요약 채널을 설정합니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("시간대")
+                                    .description("Asia/Seoul 같은 시간대 이름이나 +09:00 같은 UTC 오프셋을 입력해주세요.")
+                                    .kind(CommandOptionType::String)
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("시작시각")
+                                    .description("날갱 하루가 시작되는 시각을 입력해주세요.")
+                                    .kind(CommandOptionType::Integer)
+                                    .min_int_value(0)
+                                    .max_int_value(23)
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("탈퇴자숨김")
+                                    .description("서버를 떠난 사용자를 랭킹에서 숨길지 선택해주세요.")
+                                    .kind(CommandOptionType::Boolean)
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("요약채널")
+                                    .description("하루가 지나면 전날의 날갱 요약을 올릴 채널을 선택해주세요.")
+                                    .kind(CommandOptionType::Channel)
+                                    .channel_types(&[ChannelType::Text])
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("요약끄기")
+                                    .description("전날의 날갱 요약을 더 이상 올리지 않습니다.")
+                                    .kind(CommandOptionType::Boolean)
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("알림")
+                            .description("연속 출석이 끊기기 전에 알림을 받습니다.")
+                            .create_option(|option| {
+                                option
+                                    .name("설정")
+                                    .description("알림을 받을 시각과 방식을 설정합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("시각")
+                                            .description("서버 시간대 기준으로 21:30 형식으로 입력해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("방식")
+                                            .description("알림을 받을 방식을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("DM", "dm")
+                                            .add_string_choice("채널 멘션", "channel")
+                                            .required(false)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("끄기")
+                                    .description("알림을 더 이상 받지 않습니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("알림설정")
+                            .description("서버의 연속 출석 알림을 설정합니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("사용")
+                                    .description("연속 출석 알림을 사용할지 선택해주세요.")
+                                    .kind(CommandOptionType::Boolean)
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("채널")
+                                    .description("채널 멘션 알림을 올릴 채널을 선택해주세요.")
+                                    .kind(CommandOptionType::Channel)
+                                    .channel_types(&[ChannelType::Text])
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("채널해제")
+                                    .description("채널 멘션 알림을 DM으로 대신 보냅니다.")
+                                    .kind(CommandOptionType::Boolean)
+                                    .required(false)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("기준")
+                                    .description("알림을 보낼 최소 연속 출석 일수를 입력해주세요.")
+                                    .kind(CommandOptionType::Integer)
+                                    .min_int_value(1)
+                                    .required(false)
+                            })
+                    })
+                    .create_application_command(|command| {
+                        command
+                            .name("점수규칙")
+                            .description("서버의 날갱 점수 규칙을 확인하거나 변경합니다.")
+                            .default_member_permissions(Permissions::ADMINISTRATOR)
+                            .create_option(|option| {
+                                option
+                                    .name("보기")
+                                    .description("현재 점수 규칙을 확인합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("순위점수")
+                                    .description("날갱 순위별 점수를 설정합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("점수")
+                                            .description("1등부터 순서대로 쉼표로 구분해 입력해주세요. 예: 10,5,3,1")
+                                            .kind(CommandOptionType::String)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("연속보너스")
+                                    .description("연속 출석 보너스를 추가하거나 변경합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("종류")
+                                            .description("보너스를 받는 조건을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("주기", "modulo")
+                                            .add_string_choice("정확히", "exact")
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("일수")
+                                            .description("연속 출석 일수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("점수")
+                                            .description("보너스 점수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("연속보너스삭제")
+                                    .description("연속 출석 보너스를 삭제합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("종류")
+                                            .description("보너스를 받는 조건을 선택해주세요.")
+                                            .kind(CommandOptionType::String)
+                                            .add_string_choice("주기", "modulo")
+                                            .add_string_choice("정확히", "exact")
+                                            .required(true)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("일수")
+                                            .description("연속 출석 일수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(1)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("1년보너스")
+                                    .description("연속 출석 1년마다 받는 보너스를 설정합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("점수")
+                                            .description("보너스 점수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(true)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("휴가")
+                                    .description("휴가 가격과 지급 규칙을 설정합니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("가격")
+                                            .description("휴가 1개의 점수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(false)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("주기")
+                                            .description("연속 출석 며칠마다 휴가를 줄지 입력해주세요. 0이면 주지 않습니다.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(false)
+                                    })
+                                    .create_sub_option(|option| {
+                                        option
+                                            .name("최대")
+                                            .description("한 사람이 가질 수 있는 휴가 수를 입력해주세요.")
+                                            .kind(CommandOptionType::Integer)
+                                            .min_int_value(0)
+                                            .required(false)
+                                    })
+                            })
+                            .create_option(|option| {
+                                option
+                                    .name("초기화")
+                                    .description("점수 규칙을 기본값으로 되돌립니다.")
+                                    .kind(CommandOptionType::SubCommand)
+                            })
                     })
                     .create_application_command(|command| {
                         command
@@ -752,19 +2753,40 @@ impl EventHandler for Handler {
 async fn main() {
     dotenv::dotenv().expect("Failed to read .env file");
 
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
-    let database = sqlx::sqlite::SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect_with(sqlx::sqlite::SqliteConnectOptions::new().filename("database.sqlite"))
+    let database_url =
+        env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:database.sqlite".to_string());
+    let storage = DatabaseStorage::connect(&database_url)
         .await
         .expect("Couldn't connect to database");
-
-    sqlx::migrate!("./migrations")
-        .run(&database)
+    storage
+        .migrate()
         .await
         .expect("Couldn't run database migrations");
 
-    let handler = Handler { database };
+    let handler = Handler {
+        nalgang: Nalgang::new(storage, SystemClock),
+    };
+
+    // Subcommands run on the database and exit without starting the bot.
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        match cli::run(&handler.nalgang, &args).await {
+            Ok(message) => println!("{}", message),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
+
+    let api_address = env::var("API_ADDRESS")
+        .unwrap_or_else(|_| "127.0.0.1:8080".to_string())
+        .parse()
+        .expect("API address is not a valid socket address");
+    tokio::spawn(api::serve(handler.clone(), api_address));
 
     let application_id: u64 = env::var("APPLICATION_ID")
         .expect("Expected an application id in the environment")
@@ -774,14 +2796,18 @@ async fn main() {
     let intents = GatewayIntents::GUILDS
         | GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
+        | GatewayIntents::MESSAGE_CONTENT
+        | GatewayIntents::GUILD_MEMBERS;
 
+    let nalgang = handler.nalgang.clone();
     let mut client = Client::builder(token, intents)
         .event_handler(handler)
         .application_id(application_id)
         .await
         .expect("Error creating client");
 
+    tokio::spawn(scheduler::run(client.cache_and_http.http.clone(), nalgang));
+
     if let Err(why) = client.start().await {
         println!("Client error: {:?}", why);
     }
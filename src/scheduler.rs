@@ -0,0 +1,346 @@
+use std::fmt::Write as FmtWrite;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nalgang_core::{
+    nalgang_error,
+    reminder::{DueReminder, ReminderDelivery},
+    summary::DailySummary,
+    Clock, Nalgang, NalgangError, NalgangErrorInner, Storage,
+};
+use serenity::http::Http;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+
+// Day boundaries are checked this often, so a day starts at most this late.
+const TICK: Duration = Duration::from_secs(60);
+
+// Starts the attendance day of every guild at its day boundary, posts the summary of the
+// previous day to the guilds which set a summary channel, sends the due streak reminders and takes
+// back the roles of expired shop items.
+pub async fn run<S: Storage, C: Clock>(http: Arc<Http>, nalgang: Nalgang<S, C>) {
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+
+        let guilds = match nalgang.guilds().await {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        for gid in guilds {
+            match rollover_summary(&nalgang, gid).await {
+                Ok(Some((channel_id, summary))) => post_summary(&http, channel_id, &summary).await,
+                Ok(None) => (),
+                Err(e) => println!("{}", e),
+            }
+            match nalgang.due_reminders(gid).await {
+                Ok(reminders) if reminders.is_empty() => (),
+                Ok(reminders) => send_reminders(&http, &nalgang, gid, &reminders).await,
+                Err(e) => println!("{}", e),
+            }
+            match nalgang.expire_items(gid).await {
+                Ok(roles) => {
+                    for role in roles {
+                        if let Err(why) = http
+                            .remove_member_role(
+                                gid as u64,
+                                role.user_id as u64,
+                                role.role_id as u64,
+                                Some("날갱 상점 물건 만료"),
+                            )
+                            .await
+                        {
+                            println!("Cannot remove role: {}", why);
+                        }
+                    }
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+}
+
+// Discord limits of an embed description and field value, in characters
+const DESCRIPTION_LIMIT: usize = 4096;
+const FIELD_LIMIT: usize = 1024;
+
+// Drops the lines which do not fit in `limit` characters.
+fn fit_lines(content: String, limit: usize) -> String {
+    if content.chars().count() <= limit {
+        return content;
+    }
+    let mut fitted = String::new();
+    for line in content.lines() {
+        // Leaves room for the line and the trailing "…".
+        if fitted.chars().count() + line.chars().count() + 2 > limit {
+            break;
+        }
+        fitted.push_str(line);
+        fitted.push('\n');
+    }
+    fitted.push('…');
+    fitted
+}
+
+// Lines of the summary sections, each empty if there is nothing to tell.
+struct SummaryContent {
+    attendances: String,
+    combo_rewards: String,
+    broken_streaks: String,
+    rank_changes: String,
+}
+
+// Mentions in embeds do not notify, and need no guild cache to show the name.
+fn summary_content(summary: &DailySummary) -> Result<SummaryContent, NalgangError> {
+    let mut attendances = String::new();
+    for (rank, entry) in summary.attendances.iter().enumerate() {
+        write!(&mut attendances, "{}. <@{}>", rank + 1, entry.user_id)
+            .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+        match entry.hit_message.as_deref() {
+            Some(message) if !message.is_empty() => writeln!(&mut attendances, ": {}", message),
+            _ => writeln!(&mut attendances),
+        }
+        .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+    }
+
+    let mut combo_rewards = String::new();
+    for reward in summary.combo_rewards.iter() {
+        writeln!(
+            &mut combo_rewards,
+            "<@{}> {}일 연속 +{}점",
+            reward.user_id, reward.combo, reward.bonus
+        )
+        .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+    }
+
+    let mut broken_streaks = String::new();
+    for streak in summary.broken_streaks.iter() {
+        writeln!(
+            &mut broken_streaks,
+            "<@{}> {}일 연속",
+            streak.user_id, streak.combo
+        )
+        .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+    }
+
+    let mut rank_changes = String::new();
+    for change in summary.rank_changes.iter() {
+        match change.before {
+            Some(before) => writeln!(
+                &mut rank_changes,
+                "{} <@{}> {}위 → {}위",
+                if change.after < before { "▲" } else { "▼" },
+                change.user_id,
+                before + 1,
+                change.after + 1
+            ),
+            None => writeln!(
+                &mut rank_changes,
+                "🆕 <@{}> {}위",
+                change.user_id,
+                change.after + 1
+            ),
+        }
+        .map_err(|e| nalgang_error!(NalgangErrorInner::BufferError(e)))?;
+    }
+
+    Ok(SummaryContent {
+        attendances,
+        combo_rewards,
+        broken_streaks,
+        rank_changes,
+    })
+}
+
+// Starts the attendance day of the guild once its day boundary passed. Returns the summary of the
+// previous day and the channel to post it in, if the guild set one and the day has anything to tell.
+async fn rollover_summary<S: Storage, C: Clock>(
+    nalgang: &Nalgang<S, C>,
+    gid: i64,
+) -> Result<Option<(ChannelId, DailySummary)>, NalgangError> {
+    let rollover = match nalgang.day_rollover(gid).await? {
+        Some(rollover) => rollover,
+        None => return Ok(None),
+    };
+    let channel_id = match nalgang.summary_channel(gid, None).await? {
+        Some(channel_id) => ChannelId(channel_id as u64),
+        None => return Ok(None),
+    };
+    let summary = nalgang.daily_summary(rollover).await?;
+    match summary.is_empty() {
+        true => Ok(None),
+        false => Ok(Some((channel_id, summary))),
+    }
+}
+
+async fn post_summary(http: &Http, channel_id: ChannelId, summary: &DailySummary) {
+    let content = match summary_content(summary) {
+        Ok(content) => content,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    if let Err(why) = channel_id
+        .send_message(http, |message| {
+            message.embed(|embed| {
+                embed.title(format!(
+                    "{} 날갱 요약",
+                    summary.date.format("%Y년 %m월 %d일")
+                ));
+                if content.attendances.is_empty() {
+                    embed.description("날갱한 사람이 없습니다.");
+                } else {
+                    embed.description(fit_lines(content.attendances, DESCRIPTION_LIMIT));
+                }
+                let fields = [
+                    ("연속 출석 보너스", content.combo_rewards),
+                    ("끊긴 연속 출석", content.broken_streaks),
+                    ("랭킹 변화", content.rank_changes),
+                ];
+                for (name, value) in fields {
+                    if !value.is_empty() {
+                        embed.field(name, fit_lines(value, FIELD_LIMIT), false);
+                    }
+                }
+                embed
+            })
+        })
+        .await
+    {
+        println!("Cannot post daily summary: {}", why);
+    }
+}
+
+async fn send_reminders<S: Storage, C: Clock>(
+    http: &Http,
+    nalgang: &Nalgang<S, C>,
+    gid: i64,
+    reminders: &[DueReminder],
+) {
+    let channel_id = match nalgang.reminder_setting(gid, None, None, None).await {
+        Ok(setting) => setting
+            .channel_id
+            .map(|channel_id| ChannelId(channel_id as u64)),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    };
+
+    let (mentions, direct_messages): (Vec<&DueReminder>, Vec<&DueReminder>) =
+        reminders.iter().partition(|reminder| {
+            reminder.delivery == ReminderDelivery::Channel && channel_id.is_some()
+        });
+
+    if let (Some(channel_id), false) = (channel_id, mentions.is_empty()) {
+        let mut content =
+            String::from("아직 오늘 날갱하지 않았어요! 연속 출석이 끊기기 전에 날갱해주세요.\n");
+        for reminder in mentions {
+            content.push_str(&format!(
+                "<@{}> {}일 연속\n",
+                reminder.user_id, reminder.combo
+            ));
+        }
+        if let Err(why) = channel_id.say(http, content).await {
+            println!("Cannot send reminder: {}", why);
+        }
+    }
+
+    if direct_messages.is_empty() {
+        return;
+    }
+    let guild_name = match GuildId(gid as u64).to_partial_guild(http).await {
+        Ok(guild) => guild.name,
+        Err(why) => {
+            println!("Cannot get guild: {}", why);
+            return;
+        }
+    };
+    for reminder in direct_messages {
+        let content = format!(
+            "{} 서버에서 아직 오늘 날갱하지 않았어요! {}일 연속 출석이 끊기기 전에 날갱해주세요.",
+            guild_name, reminder.combo
+        );
+        let result = match UserId(reminder.user_id as u64)
+            .create_dm_channel(http)
+            .await
+        {
+            Ok(channel) => channel.say(http, content).await.map(|_| ()),
+            Err(why) => Err(why),
+        };
+        if let Err(why) = result {
+            println!("Cannot send reminder: {}", why);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use nalgang_core::{DatabaseStorage, NalgangMember};
+
+    // Monday 09:00 in Asia/Seoul, 3 hours after the default day start
+    const T0: i64 = 1704067200;
+    const DAY: i64 = 86400;
+    const GID: i64 = 1;
+
+    #[derive(Clone)]
+    struct TestClock(Arc<AtomicI64>);
+
+    impl Clock for TestClock {
+        fn now(&self) -> i64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn rollover_posts_summary_once() {
+        let path =
+            std::env::temp_dir().join(format!("nalgang-scheduler-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let storage = DatabaseStorage::connect(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        storage.migrate().await.unwrap();
+        let time = Arc::new(AtomicI64::new(T0));
+        let nalgang = Nalgang::new(storage, TestClock(time.clone()));
+
+        nalgang.register_guild(GID).await.ok().unwrap();
+        nalgang
+            .summary_channel(GID, Some(Some(5)))
+            .await
+            .ok()
+            .unwrap();
+        for uid in [10, 11] {
+            let mut member = NalgangMember::new(uid, GID);
+            nalgang.register(&mut member).await.ok().unwrap();
+            nalgang.attend(&mut member, "hello").await.ok().unwrap();
+        }
+        assert!(rollover_summary(&nalgang, GID)
+            .await
+            .ok()
+            .unwrap()
+            .is_none());
+
+        time.store(T0 + DAY, Ordering::SeqCst);
+        let (channel_id, summary) = rollover_summary(&nalgang, GID).await.ok().unwrap().unwrap();
+        assert_eq!(channel_id, ChannelId(5));
+        assert_eq!(summary.date.to_string(), "2024-01-01");
+        let content = summary_content(&summary).ok().unwrap();
+        assert_eq!(content.attendances, "1. <@10>: hello\n2. <@11>: hello\n");
+        // The day already started.
+        assert!(rollover_summary(&nalgang, GID)
+            .await
+            .ok()
+            .unwrap()
+            .is_none());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}